@@ -1,5 +1,5 @@
-//! Takes 2 audio inputs and outputs them to 2 audio outputs.
-//! All JACK notifications are also printed out.
+//! Loads the JACK profiler internal client until enter/return is pressed. See
+//! `internal_client_plugin.rs` for writing an internal client in Rust.
 use std::io;
 
 fn main() {
@@ -0,0 +1,40 @@
+//! An internal client that copies 2 audio inputs to 2 audio outputs, running inside the JACK
+//! server.
+//!
+//! Build it with `cargo build --example internal_client_plugin`, then load the resulting shared
+//! object with `jack_load rust_passthrough /path/to/libinternal_client_plugin.so`, or with
+//! `jack::InternalClientOptions`. Inside the server, the plugin calls the JACK server library
+//! that jackd already loaded, see `jack_sys::JACK_SERVER_LIB`.
+
+struct Passthrough {
+    in_a: jack::Port<jack::AudioIn>,
+    in_b: jack::Port<jack::AudioIn>,
+    out_a: jack::Port<jack::AudioOut>,
+    out_b: jack::Port<jack::AudioOut>,
+}
+
+impl jack::ProcessHandler for Passthrough {
+    fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        self.out_a
+            .as_mut_slice(ps)
+            .clone_from_slice(self.in_a.as_slice(ps));
+        self.out_b
+            .as_mut_slice(ps)
+            .clone_from_slice(self.in_b.as_slice(ps));
+        jack::Control::Continue
+    }
+}
+
+fn init(client: &jack::Client, _load_init: &str) -> Result<((), Passthrough), jack::Error> {
+    Ok((
+        (),
+        Passthrough {
+            in_a: client.register_port("in_1", jack::AudioIn::default())?,
+            in_b: client.register_port("in_2", jack::AudioIn::default())?,
+            out_a: client.register_port("out_1", jack::AudioOut::default())?,
+            out_b: client.register_port("out_2", jack::AudioOut::default())?,
+        },
+    ))
+}
+
+jack::internal_client!(init);
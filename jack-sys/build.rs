@@ -6,12 +6,27 @@ bitflags! {
     struct FunctionFlags: u8 {
         const NONE = 0b00000000;
         const WEAK = 0b00000001;
+        // Implemented by the in-process server of the `fake` feature.
+        const FAKE = 0b00000010;
+        // Uses types that only exist outside of Windows, such as `jack_native_thread_t`.
+        const NOT_WINDOWS = 0b00000100;
     }
 }
 
 fn main() {
     let out_dir = std::env::var_os("OUT_DIR").unwrap();
+    let dest_path = std::path::Path::new(&out_dir).join("functions.rs");
     let target_os = std::env::var("CARGO_CFG_TARGET_OS");
+    let is_windows = target_os.as_ref().map(|x| &**x) == Ok("windows");
+    let functions: Vec<&Function> = FUNCTIONS
+        .iter()
+        .filter(|f| !(is_windows && f.flags.contains(FunctionFlags::NOT_WINDOWS)))
+        .collect();
+    if std::env::var_os("CARGO_FEATURE_FAKE").is_some() {
+        write_fake_src(&dest_path, &functions);
+        println!("cargo:rerun-if-changed=build.rs");
+        return;
+    }
     match target_os.as_ref().map(|x| &**x) {
         Ok("linux") => {
             pkg_config::find_library("jack").unwrap();
@@ -20,12 +35,14 @@ fn main() {
             let _ = pkg_config::find_library("jack");
         },
     };
-    let dest_path = std::path::Path::new(&out_dir).join("functions.rs");
-    write_src(&dest_path, FUNCTIONS);
+    let fake_server = std::env::var_os("CARGO_FEATURE_FAKE_SERVER").is_some();
+    write_src(&dest_path, &functions, fake_server);
     println!("cargo:rerun-if-changed=build.rs");
 }
 
-fn write_src(path: &std::path::Path, fns: &[Function]) {
+/// Write functions that call into the JACK library. With `fake_server`, they call into the fake
+/// server in `src/fake` instead on threads that enabled it.
+fn write_src(path: &std::path::Path, fns: &[&Function], fake_server: bool) {
     let mut out = std::fs::File::create(path).unwrap();
     writeln!(out, "use crate::types::*;").unwrap();
     writeln!(out, "use lazy_static::lazy_static;").unwrap();
@@ -104,6 +121,9 @@ fn write_src(path: &std::path::Path, fns: &[Function]) {
                 f.args_full(),
                 f.ret
             ).unwrap();
+            if fake_server {
+                write_fake_dispatch(&mut out, f);
+            }
             writeln!(out, "    let f = FUNCTIONS.{}_impl?;", f.name).unwrap();
             writeln!(out, "    Some(f({}))", f.arg_names()).unwrap();
         } else {
@@ -113,6 +133,9 @@ fn write_src(path: &std::path::Path, fns: &[Function]) {
                 f.args_full(),
                 f.ret
             ).unwrap();
+            if fake_server {
+                write_fake_dispatch(&mut out, f);
+            }
             writeln!(out, "    let f = FUNCTIONS.{}_impl;", f.name).unwrap();
             writeln!(out, "    f({})", f.arg_names()).unwrap();
         }
@@ -120,6 +143,54 @@ fn write_src(path: &std::path::Path, fns: &[Function]) {
     }
 }
 
+/// Write functions that call into the fake server in `src/fake` instead of the JACK library.
+///
+/// Functions the fake does not implement panic, or return `None` if they are weak.
+fn write_fake_src(path: &std::path::Path, fns: &[&Function]) {
+    let mut out = std::fs::File::create(path).unwrap();
+    writeln!(out, "use crate::types::*;").unwrap();
+    for f in fns.iter() {
+        let is_weak = f.flags.contains(FunctionFlags::WEAK);
+        if !f.flags.contains(FunctionFlags::FAKE) {
+            writeln!(out, "#[allow(unused_variables)]").unwrap();
+        }
+        let ret = match is_weak {
+            true => format!("Option<{}>", f.ret),
+            false => f.ret.to_string(),
+        };
+        writeln!(out, "pub unsafe fn {}({}) -> {} {{", f.name, f.args_full(), ret).unwrap();
+        writeln!(out, "    {}", fake_call(f)).unwrap();
+        writeln!(out, "}}").unwrap();
+    }
+}
+
+/// Write the start of a function of `write_src` that calls into the fake server if the current
+/// thread enabled it.
+fn write_fake_dispatch(out: &mut std::fs::File, f: &Function) {
+    writeln!(out, "    if crate::fake::is_enabled() {{").unwrap();
+    match f.flags.contains(FunctionFlags::FAKE) || f.flags.contains(FunctionFlags::WEAK) {
+        true => writeln!(out, "        return {};", fake_call(f)),
+        false => writeln!(out, "        {};", fake_call(f)),
+    }
+    .unwrap();
+    writeln!(out, "    }}").unwrap();
+}
+
+/// The expression that calls `f` on the fake server.
+///
+/// Functions the fake does not implement panic, or return `None` if they are weak.
+fn fake_call(f: &Function) -> String {
+    match (f.flags.contains(FunctionFlags::FAKE), f.flags.contains(FunctionFlags::WEAK)) {
+        (true, true) => format!("Some(crate::fake::{}({}))", f.name, f.arg_names()),
+        (true, false) => format!("crate::fake::{}({})", f.name, f.arg_names()),
+        (false, true) => "None".to_string(),
+        (false, false) => format!(
+            "panic!(\"{} is not supported by the fake JACK server\")",
+            f.name
+        ),
+    }
+}
+
 struct Function {
     name: &'static str,
     args: &'static [(&'static str, &'static str)],
@@ -179,7 +250,7 @@ const FUNCTIONS: &[Function] = &[
         name: "jack_release_timebase",
         args: &[("client", "*mut jack_client_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_get_cycle_times",
@@ -191,7 +262,7 @@ const FUNCTIONS: &[Function] = &[
             ("period_usecs", "*mut ::libc::c_float"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::WEAK,
+        flags: FunctionFlags::WEAK.union(FunctionFlags::FAKE),
     },
     Function {
         name: "jack_set_sync_callback",
@@ -201,13 +272,13 @@ const FUNCTIONS: &[Function] = &[
             ("sync_arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_sync_timeout",
         args: &[("client", "*mut jack_client_t"), ("timeout", "jack_time_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_timebase_callback",
@@ -218,7 +289,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_transport_locate",
@@ -227,7 +298,7 @@ const FUNCTIONS: &[Function] = &[
             ("frame", "jack_nframes_t"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_transport_query",
@@ -236,13 +307,13 @@ const FUNCTIONS: &[Function] = &[
             ("pos", "*mut jack_position_t"),
         ],
         ret: "jack_transport_state_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_get_current_transport_frame",
         args: &[("client", "*const jack_client_t")],
         ret: "jack_nframes_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_transport_reposition",
@@ -251,19 +322,19 @@ const FUNCTIONS: &[Function] = &[
             ("pos", "*const jack_position_t"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_transport_start",
         args: &[("client", "*mut jack_client_t")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_transport_stop",
         args: &[("client", "*mut jack_client_t")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_get_transport_info",
@@ -293,7 +364,7 @@ const FUNCTIONS: &[Function] = &[
             // ("", "..."),
         ],
         ret: "*mut jack_client_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_client_new",
@@ -305,19 +376,19 @@ const FUNCTIONS: &[Function] = &[
         name: "jack_client_close",
         args: &[("client", "*mut jack_client_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_client_name_size",
         args: &[],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_get_client_name",
         args: &[("client", "*mut jack_client_t")],
         ret: "*mut ::libc::c_char",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_get_uuid_for_client_name",
@@ -326,7 +397,7 @@ const FUNCTIONS: &[Function] = &[
             ("client_name", "*const ::libc::c_char"),
         ],
         ret: "*mut ::libc::c_char",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_get_client_name_by_uuid",
@@ -335,7 +406,7 @@ const FUNCTIONS: &[Function] = &[
             ("client_uuid", "*const ::libc::c_char"),
         ],
         ret: "*mut ::libc::c_char",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_internal_client_new",
@@ -357,19 +428,19 @@ const FUNCTIONS: &[Function] = &[
         name: "jack_activate",
         args: &[("client", "*mut jack_client_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_deactivate",
         args: &[("client", "*mut jack_client_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_get_client_pid",
         args: &[("name", "*const ::libc::c_char")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::WEAK,
+        flags: FunctionFlags::WEAK.union(FunctionFlags::FAKE),
     },
     // #[cfg(not(target_os = "windows"))]
     // pub fn jack_client_thread_id(client: *mut jack_client_t) -> jack_native_thread_t;
@@ -377,7 +448,7 @@ const FUNCTIONS: &[Function] = &[
         name: "jack_is_realtime",
         args: &[("client", "*mut jack_client_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_thread_wait",
@@ -411,7 +482,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_thread_init_callback",
@@ -421,7 +492,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_on_shutdown",
@@ -431,7 +502,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_on_info_shutdown",
@@ -441,7 +512,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_process_callback",
@@ -451,7 +522,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_freewheel_callback",
@@ -461,7 +532,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_buffer_size_callback",
@@ -471,7 +542,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_get_sample_rate",
@@ -479,7 +550,7 @@ const FUNCTIONS: &[Function] = &[
             ("client", "*mut jack_client_t"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_sample_rate_callback",
@@ -489,7 +560,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_client_registration_callback",
@@ -499,7 +570,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_port_registration_callback",
@@ -509,7 +580,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_port_connect_callback",
@@ -519,7 +590,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_port_rename_callback",
@@ -529,7 +600,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::WEAK.union(FunctionFlags::FAKE),
     },
     Function {
         name: "jack_set_graph_order_callback",
@@ -539,7 +610,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_xrun_callback",
@@ -549,7 +620,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_latency_callback",
@@ -559,13 +630,13 @@ const FUNCTIONS: &[Function] = &[
             ("arg", "*mut ::libc::c_void"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_freewheel",
         args: &[("client", "*mut jack_client_t"), ("onoff", "::libc::c_int")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_set_buffer_size",
@@ -574,13 +645,13 @@ const FUNCTIONS: &[Function] = &[
             ("nframes", "jack_nframes_t"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_get_buffer_size",
         args: &[("client", "*mut jack_client_t")],
         ret: "jack_nframes_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_engine_takeover_timebase",
@@ -592,7 +663,7 @@ const FUNCTIONS: &[Function] = &[
         name: "jack_cpu_load",
         args: &[("client", "*mut jack_client_t")],
         ret: "::libc::c_float",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_register",
@@ -604,7 +675,7 @@ const FUNCTIONS: &[Function] = &[
             ("buffer_size", "::libc::c_ulong"),
         ],
         ret: "*mut jack_port_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_unregister",
@@ -613,43 +684,43 @@ const FUNCTIONS: &[Function] = &[
             ("port", "*mut jack_port_t"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_get_buffer",
         args: &[("port", "*mut jack_port_t"), ("nframes", "jack_nframes_t")],
         ret: "*mut ::libc::c_void",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_uuid",
         args: &[("port", "*mut jack_port_t")],
         ret: "jack_uuid_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_name",
         args: &[("port", "*mut jack_port_t")],
         ret: "*const ::libc::c_char",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_short_name",
         args: &[("port", "*mut jack_port_t")],
         ret: "*const ::libc::c_char",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_flags",
         args: &[("port", "*mut jack_port_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_type",
         args: &[("port", "*const jack_port_t")],
         ret: "*const ::libc::c_char",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_type_id",
@@ -664,13 +735,13 @@ const FUNCTIONS: &[Function] = &[
             ("port", "*const jack_port_t"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_connected",
         args: &[("port", "*const jack_port_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_connected_to",
@@ -679,13 +750,13 @@ const FUNCTIONS: &[Function] = &[
             ("port_name", "*const ::libc::c_char"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_get_connections",
         args: &[("port", "*const jack_port_t")],
         ret: "*mut *const ::libc::c_char",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_get_all_connections",
@@ -694,7 +765,7 @@ const FUNCTIONS: &[Function] = &[
             ("port", "*const jack_port_t"),
         ],
         ret: "*mut *const ::libc::c_char",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_tie",
@@ -715,7 +786,7 @@ const FUNCTIONS: &[Function] = &[
             ("port_name", "*const ::libc::c_char"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_set_alias",
@@ -724,7 +795,7 @@ const FUNCTIONS: &[Function] = &[
             ("alias", "*const ::libc::c_char"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_unset_alias",
@@ -733,7 +804,7 @@ const FUNCTIONS: &[Function] = &[
             ("alias", "*const ::libc::c_char"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_get_aliases",
@@ -742,13 +813,13 @@ const FUNCTIONS: &[Function] = &[
             ("aliases", "*mut *mut ::libc::c_char"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_request_monitor",
         args: &[("port", "*mut jack_port_t"), ("onoff", "::libc::c_int")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_request_monitor_by_name",
@@ -758,19 +829,19 @@ const FUNCTIONS: &[Function] = &[
             ("onoff", "::libc::c_int"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_ensure_monitor",
         args: &[("port", "*mut jack_port_t"), ("onoff", "::libc::c_int")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_port_monitoring_input",
         args: &[("port", "*mut jack_port_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_connect",
@@ -780,7 +851,7 @@ const FUNCTIONS: &[Function] = &[
             ("destination_port", "*const ::libc::c_char"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_disconnect(
     //     client: *mut jack_client_t,
@@ -795,7 +866,7 @@ const FUNCTIONS: &[Function] = &[
             ("destination_port", "*const ::libc::c_char"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_port_disconnect(client: *mut jack_client_t, port: *mut jack_port_t) -> ::libc::c_int;
     Function {
@@ -805,21 +876,21 @@ const FUNCTIONS: &[Function] = &[
             ("port", "*mut jack_port_t"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_port_name_size() -> ::libc::c_int;
     Function {
         name: "jack_port_name_size",
         args: &[],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_port_type_size() -> ::libc::c_int;
     Function {
         name: "jack_port_type_size",
         args: &[],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_port_type_get_buffer_size(
     //     client: *mut jack_client_t,
@@ -832,7 +903,7 @@ const FUNCTIONS: &[Function] = &[
             ("port_type", "*const ::libc::c_char"),
         ],
         ret: "::libc::size_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_port_set_latency(port: *mut jack_port_t, arg1: jack_nframes_t) -> ();
     Function {
@@ -854,7 +925,7 @@ const FUNCTIONS: &[Function] = &[
             ("range", "*mut jack_latency_range_t"),
         ],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_port_set_latency_range(
     //     port: *mut jack_port_t,
@@ -869,14 +940,14 @@ const FUNCTIONS: &[Function] = &[
             ("range", "*mut jack_latency_range_t"),
         ],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_recompute_total_latencies(client: *mut jack_client_t) -> ::libc::c_int;
     Function {
         name: "jack_recompute_total_latencies",
         args: &[("client", "*mut jack_client_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_port_get_latency(port: *mut jack_port_t) -> jack_nframes_t;
     Function {
@@ -923,7 +994,7 @@ const FUNCTIONS: &[Function] = &[
             ("flags", "::libc::c_ulong"),
         ],
         ret: "*mut *const ::libc::c_char",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_port_by_name(
     //     client: *mut jack_client_t,
@@ -936,7 +1007,7 @@ const FUNCTIONS: &[Function] = &[
             ("port_name", "*const ::libc::c_char"),
         ],
         ret: "*mut jack_port_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_port_by_id(client: *mut jack_client_t, port_id: jack_port_id_t) -> *mut jack_port_t;
     Function {
@@ -946,28 +1017,28 @@ const FUNCTIONS: &[Function] = &[
             ("port_id", "jack_port_id_t"),
         ],
         ret: "*mut jack_port_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_frames_since_cycle_start(arg1: *const jack_client_t) -> jack_nframes_t;
     Function {
         name: "jack_frames_since_cycle_start",
         args: &[("arg1", "*const jack_client_t")],
         ret: "jack_nframes_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_frame_time(arg1: *const jack_client_t) -> jack_nframes_t;
     Function {
         name: "jack_frame_time",
         args: &[("arg1", "*const jack_client_t")],
         ret: "jack_nframes_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_last_frame_time(client: *const jack_client_t) -> jack_nframes_t;
     Function {
         name: "jack_last_frame_time",
         args: &[("client", "*const jack_client_t")],
         ret: "jack_nframes_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_frames_to_time(client: *const jack_client_t, arg1: jack_nframes_t) -> jack_time_t;
     Function {
@@ -977,21 +1048,21 @@ const FUNCTIONS: &[Function] = &[
             ("arg1", "jack_nframes_t"),
         ],
         ret: "jack_time_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_time_to_frames(client: *const jack_client_t, arg1: jack_time_t) -> jack_nframes_t;
     Function {
         name: "jack_time_to_frames",
         args: &[("client", "*const jack_client_t"), ("arg1", "jack_time_t")],
         ret: "jack_nframes_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_get_time() -> jack_time_t;
     Function {
         name: "jack_get_time",
         args: &[],
         ret: "jack_time_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_set_error_function(
     //     func: ::std::option::Option<unsafe extern "C" fn(arg1: *const ::libc::c_char) -> ()>,
@@ -1003,7 +1074,7 @@ const FUNCTIONS: &[Function] = &[
             "::std::option::Option<unsafe extern \"C\" fn(arg1: *const ::libc::c_char) -> ()>",
         )],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_set_info_function(
     //     func: ::std::option::Option<unsafe extern "C" fn(arg1: *const ::libc::c_char) -> ()>,
@@ -1015,34 +1086,43 @@ const FUNCTIONS: &[Function] = &[
             "::std::option::Option<unsafe extern \"C\" fn(arg1: *const ::libc::c_char) -> ()>",
         )],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_free(ptr: *mut ::libc::c_void) -> ();
     Function {
         name: "jack_free",
         args: &[("ptr", "*mut ::libc::c_void")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_client_real_time_priority(arg1: *mut jack_client_t) -> ::libc::c_int;
     Function {
         name: "jack_client_real_time_priority",
         args: &[("arg1", "*mut jack_client_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_client_max_real_time_priority(arg1: *mut jack_client_t) -> ::libc::c_int;
     Function {
         name: "jack_client_max_real_time_priority",
         args: &[("arg1", "*mut jack_client_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // #[cfg(not(target_os = "windows"))]
     // pub fn jack_acquire_real_time_scheduling(
     //     thread: jack_native_thread_t,
     //     priority: ::libc::c_int,
     // ) -> ::libc::c_int;
+    Function {
+        name: "jack_acquire_real_time_scheduling",
+        args: &[
+            ("thread", "jack_native_thread_t"),
+            ("priority", "::libc::c_int"),
+        ],
+        ret: "::libc::c_int",
+        flags: FunctionFlags::NOT_WINDOWS,
+    },
     // #[cfg(not(target_os = "windows"))]
     // pub fn jack_client_create_thread(
     //     client: *mut jack_client_t,
@@ -1054,13 +1134,44 @@ const FUNCTIONS: &[Function] = &[
     //     >,
     //     arg: *mut ::libc::c_void,
     // ) -> ::libc::c_int;
+    Function {
+        name: "jack_client_create_thread",
+        args: &[
+            ("client", "*mut jack_client_t"),
+            ("thread", "*mut jack_native_thread_t"),
+            ("priority", "::libc::c_int"),
+            ("realtime", "::libc::c_int"),
+            (
+                "start_routine",
+                "::std::option::Option<unsafe extern \"C\" fn(arg1: *mut ::libc::c_void) -> *mut ::libc::c_void>",
+            ),
+            ("arg", "*mut ::libc::c_void"),
+        ],
+        ret: "::libc::c_int",
+        flags: FunctionFlags::NOT_WINDOWS,
+    },
     // #[cfg(not(target_os = "windows"))]
     // pub fn jack_drop_real_time_scheduling(thread: jack_native_thread_t) -> ::libc::c_int;
+    Function {
+        name: "jack_drop_real_time_scheduling",
+        args: &[("thread", "jack_native_thread_t")],
+        ret: "::libc::c_int",
+        flags: FunctionFlags::NOT_WINDOWS,
+    },
     // #[cfg(not(target_os = "windows"))]
     // pub fn jack_client_stop_thread(
     //     client: *mut jack_client_t,
     //     thread: jack_native_thread_t,
     // ) -> ::libc::c_int;
+    Function {
+        name: "jack_client_stop_thread",
+        args: &[
+            ("client", "*mut jack_client_t"),
+            ("thread", "jack_native_thread_t"),
+        ],
+        ret: "::libc::c_int",
+        flags: FunctionFlags::NOT_WINDOWS,
+    },
     // #[cfg(not(target_os = "windows"))]
     // pub fn jack_client_kill_thread(
     //     client: *mut jack_client_t,
@@ -1068,6 +1179,12 @@ const FUNCTIONS: &[Function] = &[
     // ) -> ::libc::c_int;
     // #[cfg(not(target_os = "windows"))]
     // pub fn jack_set_thread_creator(creator: jack_thread_creator_t) -> ();
+    Function {
+        name: "jack_set_thread_creator",
+        args: &[("creator", "jack_thread_creator_t")],
+        ret: "()",
+        flags: FunctionFlags::WEAK.union(FunctionFlags::NOT_WINDOWS),
+    },
     // pub fn jack_set_session_callback(
     //     client: *mut jack_client_t,
     //     session_callback: JackSessionCallback,
@@ -1108,7 +1225,7 @@ const FUNCTIONS: &[Function] = &[
         name: "jack_client_get_uuid",
         args: &[("client", "*mut jack_client_t")],
         ret: "*mut ::libc::c_char",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_session_notify(
     //     client: *mut jack_client_t,
@@ -1736,28 +1853,28 @@ const FUNCTIONS: &[Function] = &[
         name: "jack_get_max_delayed_usecs",
         args: &[("client", "*mut jack_client_t")],
         ret: "::libc::c_float",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_get_xrun_delayed_usecs(client: *mut jack_client_t) -> ::libc::c_float;
     Function {
         name: "jack_get_xrun_delayed_usecs",
         args: &[("client", "*mut jack_client_t")],
         ret: "::libc::c_float",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_reset_max_delayed_usecs(client: *mut jack_client_t) -> ();
     Function {
         name: "jack_reset_max_delayed_usecs",
         args: &[("client", "*mut jack_client_t")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_midi_get_event_count(port_buffer: *mut ::libc::c_void) -> u32;
     Function {
         name: "jack_midi_get_event_count",
         args: &[("port_buffer", "*mut ::libc::c_void")],
         ret: "u32",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_midi_event_get(
     //     event: *mut jack_midi_event_t,
@@ -1772,21 +1889,21 @@ const FUNCTIONS: &[Function] = &[
             ("event_index", "u32"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_midi_clear_buffer(port_buffer: *mut ::libc::c_void) -> ();
     Function {
         name: "jack_midi_clear_buffer",
         args: &[("port_buffer", "*mut ::libc::c_void")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_midi_max_event_size(port_buffer: *mut ::libc::c_void) -> ::libc::size_t;
     Function {
         name: "jack_midi_max_event_size",
         args: &[("port_buffer", "*mut ::libc::c_void")],
         ret: "::libc::size_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_midi_event_reserve(
     //     port_buffer: *mut ::libc::c_void,
@@ -1801,7 +1918,7 @@ const FUNCTIONS: &[Function] = &[
             ("data_size", "::libc::size_t"),
         ],
         ret: "*mut jack_midi_data_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_midi_event_write(
     //     port_buffer: *mut ::libc::c_void,
@@ -1818,28 +1935,28 @@ const FUNCTIONS: &[Function] = &[
             ("data_size", "::libc::size_t"),
         ],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_midi_get_lost_event_count(port_buffer: *mut ::libc::c_void) -> u32;
     Function {
         name: "jack_midi_get_lost_event_count",
         args: &[("port_buffer", "*mut ::libc::c_void")],
         ret: "u32",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_create(sz: ::libc::size_t) -> *mut jack_ringbuffer_t;
     Function {
         name: "jack_ringbuffer_create",
         args: &[("sz", "::libc::size_t")],
         ret: "*mut jack_ringbuffer_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_free(rb: *mut jack_ringbuffer_t) -> ();
     Function {
         name: "jack_ringbuffer_free",
         args: &[("rb", "*mut jack_ringbuffer_t")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_get_read_vector(
     //     rb: *const jack_ringbuffer_t,
@@ -1852,7 +1969,7 @@ const FUNCTIONS: &[Function] = &[
             ("vec", "*mut jack_ringbuffer_data_t"),
         ],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_get_write_vector(
     //     rb: *const jack_ringbuffer_t,
@@ -1865,7 +1982,7 @@ const FUNCTIONS: &[Function] = &[
             ("vec", "*mut jack_ringbuffer_data_t"),
         ],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_read(
     //     rb: *mut jack_ringbuffer_t,
@@ -1880,7 +1997,7 @@ const FUNCTIONS: &[Function] = &[
             ("cnt", "::libc::size_t"),
         ],
         ret: "::libc::size_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_peek(
     //     rb: *mut jack_ringbuffer_t,
@@ -1895,35 +2012,35 @@ const FUNCTIONS: &[Function] = &[
             ("cnt", "::libc::size_t"),
         ],
         ret: "::libc::size_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_read_advance(rb: *mut jack_ringbuffer_t, cnt: ::libc::size_t) -> ();
     Function {
         name: "jack_ringbuffer_read_advance",
         args: &[("rb", "*mut jack_ringbuffer_t"), ("cnt", "::libc::size_t")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_read_space(rb: *const jack_ringbuffer_t) -> ::libc::size_t;
     Function {
         name: "jack_ringbuffer_read_space",
         args: &[("rb", "*const jack_ringbuffer_t")],
         ret: "::libc::size_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_mlock(rb: *mut jack_ringbuffer_t) -> ::libc::c_int;
     Function {
         name: "jack_ringbuffer_mlock",
         args: &[("rb", "*mut jack_ringbuffer_t")],
         ret: "::libc::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_reset(rb: *mut jack_ringbuffer_t) -> ();
     Function {
         name: "jack_ringbuffer_reset",
         args: &[("rb", "*mut jack_ringbuffer_t")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_write(
     //     rb: *mut jack_ringbuffer_t,
@@ -1938,21 +2055,21 @@ const FUNCTIONS: &[Function] = &[
             ("cnt", "::libc::size_t"),
         ],
         ret: "::libc::size_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_write_advance(rb: *mut jack_ringbuffer_t, cnt: ::libc::size_t) -> ();
     Function {
         name: "jack_ringbuffer_write_advance",
         args: &[("rb", "*mut jack_ringbuffer_t"), ("cnt", "::libc::size_t")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_ringbuffer_write_space(rb: *const jack_ringbuffer_t) -> ::libc::size_t;
     Function {
         name: "jack_ringbuffer_write_space",
         args: &[("rb", "*const jack_ringbuffer_t")],
         ret: "::libc::size_t",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
 
     // pub fn jack_uuid_to_index(arg1: jack_uuid_t) -> u32;
@@ -1960,28 +2077,28 @@ const FUNCTIONS: &[Function] = &[
         name: "jack_uuid_to_index",
         args: &[("arg1", "jack_uuid_t")],
         ret: "u32",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_uuid_compare(arg1: jack_uuid_t, arg2: jack_uuid_t) -> ::std::os::raw::c_int;
     Function {
         name: "jack_uuid_compare",
         args: &[("arg1", "jack_uuid_t"), ("arg2", "jack_uuid_t")],
         ret: "::std::os::raw::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_uuid_copy(dst: *mut jack_uuid_t, src: jack_uuid_t);
     Function {
         name: "jack_uuid_copy",
         args: &[("dst", "*mut jack_uuid_t"), ("src", "jack_uuid_t")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     // pub fn jack_uuid_clear(arg1: *mut jack_uuid_t);
     Function {
         name: "jack_uuid_clear",
         args: &[("arg1", "*mut jack_uuid_t")],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_uuid_parse",
@@ -1990,7 +2107,7 @@ const FUNCTIONS: &[Function] = &[
             ("arg1", "*mut jack_uuid_t"),
         ],
         ret: "::std::os::raw::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_uuid_unparse",
@@ -1999,12 +2116,12 @@ const FUNCTIONS: &[Function] = &[
             ("buf", "*mut ::std::os::raw::c_char"),
         ],
         ret: "()",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
     Function {
         name: "jack_uuid_empty",
         args: &[("arg1", "jack_uuid_t")],
         ret: "::std::os::raw::c_int",
-        flags: FunctionFlags::NONE,
+        flags: FunctionFlags::FAKE,
     },
 ];
@@ -16,3 +16,16 @@ pub const JACK_LIB: &str = if cfg!(windows) {
 } else {
     "libjack.so.0"
 };
+
+/// The path to the jack server library, which the JACK server runs internal clients with.
+pub const JACK_SERVER_LIB: &str = if cfg!(windows) {
+    if cfg!(target_arch = "x86") {
+        "libjackserver.dll"
+    } else {
+        "libjackserver64.dll"
+    }
+} else if cfg!(target_vendor = "apple") {
+    "libjackserver.0.dylib"
+} else {
+    "libjackserver.so.0"
+};
@@ -0,0 +1,430 @@
+//! Client, callback and time functions of the fake server.
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use super::server::{client_ptr, dispatch, id_of, with_server, Event, CLIENT_NAME_SIZE};
+use crate::types::*;
+
+/// Copy `s` into memory that the caller frees with `jack_free`.
+pub(crate) fn malloc_str(s: &CStr) -> *mut libc::c_char {
+    let bytes = s.to_bytes_with_nul();
+    unsafe {
+        let copy = libc::malloc(bytes.len()) as *mut libc::c_char;
+        if !copy.is_null() {
+            ptr::copy_nonoverlapping(bytes.as_ptr() as *const libc::c_char, copy, bytes.len());
+        }
+        copy
+    }
+}
+
+pub(crate) unsafe fn client_open(
+    client_name: *const libc::c_char,
+    options: jack_options_t,
+    status: *mut jack_status_t,
+    _server_name: *const libc::c_char,
+    _session_id: *const libc::c_char,
+) -> *mut jack_client_t {
+    let name = CStr::from_ptr(client_name);
+    let res = with_server(|s| s.open_client(name, options));
+    let (client, status_bits) = match res {
+        Ok((id, status_bits)) => (client_ptr(id), status_bits),
+        Err(status_bits) => (ptr::null_mut(), status_bits),
+    };
+    if !status.is_null() {
+        *status = status_bits;
+    }
+    dispatch();
+    client
+}
+
+pub(crate) unsafe fn internal_client_load(
+    _client: *mut jack_client_t,
+    _client_name: *const libc::c_char,
+    _options: jack_options_t,
+    _status: *mut jack_status_t,
+    _load_name: *const libc::c_char,
+    _load_init: *const libc::c_char,
+) -> Option<jack_intclient_t> {
+    None
+}
+
+pub(crate) unsafe extern "C" fn jack_client_open(
+    client_name: *const libc::c_char,
+    options: jack_options_t,
+    status: *mut jack_status_t,
+) -> *mut jack_client_t {
+    client_open(client_name, options, status, ptr::null(), ptr::null())
+}
+
+pub(crate) unsafe extern "C" fn jack_client_close(client: *mut jack_client_t) -> libc::c_int {
+    with_server(|s| s.close_client(id_of(client)));
+    dispatch();
+    0
+}
+
+pub(crate) unsafe extern "C" fn jack_client_name_size() -> libc::c_int {
+    CLIENT_NAME_SIZE as libc::c_int
+}
+
+pub(crate) unsafe extern "C" fn jack_get_client_name(
+    client: *mut jack_client_t,
+) -> *mut libc::c_char {
+    with_server(|s| match s.client(client) {
+        // The name is never changed, so it stays valid as long as the client.
+        Some(c) => c.name.as_ptr() as *mut libc::c_char,
+        None => ptr::null_mut(),
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_get_uuid_for_client_name(
+    _client: *mut jack_client_t,
+    client_name: *const libc::c_char,
+) -> *mut libc::c_char {
+    let name = CStr::from_ptr(client_name);
+    match with_server(|s| s.client_by_name(name)) {
+        Some(id) => malloc_str(&CString::new(id.to_string()).unwrap()),
+        None => ptr::null_mut(),
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_get_client_name_by_uuid(
+    _client: *mut jack_client_t,
+    client_uuid: *const libc::c_char,
+) -> *mut libc::c_char {
+    let id = CStr::from_ptr(client_uuid)
+        .to_str()
+        .ok()
+        .and_then(|uuid| uuid.parse::<u32>().ok());
+    with_server(|s| match id.and_then(|id| s.clients.get(&id)) {
+        Some(c) => malloc_str(&c.name),
+        None => ptr::null_mut(),
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_client_get_uuid(
+    client: *mut jack_client_t,
+) -> *mut libc::c_char {
+    match with_server(|s| s.client(client).is_some()) {
+        true => malloc_str(&CString::new(id_of(client).to_string()).unwrap()),
+        false => ptr::null_mut(),
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_activate(client: *mut jack_client_t) -> libc::c_int {
+    let is_active = with_server(|s| s.activate_client(id_of(client)));
+    dispatch();
+    match is_active {
+        true => 0,
+        false => -1,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_deactivate(client: *mut jack_client_t) -> libc::c_int {
+    with_server(|s| s.deactivate_client(id_of(client)));
+    0
+}
+
+pub(crate) unsafe extern "C" fn jack_get_client_pid(name: *const libc::c_char) -> libc::c_int {
+    let name = CStr::from_ptr(name);
+    match with_server(|s| s.client_by_name(name)) {
+        Some(_) => std::process::id() as libc::c_int,
+        None => 0,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_is_realtime(_client: *mut jack_client_t) -> libc::c_int {
+    0
+}
+
+pub(crate) unsafe extern "C" fn jack_client_real_time_priority(
+    _client: *mut jack_client_t,
+) -> libc::c_int {
+    -1
+}
+
+pub(crate) unsafe extern "C" fn jack_client_max_real_time_priority(
+    _client: *mut jack_client_t,
+) -> libc::c_int {
+    -1
+}
+
+/// Process threads are not supported, as cycles are run by `fake::run_cycles` on the calling
+/// thread. Clearing the process thread succeeds.
+pub(crate) unsafe extern "C" fn jack_set_process_thread(
+    client: *mut jack_client_t,
+    thread_callback: JackThreadCallback,
+    _arg: *mut libc::c_void,
+) -> libc::c_int {
+    match (with_server(|s| s.client(client).is_some()), thread_callback) {
+        (true, None) => 0,
+        _ => -1,
+    }
+}
+
+/// Define a function that sets a callback of a client, like JACK only while it is inactive.
+macro_rules! set_callback {
+    ($name:ident, $field:ident, $callback_type:ty) => {
+        pub(crate) unsafe extern "C" fn $name(
+            client: *mut jack_client_t,
+            callback: $callback_type,
+            arg: *mut libc::c_void,
+        ) -> libc::c_int {
+            with_server(|s| match s.client_mut(client) {
+                Some(c) if !c.is_active => {
+                    c.callbacks.$field.set(callback, arg);
+                    0
+                }
+                _ => -1,
+            })
+        }
+    };
+}
+
+set_callback!(jack_set_process_callback, process, JackProcessCallback);
+set_callback!(
+    jack_set_thread_init_callback,
+    thread_init,
+    JackThreadInitCallback
+);
+set_callback!(
+    jack_set_freewheel_callback,
+    freewheel,
+    JackFreewheelCallback
+);
+set_callback!(
+    jack_set_buffer_size_callback,
+    buffer_size,
+    JackBufferSizeCallback
+);
+set_callback!(
+    jack_set_sample_rate_callback,
+    sample_rate,
+    JackSampleRateCallback
+);
+set_callback!(
+    jack_set_client_registration_callback,
+    client_registration,
+    JackClientRegistrationCallback
+);
+set_callback!(
+    jack_set_port_registration_callback,
+    port_registration,
+    JackPortRegistrationCallback
+);
+set_callback!(
+    jack_set_port_connect_callback,
+    port_connect,
+    JackPortConnectCallback
+);
+set_callback!(
+    jack_set_port_rename_callback,
+    port_rename,
+    JackPortRenameCallback
+);
+set_callback!(
+    jack_set_graph_order_callback,
+    graph_order,
+    JackGraphOrderCallback
+);
+set_callback!(jack_set_xrun_callback, xrun, JackXRunCallback);
+set_callback!(jack_set_latency_callback, latency, JackLatencyCallback);
+
+pub(crate) unsafe extern "C" fn jack_on_shutdown(
+    client: *mut jack_client_t,
+    callback: JackShutdownCallback,
+    arg: *mut libc::c_void,
+) {
+    with_server(|s| {
+        if let Some(c) = s.client_mut(client) {
+            c.callbacks.shutdown.set(callback, arg);
+        }
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_on_info_shutdown(
+    client: *mut jack_client_t,
+    callback: JackInfoShutdownCallback,
+    arg: *mut libc::c_void,
+) {
+    with_server(|s| {
+        if let Some(c) = s.client_mut(client) {
+            c.callbacks.info_shutdown.set(callback, arg);
+        }
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_set_freewheel(
+    client: *mut jack_client_t,
+    onoff: libc::c_int,
+) -> libc::c_int {
+    let res = with_server(|s| {
+        s.client(client)?;
+        let is_freewheeling = onoff != 0;
+        if s.is_freewheeling != is_freewheeling {
+            s.is_freewheeling = is_freewheeling;
+            s.notify(Event::Freewheel(is_freewheeling));
+        }
+        Some(())
+    });
+    dispatch();
+    match res {
+        Some(()) => 0,
+        None => -1,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_set_buffer_size(
+    client: *mut jack_client_t,
+    nframes: jack_nframes_t,
+) -> libc::c_int {
+    let is_set = with_server(|s| s.client(client).is_some() && s.set_buffer_size(nframes));
+    dispatch();
+    match is_set {
+        true => 0,
+        false => -1,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_get_buffer_size(
+    _client: *mut jack_client_t,
+) -> jack_nframes_t {
+    with_server(|s| s.buffer_size)
+}
+
+pub(crate) unsafe extern "C" fn jack_get_sample_rate(_client: *mut jack_client_t) -> libc::c_int {
+    with_server(|s| s.sample_rate as libc::c_int)
+}
+
+pub(crate) unsafe extern "C" fn jack_cpu_load(_client: *mut jack_client_t) -> libc::c_float {
+    0.0
+}
+
+pub(crate) unsafe extern "C" fn jack_get_max_delayed_usecs(
+    _client: *mut jack_client_t,
+) -> libc::c_float {
+    0.0
+}
+
+pub(crate) unsafe extern "C" fn jack_get_xrun_delayed_usecs(
+    _client: *mut jack_client_t,
+) -> libc::c_float {
+    0.0
+}
+
+pub(crate) unsafe extern "C" fn jack_reset_max_delayed_usecs(_client: *mut jack_client_t) {}
+
+pub(crate) unsafe extern "C" fn jack_get_cycle_times(
+    _client: *const jack_client_t,
+    current_frames: *mut jack_nframes_t,
+    current_usecs: *mut jack_time_t,
+    next_usecs: *mut jack_time_t,
+    period_usecs: *mut libc::c_float,
+) -> libc::c_int {
+    with_server(|s| {
+        let next = s.cycle_start + u64::from(s.buffer_size);
+        *current_frames = s.cycle_start as jack_nframes_t;
+        *current_usecs = s.frames_to_usecs(s.cycle_start);
+        *next_usecs = s.frames_to_usecs(next);
+        *period_usecs = (*next_usecs - *current_usecs) as libc::c_float;
+    });
+    0
+}
+
+// Time only advances with the cycles that are run, and no time passes within a cycle.
+
+pub(crate) unsafe extern "C" fn jack_frames_since_cycle_start(
+    _client: *const jack_client_t,
+) -> jack_nframes_t {
+    0
+}
+
+pub(crate) unsafe extern "C" fn jack_frame_time(_client: *const jack_client_t) -> jack_nframes_t {
+    with_server(|s| s.frames as jack_nframes_t)
+}
+
+pub(crate) unsafe extern "C" fn jack_last_frame_time(
+    _client: *const jack_client_t,
+) -> jack_nframes_t {
+    with_server(|s| s.cycle_start as jack_nframes_t)
+}
+
+pub(crate) unsafe extern "C" fn jack_frames_to_time(
+    _client: *const jack_client_t,
+    frames: jack_nframes_t,
+) -> jack_time_t {
+    with_server(|s| s.frames_to_usecs(u64::from(frames)))
+}
+
+pub(crate) unsafe extern "C" fn jack_time_to_frames(
+    _client: *const jack_client_t,
+    time: jack_time_t,
+) -> jack_nframes_t {
+    with_server(|s| s.usecs_to_frames(time) as jack_nframes_t)
+}
+
+pub(crate) unsafe extern "C" fn jack_get_time() -> jack_time_t {
+    with_server(|s| s.frames_to_usecs(s.frames))
+}
+
+/// Errors are reported through return values only.
+pub(crate) unsafe extern "C" fn jack_set_error_function(
+    _func: Option<unsafe extern "C" fn(*const libc::c_char)>,
+) {
+}
+
+pub(crate) unsafe extern "C" fn jack_set_info_function(
+    _func: Option<unsafe extern "C" fn(*const libc::c_char)>,
+) {
+}
+
+pub(crate) unsafe extern "C" fn jack_free(ptr: *mut libc::c_void) {
+    libc::free(ptr)
+}
+
+pub(crate) unsafe extern "C" fn jack_uuid_to_index(uuid: jack_uuid_t) -> u32 {
+    (uuid & 0xffff_ffff) as u32
+}
+
+pub(crate) unsafe extern "C" fn jack_uuid_compare(a: jack_uuid_t, b: jack_uuid_t) -> libc::c_int {
+    match a.cmp(&b) {
+        std::cmp::Ordering::Less => -1,
+        std::cmp::Ordering::Equal => 0,
+        std::cmp::Ordering::Greater => 1,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_uuid_copy(dst: *mut jack_uuid_t, src: jack_uuid_t) {
+    *dst = src;
+}
+
+pub(crate) unsafe extern "C" fn jack_uuid_clear(uuid: *mut jack_uuid_t) {
+    *uuid = 0;
+}
+
+pub(crate) unsafe extern "C" fn jack_uuid_parse(
+    buf: *const libc::c_char,
+    uuid: *mut jack_uuid_t,
+) -> libc::c_int {
+    match CStr::from_ptr(buf)
+        .to_str()
+        .ok()
+        .and_then(|s| s.parse().ok())
+    {
+        Some(parsed) => {
+            *uuid = parsed;
+            0
+        }
+        None => -1,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_uuid_unparse(uuid: jack_uuid_t, buf: *mut libc::c_char) {
+    // `buf` holds at least 37 bytes, a `u64` takes at most 20 digits.
+    let s = CString::new(uuid.to_string()).unwrap();
+    let bytes = s.as_bytes_with_nul();
+    ptr::copy_nonoverlapping(bytes.as_ptr() as *const libc::c_char, buf, bytes.len());
+}
+
+pub(crate) unsafe extern "C" fn jack_uuid_empty(uuid: jack_uuid_t) -> libc::c_int {
+    (uuid == 0) as libc::c_int
+}
@@ -0,0 +1,148 @@
+//! MIDI buffers of the fake server.
+use crate::types::*;
+
+/// The size of a MIDI port buffer in bytes, as in JACK2.
+pub(crate) const MIDI_BUFFER_SIZE: usize = 32768;
+/// The bytes used by the buffer header and by each event, besides its data.
+const MIDI_HEADER_SIZE: usize = 32;
+const MIDI_EVENT_SIZE: usize = 8;
+
+pub(crate) struct MidiEvent {
+    pub(crate) time: jack_nframes_t,
+    pub(crate) data: Vec<jack_midi_data_t>,
+}
+
+/// The buffer of a MIDI port. Events are kept in the order of their time.
+pub(crate) struct MidiBuffer {
+    n_frames: jack_nframes_t,
+    pub(crate) events: Vec<MidiEvent>,
+    lost_events: u32,
+}
+
+impl MidiBuffer {
+    pub(crate) fn new(n_frames: jack_nframes_t) -> MidiBuffer {
+        MidiBuffer {
+            n_frames,
+            events: Vec::new(),
+            lost_events: 0,
+        }
+    }
+
+    pub(crate) fn reset(&mut self, n_frames: jack_nframes_t) {
+        self.n_frames = n_frames;
+        self.events.clear();
+        self.lost_events = 0;
+    }
+
+    fn max_event_size(&self) -> usize {
+        let used = MIDI_HEADER_SIZE
+            + MIDI_EVENT_SIZE * (self.events.len() + 1)
+            + self.events.iter().map(|e| e.data.len()).sum::<usize>();
+        MIDI_BUFFER_SIZE.saturating_sub(used)
+    }
+
+    /// Add an event of `size` bytes, and return it to be filled in.
+    ///
+    /// Like JACK, this fails and counts the event as lost if it is out of order, outside of the
+    /// cycle or does not fit.
+    pub(crate) fn reserve(
+        &mut self,
+        time: jack_nframes_t,
+        size: usize,
+    ) -> Option<&mut [jack_midi_data_t]> {
+        let is_in_order = self.events.last().is_none_or(|e| e.time <= time);
+        if !is_in_order || time >= self.n_frames || size == 0 || size > self.max_event_size() {
+            self.lost_events += 1;
+            return None;
+        }
+        self.events.push(MidiEvent {
+            time,
+            data: vec![0; size],
+        });
+        Some(&mut self.events.last_mut().unwrap().data)
+    }
+
+    /// Add the events of `other`, as when mixing the outputs connected to an input port.
+    pub(crate) fn merge(&mut self, other: &MidiBuffer) {
+        for event in &other.events {
+            let index = self.events.partition_point(|e| e.time <= event.time);
+            let data = event.data.clone();
+            self.events.insert(
+                index,
+                MidiEvent {
+                    time: event.time,
+                    data,
+                },
+            );
+        }
+    }
+}
+
+unsafe fn buffer<'a>(port_buffer: *mut libc::c_void) -> &'a mut MidiBuffer {
+    &mut *(port_buffer as *mut MidiBuffer)
+}
+
+pub(crate) unsafe extern "C" fn jack_midi_get_event_count(port_buffer: *mut libc::c_void) -> u32 {
+    buffer(port_buffer).events.len() as u32
+}
+
+pub(crate) unsafe extern "C" fn jack_midi_event_get(
+    event: *mut jack_midi_event_t,
+    port_buffer: *mut libc::c_void,
+    event_index: u32,
+) -> libc::c_int {
+    match buffer(port_buffer).events.get_mut(event_index as usize) {
+        Some(e) => {
+            *event = jack_midi_event_t {
+                time: e.time,
+                size: e.data.len(),
+                buffer: e.data.as_mut_ptr(),
+            };
+            0
+        }
+        None => libc::ENODATA,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_midi_clear_buffer(port_buffer: *mut libc::c_void) {
+    let buffer = buffer(port_buffer);
+    buffer.reset(buffer.n_frames);
+}
+
+pub(crate) unsafe extern "C" fn jack_midi_max_event_size(
+    port_buffer: *mut libc::c_void,
+) -> libc::size_t {
+    buffer(port_buffer).max_event_size()
+}
+
+pub(crate) unsafe extern "C" fn jack_midi_event_reserve(
+    port_buffer: *mut libc::c_void,
+    time: jack_nframes_t,
+    data_size: libc::size_t,
+) -> *mut jack_midi_data_t {
+    match buffer(port_buffer).reserve(time, data_size) {
+        Some(data) => data.as_mut_ptr(),
+        None => std::ptr::null_mut(),
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_midi_event_write(
+    port_buffer: *mut libc::c_void,
+    time: jack_nframes_t,
+    data: *const jack_midi_data_t,
+    data_size: libc::size_t,
+) -> libc::c_int {
+    match buffer(port_buffer).reserve(time, data_size) {
+        Some(reserved) => {
+            reserved.copy_from_slice(std::slice::from_raw_parts(data, data_size));
+            0
+        }
+        None => libc::ENOBUFS,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_midi_get_lost_event_count(
+    port_buffer: *mut libc::c_void,
+) -> u32 {
+    buffer(port_buffer).lost_events
+}
@@ -0,0 +1,108 @@
+//! An in-process fake JACK server, built with the `fake-server` feature.
+//!
+//! On threads that called [`enable`], the JACK functions of this crate call into a server written
+//! in Rust instead of loading the JACK library, so no JACK server has to run. Other threads keep
+//! using the JACK library. The `fake` feature enables the fake server on every thread.
+//!
+//! The fake server supports clients, ports, connections, notifications, transport, MIDI and ring
+//! buffers. Functions it does not support panic, or return `None` if they are optional in JACK.
+//!
+//! Each thread has its own server, which starts with the ports of `jackd -ddummy -r44100 -p1024`:
+//! `system:capture_1` and `system:capture_2`, and `system:playback_1` and `system:playback_2`.
+//! Tests that run in parallel therefore never see each other's clients.
+//!
+//! Nothing happens in the background. Notifications are delivered before the function that caused
+//! them returns, and process cycles only run when [`run_cycles`] is called, on the calling thread.
+//! Clients are processed after the clients that feed their inputs, and otherwise in the order they
+//! were activated. Time is counted in processed frames, so results do not depend on timing.
+//!
+//! Because of this, anything that waits for the process callback to run on another thread does not
+//! work, and neither do process threads set with `jack_set_process_thread`. Internal clients,
+//! sessions, threads created by JACK and metadata are not supported.
+mod client;
+mod midi;
+mod pattern;
+mod port;
+mod ringbuffer;
+mod server;
+mod transport;
+
+pub(crate) use self::client::*;
+pub(crate) use self::midi::*;
+pub(crate) use self::port::*;
+pub(crate) use self::ringbuffer::*;
+pub(crate) use self::transport::*;
+
+use std::cell::Cell;
+use std::ffi::CString;
+
+use self::server::{dispatch, with_server, Event};
+use crate::types::jack_nframes_t;
+
+thread_local! {
+    static ENABLED: Cell<bool> = Cell::new(cfg!(feature = "fake"));
+}
+
+/// Serve the JACK functions called on the current thread from its fake server, for the rest of
+/// the life of the thread.
+///
+/// Clients of the JACK library must not be used on the thread afterwards. With the `fake`
+/// feature, every thread is enabled from the start.
+pub fn enable() {
+    ENABLED.with(|enabled| enabled.set(true));
+}
+
+/// Whether the JACK functions called on the current thread are served by its fake server.
+pub fn is_enabled() -> bool {
+    ENABLED.with(Cell::get)
+}
+
+/// Replace the server of the current thread with a new one.
+///
+/// Clients of the old server stay open, but calls with them fail as if the server had gone away
+/// without notifying them. Use [`shutdown`] to notify them.
+pub fn reset() {
+    server::replace_server();
+}
+
+/// Run `count` process cycles of the server of the current thread.
+///
+/// Stops early if the server is shut down. Notifications caused by a cycle are delivered after it.
+pub fn run_cycles(count: usize) {
+    server::run_cycles(count);
+}
+
+/// Shut the server of the current thread down, as if it stopped with `reason`.
+///
+/// The shutdown callbacks of the active clients are called. Afterwards clients can not be opened
+/// or activated until [`reset`] starts a new server.
+pub fn shutdown(reason: &str) {
+    let reason = CString::new(reason.replace('\0', "")).unwrap();
+    server::shutdown(&reason);
+}
+
+/// Whether the server of the current thread is running.
+pub fn is_running() -> bool {
+    with_server(|s| s.is_running)
+}
+
+/// Change the sample rate of the server of the current thread and notify the clients.
+pub fn set_sample_rate(sample_rate: jack_nframes_t) {
+    with_server(|s| {
+        if s.is_running && sample_rate != 0 && sample_rate != s.sample_rate {
+            s.sample_rate = sample_rate;
+            s.notify(Event::SampleRate(sample_rate));
+        }
+    });
+    dispatch();
+}
+
+/// Notify the clients of the server of the current thread of an xrun.
+pub fn xrun() {
+    with_server(|s| {
+        if s.is_running {
+            s.notify(Event::Xrun);
+        }
+    });
+    dispatch();
+}
@@ -0,0 +1,178 @@
+//! The patterns of `jack_get_ports`.
+//!
+//! JACK matches port names and types against POSIX extended regular expressions. This supports
+//! the commonly used subset: `.`, bracket expressions like `[a-z]` and `[^0-9]`, the repetitions
+//! `*`, `+` and `?`, the anchors `^` and `$`, and the escapes `\d`, `\w`, `\s`. Other characters,
+//! including `(`, `)`, `|`, `{` and `}`, match themselves.
+
+#[derive(Debug)]
+enum Atom {
+    Any,
+    Char(char),
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Any => true,
+            Atom::Char(expected) => c == *expected,
+            Atom::Class { ranges, negated } => {
+                ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Repeat {
+    Once,
+    ZeroOrOne,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+#[derive(Debug)]
+struct Pattern {
+    items: Vec<(Atom, Repeat)>,
+    is_anchored_start: bool,
+    is_anchored_end: bool,
+}
+
+fn escaped_class(c: char) -> Option<Vec<(char, char)>> {
+    match c {
+        'd' => Some(vec![('0', '9')]),
+        'w' => Some(vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')]),
+        's' => Some(vec![(' ', ' '), ('\t', '\r')]),
+        _ => None,
+    }
+}
+
+fn parse(pattern: &str) -> Pattern {
+    let mut chars: Vec<char> = pattern.chars().collect();
+    let is_anchored_start = chars.first() == Some(&'^');
+    if is_anchored_start {
+        chars.remove(0);
+    }
+    let is_anchored_end = chars.last() == Some(&'$') && !ends_with_escape(&chars);
+    if is_anchored_end {
+        chars.pop();
+    }
+    let mut items = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let atom = match chars[i] {
+            '.' => Atom::Any,
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                match escaped_class(chars[i]) {
+                    Some(ranges) => Atom::Class {
+                        ranges,
+                        negated: false,
+                    },
+                    None => Atom::Char(chars[i]),
+                }
+            }
+            '[' => match parse_class(&chars[i + 1..]) {
+                Some((atom, len)) => {
+                    i += len;
+                    atom
+                }
+                None => Atom::Char('['),
+            },
+            c => Atom::Char(c),
+        };
+        i += 1;
+        let repeat = match chars.get(i) {
+            Some('*') => Repeat::ZeroOrMore,
+            Some('+') => Repeat::OneOrMore,
+            Some('?') => Repeat::ZeroOrOne,
+            _ => Repeat::Once,
+        };
+        if repeat != Repeat::Once {
+            i += 1;
+        }
+        items.push((atom, repeat));
+    }
+    Pattern {
+        items,
+        is_anchored_start,
+        is_anchored_end,
+    }
+}
+
+fn ends_with_escape(chars: &[char]) -> bool {
+    let backslashes = chars[..chars.len() - 1]
+        .iter()
+        .rev()
+        .take_while(|&&c| c == '\\')
+        .count();
+    backslashes % 2 == 1
+}
+
+/// Parse the bracket expression after a `[`. Returns the class and the number of characters it
+/// spans, including the closing `]`.
+fn parse_class(chars: &[char]) -> Option<(Atom, usize)> {
+    let negated = chars.first() == Some(&'^');
+    let mut i = negated as usize;
+    let mut ranges = Vec::new();
+    // A `]` right after the opening bracket is part of the class.
+    let mut is_first = true;
+    loop {
+        let c = *chars.get(i)?;
+        if c == ']' && !is_first {
+            return Some((Atom::Class { ranges, negated }, i + 1));
+        }
+        is_first = false;
+        match (chars.get(i + 1), chars.get(i + 2)) {
+            (Some('-'), Some(&hi)) if hi != ']' => {
+                ranges.push((c, hi));
+                i += 3;
+            }
+            _ => {
+                ranges.push((c, c));
+                i += 1;
+            }
+        }
+    }
+}
+
+impl Pattern {
+    fn is_match(&self, text: &[char]) -> bool {
+        match self.is_anchored_start {
+            true => self.matches_at(0, text),
+            false => (0..=text.len()).any(|start| self.matches_at(0, &text[start..])),
+        }
+    }
+
+    fn matches_at(&self, item: usize, text: &[char]) -> bool {
+        let (atom, repeat) = match self.items.get(item) {
+            Some(item) => item,
+            None => return !self.is_anchored_end || text.is_empty(),
+        };
+        let (min, max) = match repeat {
+            Repeat::Once => (1, 1),
+            Repeat::ZeroOrOne => (0, 1),
+            Repeat::ZeroOrMore => (0, text.len()),
+            Repeat::OneOrMore => (1, text.len()),
+        };
+        let available = text
+            .iter()
+            .take(max)
+            .take_while(|&&c| atom.matches(c))
+            .count();
+        // Repetitions are greedy, so try the longest one first.
+        (min..=available)
+            .rev()
+            .any(|n| self.matches_at(item + 1, &text[n..]))
+    }
+}
+
+/// Whether `text` contains a match of `pattern`. An empty pattern matches everything.
+pub(crate) fn is_match(pattern: &str, text: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    parse(pattern).is_match(&text)
+}
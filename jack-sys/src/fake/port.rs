@@ -0,0 +1,441 @@
+//! Port and connection functions of the fake server.
+use std::ffi::CStr;
+use std::mem;
+use std::ptr;
+
+use super::midi::MIDI_BUFFER_SIZE;
+use super::pattern;
+use super::server::{dispatch, id_of, port_ptr, with_server, Port, PORT_NAME_SIZE, PORT_TYPE_SIZE};
+use crate::consts::RAW_MIDI_TYPE;
+use crate::types::*;
+
+/// JACK2 keeps at most two aliases per port.
+const MAX_ALIASES: usize = 2;
+
+/// Copy `names` into a nul terminated array that the caller frees with a single `jack_free`.
+///
+/// Returns a null pointer if there are no names, like JACK does.
+fn malloc_names(names: &[&CStr]) -> *mut *const libc::c_char {
+    if names.is_empty() {
+        return ptr::null_mut();
+    }
+    let array_size = (names.len() + 1) * mem::size_of::<*const libc::c_char>();
+    let strings_size: usize = names.iter().map(|n| n.to_bytes_with_nul().len()).sum();
+    unsafe {
+        let array = libc::malloc(array_size + strings_size) as *mut *const libc::c_char;
+        if array.is_null() {
+            return array;
+        }
+        let mut string = (array as *mut u8).add(array_size);
+        for (i, name) in names.iter().enumerate() {
+            let bytes = name.to_bytes_with_nul();
+            ptr::copy_nonoverlapping(bytes.as_ptr(), string, bytes.len());
+            *array.add(i) = string as *const libc::c_char;
+            string = string.add(bytes.len());
+        }
+        *array.add(names.len()) = ptr::null();
+        array
+    }
+}
+
+/// The names of the ports connected to `port`, as returned by `jack_port_get_connections`.
+fn connection_names(port: *const jack_port_t) -> *mut *const libc::c_char {
+    with_server(|s| {
+        let port = match s.port_id(port) {
+            Some(port) => port,
+            None => return ptr::null_mut(),
+        };
+        let names: Vec<&CStr> = s
+            .connected_ports(port)
+            .into_iter()
+            .map(|other| s.ports[&other].name.as_c_str())
+            .collect();
+        malloc_names(&names)
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_port_register(
+    client: *mut jack_client_t,
+    port_name: *const libc::c_char,
+    port_type: *const libc::c_char,
+    flags: libc::c_ulong,
+    _buffer_size: libc::c_ulong,
+) -> *mut jack_port_t {
+    let short_name = CStr::from_ptr(port_name);
+    let port_type = CStr::from_ptr(port_type);
+    let port = with_server(|s| {
+        s.client(client)?;
+        s.register_port(id_of(client), short_name, port_type, flags)
+    });
+    dispatch();
+    match port {
+        Some(port) => port_ptr(port),
+        None => ptr::null_mut(),
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_port_unregister(
+    client: *mut jack_client_t,
+    port: *mut jack_port_t,
+) -> libc::c_int {
+    let res = with_server(|s| match s.port(port) {
+        Some(p) if p.client == id_of(client) => {
+            s.unregister_port(id_of(port));
+            0
+        }
+        _ => -1,
+    });
+    dispatch();
+    res
+}
+
+pub(crate) unsafe extern "C" fn jack_port_get_buffer(
+    port: *mut jack_port_t,
+    _nframes: jack_nframes_t,
+) -> *mut libc::c_void {
+    // The buffer stays in place until the next cycle or change of the buffer size.
+    with_server(|s| match s.port_mut(port) {
+        Some(p) => p.buffer.as_mut_ptr(),
+        None => ptr::null_mut(),
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_port_uuid(port: *mut jack_port_t) -> jack_uuid_t {
+    with_server(|s| s.port_id(port).map_or(0, jack_uuid_t::from))
+}
+
+// Names stay valid until the port is renamed or unregistered.
+
+pub(crate) unsafe extern "C" fn jack_port_name(port: *mut jack_port_t) -> *const libc::c_char {
+    with_server(|s| s.port(port).map_or(ptr::null(), |p| p.name.as_ptr()))
+}
+
+pub(crate) unsafe extern "C" fn jack_port_short_name(
+    port: *mut jack_port_t,
+) -> *const libc::c_char {
+    with_server(|s| {
+        s.port(port)
+            .map_or(ptr::null(), |p| p.short_name().as_ptr())
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_port_flags(port: *mut jack_port_t) -> libc::c_int {
+    with_server(|s| s.port(port).map_or(0, |p| p.flags as libc::c_int))
+}
+
+pub(crate) unsafe extern "C" fn jack_port_type(port: *const jack_port_t) -> *const libc::c_char {
+    with_server(|s| s.port(port).map_or(ptr::null(), |p| p.port_type.as_ptr()))
+}
+
+pub(crate) unsafe extern "C" fn jack_port_is_mine(
+    client: *const jack_client_t,
+    port: *const jack_port_t,
+) -> libc::c_int {
+    with_server(|s| s.port(port).is_some_and(|p| p.client == id_of(client))) as libc::c_int
+}
+
+pub(crate) unsafe extern "C" fn jack_port_connected(port: *const jack_port_t) -> libc::c_int {
+    with_server(|s| s.port_id(port).map_or(0, |p| s.connections_of(p).len())) as libc::c_int
+}
+
+pub(crate) unsafe extern "C" fn jack_port_connected_to(
+    port: *const jack_port_t,
+    port_name: *const libc::c_char,
+) -> libc::c_int {
+    let other_name = CStr::from_ptr(port_name);
+    with_server(|s| {
+        let port = s.port_id(port)?;
+        let other = s.port_by_name(other_name)?;
+        Some(s.connected_ports(port).contains(&other))
+    })
+    .unwrap_or(false) as libc::c_int
+}
+
+pub(crate) unsafe extern "C" fn jack_port_get_connections(
+    port: *const jack_port_t,
+) -> *mut *const libc::c_char {
+    connection_names(port)
+}
+
+pub(crate) unsafe extern "C" fn jack_port_get_all_connections(
+    _client: *const jack_client_t,
+    port: *const jack_port_t,
+) -> *mut *const libc::c_char {
+    connection_names(port)
+}
+
+pub(crate) unsafe extern "C" fn jack_port_set_name(
+    port: *mut jack_port_t,
+    port_name: *const libc::c_char,
+) -> libc::c_int {
+    let short_name = CStr::from_ptr(port_name);
+    let is_renamed = with_server(|s| s.rename_port(id_of(port), short_name));
+    dispatch();
+    match is_renamed {
+        true => 0,
+        false => -1,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_port_set_alias(
+    port: *mut jack_port_t,
+    alias: *const libc::c_char,
+) -> libc::c_int {
+    let alias = CStr::from_ptr(alias);
+    with_server(|s| {
+        let is_taken = s.port_by_name(alias).is_some();
+        match s.port_mut(port) {
+            Some(p)
+                if !is_taken
+                    && p.aliases.len() < MAX_ALIASES
+                    && alias.to_bytes().len() < PORT_NAME_SIZE =>
+            {
+                p.aliases.push(alias.to_owned());
+                0
+            }
+            _ => -1,
+        }
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_port_unset_alias(
+    port: *mut jack_port_t,
+    alias: *const libc::c_char,
+) -> libc::c_int {
+    let alias = CStr::from_ptr(alias);
+    with_server(|s| {
+        let p = s.port_mut(port)?;
+        let index = p.aliases.iter().position(|a| a.as_c_str() == alias)?;
+        p.aliases.remove(index);
+        Some(())
+    })
+    .map_or(-1, |()| 0)
+}
+
+pub(crate) unsafe extern "C" fn jack_port_get_aliases(
+    port: *const jack_port_t,
+    aliases: *mut *mut libc::c_char,
+) -> libc::c_int {
+    with_server(|s| {
+        let p = match s.port(port) {
+            Some(p) => p,
+            None => return 0,
+        };
+        // Each of `aliases` has room for a port name.
+        for (i, alias) in p.aliases.iter().enumerate() {
+            let bytes = alias.as_bytes_with_nul();
+            ptr::copy_nonoverlapping(
+                bytes.as_ptr() as *const libc::c_char,
+                *aliases.add(i),
+                bytes.len(),
+            );
+        }
+        p.aliases.len() as libc::c_int
+    })
+}
+
+/// Apply a monitor request to `port`.
+fn request_monitor(port: &mut Port, onoff: libc::c_int) {
+    match onoff {
+        0 => port.monitor_requests = port.monitor_requests.saturating_sub(1),
+        _ => port.monitor_requests += 1,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_port_request_monitor(
+    port: *mut jack_port_t,
+    onoff: libc::c_int,
+) -> libc::c_int {
+    with_server(|s| match s.port_mut(port) {
+        Some(p) => {
+            request_monitor(p, onoff);
+            0
+        }
+        None => -1,
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_port_request_monitor_by_name(
+    _client: *mut jack_client_t,
+    port_name: *const libc::c_char,
+    onoff: libc::c_int,
+) -> libc::c_int {
+    let name = CStr::from_ptr(port_name);
+    with_server(|s| match s.port_by_name(name) {
+        Some(port) => {
+            request_monitor(s.ports.get_mut(&port).unwrap(), onoff);
+            0
+        }
+        None => -1,
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_port_ensure_monitor(
+    port: *mut jack_port_t,
+    onoff: libc::c_int,
+) -> libc::c_int {
+    with_server(|s| match s.port_mut(port) {
+        Some(p) => {
+            match (onoff != 0, p.monitor_requests) {
+                (true, 0) => p.monitor_requests = 1,
+                (false, n) if n > 0 => p.monitor_requests = 0,
+                _ => (),
+            }
+            0
+        }
+        None => -1,
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_port_monitoring_input(port: *mut jack_port_t) -> libc::c_int {
+    with_server(|s| s.port(port).is_some_and(|p| p.monitor_requests > 0)) as libc::c_int
+}
+
+pub(crate) unsafe extern "C" fn jack_connect(
+    client: *mut jack_client_t,
+    source_port: *const libc::c_char,
+    destination_port: *const libc::c_char,
+) -> libc::c_int {
+    let source = CStr::from_ptr(source_port);
+    let destination = CStr::from_ptr(destination_port);
+    let res = with_server(|s| {
+        if s.client(client).is_none() {
+            return -1;
+        }
+        match (s.port_by_name(source), s.port_by_name(destination)) {
+            (Some(output), Some(input)) => s.connect(output, input),
+            _ => -1,
+        }
+    });
+    dispatch();
+    res
+}
+
+pub(crate) unsafe extern "C" fn jack_disconnect(
+    client: *mut jack_client_t,
+    source_port: *const libc::c_char,
+    destination_port: *const libc::c_char,
+) -> libc::c_int {
+    let source = CStr::from_ptr(source_port);
+    let destination = CStr::from_ptr(destination_port);
+    let res = with_server(|s| {
+        if s.client(client).is_none() {
+            return -1;
+        }
+        match (s.port_by_name(source), s.port_by_name(destination)) {
+            (Some(output), Some(input)) => s.disconnect(output, input),
+            _ => -1,
+        }
+    });
+    dispatch();
+    res
+}
+
+pub(crate) unsafe extern "C" fn jack_port_disconnect(
+    client: *mut jack_client_t,
+    port: *mut jack_port_t,
+) -> libc::c_int {
+    let res = with_server(|s| match (s.client(client), s.port_id(port)) {
+        (Some(_), Some(port)) => {
+            s.disconnect_all(port);
+            0
+        }
+        _ => -1,
+    });
+    dispatch();
+    res
+}
+
+pub(crate) unsafe extern "C" fn jack_port_name_size() -> libc::c_int {
+    PORT_NAME_SIZE as libc::c_int
+}
+
+pub(crate) unsafe extern "C" fn jack_port_type_size() -> libc::c_int {
+    PORT_TYPE_SIZE as libc::c_int
+}
+
+pub(crate) unsafe extern "C" fn jack_port_type_get_buffer_size(
+    _client: *mut jack_client_t,
+    port_type: *const libc::c_char,
+) -> libc::size_t {
+    let port_type = CStr::from_ptr(port_type);
+    match port_type.to_bytes() == RAW_MIDI_TYPE.as_bytes() {
+        true => MIDI_BUFFER_SIZE,
+        false => with_server(|s| s.buffer_size as usize * mem::size_of::<f32>()),
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_port_get_latency_range(
+    port: *mut jack_port_t,
+    mode: jack_latency_callback_mode_t,
+    range: *mut jack_latency_range_t,
+) {
+    with_server(|s| {
+        *range = match s.port(port) {
+            Some(p) => p.latency[mode as usize],
+            None => jack_latency_range_t::default(),
+        };
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_port_set_latency_range(
+    port: *mut jack_port_t,
+    mode: jack_latency_callback_mode_t,
+    range: *mut jack_latency_range_t,
+) {
+    with_server(|s| {
+        if let Some(p) = s.port_mut(port) {
+            p.latency[mode as usize] = *range;
+        }
+    })
+}
+
+/// Latencies are not propagated along connections, so there is nothing to recompute.
+pub(crate) unsafe extern "C" fn jack_recompute_total_latencies(
+    client: *mut jack_client_t,
+) -> libc::c_int {
+    with_server(|s| s.client(client).map_or(-1, |_| 0))
+}
+
+pub(crate) unsafe extern "C" fn jack_get_ports(
+    _client: *mut jack_client_t,
+    port_name_pattern: *const libc::c_char,
+    type_name_pattern: *const libc::c_char,
+    flags: libc::c_ulong,
+) -> *mut *const libc::c_char {
+    let pattern_of = |p: *const libc::c_char| match p.is_null() {
+        true => String::new(),
+        false => CStr::from_ptr(p).to_string_lossy().into_owned(),
+    };
+    let name_pattern = pattern_of(port_name_pattern);
+    let type_pattern = pattern_of(type_name_pattern);
+    with_server(|s| {
+        let names: Vec<&CStr> = s
+            .ports
+            .values()
+            .filter(|p| p.flags & flags == flags)
+            .filter(|p| pattern::is_match(&name_pattern, &p.name.to_string_lossy()))
+            .filter(|p| pattern::is_match(&type_pattern, &p.port_type.to_string_lossy()))
+            .map(|p| p.name.as_c_str())
+            .collect();
+        malloc_names(&names)
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_port_by_name(
+    _client: *mut jack_client_t,
+    port_name: *const libc::c_char,
+) -> *mut jack_port_t {
+    let name = CStr::from_ptr(port_name);
+    with_server(|s| s.port_by_name(name).map_or(ptr::null_mut(), port_ptr))
+}
+
+pub(crate) unsafe extern "C" fn jack_port_by_id(
+    _client: *mut jack_client_t,
+    port_id: jack_port_id_t,
+) -> *mut jack_port_t {
+    with_server(|s| match s.ports.contains_key(&port_id) {
+        true => port_ptr(port_id),
+        false => ptr::null_mut(),
+    })
+}
@@ -0,0 +1,174 @@
+//! The lock-free ring buffer of JACK, ported from `ringbuffer.c`.
+//!
+//! The read and write pointers are accessed atomically, so that a reader and a writer on
+//! different threads see each other's progress.
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::types::*;
+
+unsafe fn read_ptr<'a>(rb: *const jack_ringbuffer_t) -> &'a AtomicUsize {
+    &*(ptr::addr_of!((*rb).read_ptr) as *const AtomicUsize)
+}
+
+unsafe fn write_ptr<'a>(rb: *const jack_ringbuffer_t) -> &'a AtomicUsize {
+    &*(ptr::addr_of!((*rb).write_ptr) as *const AtomicUsize)
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_create(sz: libc::size_t) -> *mut jack_ringbuffer_t {
+    let size = sz.max(1).next_power_of_two();
+    let buf = libc::calloc(size, 1) as *mut libc::c_char;
+    if buf.is_null() {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(jack_ringbuffer_t {
+        buf,
+        write_ptr: 0,
+        read_ptr: 0,
+        size,
+        size_mask: size - 1,
+        mlocked: 0,
+    }))
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_free(rb: *mut jack_ringbuffer_t) {
+    let rb = Box::from_raw(rb);
+    libc::free(rb.buf as *mut libc::c_void);
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_mlock(rb: *mut jack_ringbuffer_t) -> libc::c_int {
+    // The buffer is not actually locked into memory, which only matters for real-time use.
+    (*rb).mlocked = 1;
+    0
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_reset(rb: *mut jack_ringbuffer_t) {
+    read_ptr(rb).store(0, Ordering::Release);
+    write_ptr(rb).store(0, Ordering::Release);
+    ptr::write_bytes((*rb).buf, 0, (*rb).size);
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_read_space(
+    rb: *const jack_ringbuffer_t,
+) -> libc::size_t {
+    let w = write_ptr(rb).load(Ordering::Acquire);
+    let r = read_ptr(rb).load(Ordering::Acquire);
+    match w > r {
+        true => w - r,
+        false => (w + (*rb).size - r) & (*rb).size_mask,
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_write_space(
+    rb: *const jack_ringbuffer_t,
+) -> libc::size_t {
+    let w = write_ptr(rb).load(Ordering::Acquire);
+    let r = read_ptr(rb).load(Ordering::Acquire);
+    match w.cmp(&r) {
+        std::cmp::Ordering::Greater => ((r + (*rb).size - w) & (*rb).size_mask) - 1,
+        std::cmp::Ordering::Less => r - w - 1,
+        std::cmp::Ordering::Equal => (*rb).size - 1,
+    }
+}
+
+/// Split `count` bytes starting at `start` into the parts before and after the end of the buffer.
+unsafe fn vector(
+    rb: *const jack_ringbuffer_t,
+    start: usize,
+    count: usize,
+    vec: *mut jack_ringbuffer_data_t,
+) {
+    let end = start + count;
+    let (first, second) = match end > (*rb).size {
+        true => ((*rb).size - start, end & (*rb).size_mask),
+        false => (count, 0),
+    };
+    *vec = jack_ringbuffer_data_t {
+        buf: (*rb).buf.add(start),
+        len: first,
+    };
+    *vec.add(1) = jack_ringbuffer_data_t {
+        buf: (*rb).buf,
+        len: second,
+    };
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_get_read_vector(
+    rb: *const jack_ringbuffer_t,
+    vec: *mut jack_ringbuffer_data_t,
+) {
+    let count = jack_ringbuffer_read_space(rb);
+    vector(rb, read_ptr(rb).load(Ordering::Acquire), count, vec);
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_get_write_vector(
+    rb: *const jack_ringbuffer_t,
+    vec: *mut jack_ringbuffer_data_t,
+) {
+    let count = jack_ringbuffer_write_space(rb);
+    vector(rb, write_ptr(rb).load(Ordering::Acquire), count, vec);
+}
+
+/// Copy up to `cnt` readable bytes to `dest`, and return the number of bytes copied.
+unsafe fn copy_out(rb: *const jack_ringbuffer_t, dest: *mut libc::c_char, cnt: usize) -> usize {
+    let mut vec = [jack_ringbuffer_data_t::default(); 2];
+    jack_ringbuffer_get_read_vector(rb, vec.as_mut_ptr());
+    let mut copied = 0;
+    for part in &vec {
+        let n = part.len.min(cnt - copied);
+        ptr::copy_nonoverlapping(part.buf, dest.add(copied), n);
+        copied += n;
+    }
+    copied
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_read(
+    rb: *mut jack_ringbuffer_t,
+    dest: *mut libc::c_char,
+    cnt: libc::size_t,
+) -> libc::size_t {
+    let read = copy_out(rb, dest, cnt);
+    jack_ringbuffer_read_advance(rb, read);
+    read
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_peek(
+    rb: *mut jack_ringbuffer_t,
+    dest: *mut libc::c_char,
+    cnt: libc::size_t,
+) -> libc::size_t {
+    copy_out(rb, dest, cnt)
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_read_advance(
+    rb: *mut jack_ringbuffer_t,
+    cnt: libc::size_t,
+) {
+    let r = read_ptr(rb).load(Ordering::Acquire);
+    read_ptr(rb).store((r + cnt) & (*rb).size_mask, Ordering::Release);
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_write(
+    rb: *mut jack_ringbuffer_t,
+    src: *const libc::c_char,
+    cnt: libc::size_t,
+) -> libc::size_t {
+    let mut vec = [jack_ringbuffer_data_t::default(); 2];
+    jack_ringbuffer_get_write_vector(rb, vec.as_mut_ptr());
+    let mut written = 0;
+    for part in &vec {
+        let n = part.len.min(cnt - written);
+        ptr::copy_nonoverlapping(src.add(written), part.buf, n);
+        written += n;
+    }
+    jack_ringbuffer_write_advance(rb, written);
+    written
+}
+
+pub(crate) unsafe extern "C" fn jack_ringbuffer_write_advance(
+    rb: *mut jack_ringbuffer_t,
+    cnt: libc::size_t,
+) {
+    let w = write_ptr(rb).load(Ordering::Acquire);
+    write_ptr(rb).store((w + cnt) & (*rb).size_mask, Ordering::Release);
+}
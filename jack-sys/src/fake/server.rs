@@ -0,0 +1,803 @@
+//! The state of a fake server, and how its callbacks are called.
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use super::midi::MidiBuffer;
+use super::transport::Transport;
+use crate::consts::RAW_MIDI_TYPE;
+use crate::types::*;
+
+pub(crate) const DEFAULT_SAMPLE_RATE: jack_nframes_t = 44100;
+pub(crate) const DEFAULT_BUFFER_SIZE: jack_nframes_t = 1024;
+pub(crate) const MAX_BUFFER_SIZE: jack_nframes_t = 8192;
+
+// The sizes of names, including the terminating nul character, are the same as in JACK2.
+pub(crate) const CLIENT_NAME_SIZE: usize = 64;
+pub(crate) const PORT_NAME_SIZE: usize = 320;
+pub(crate) const PORT_TYPE_SIZE: usize = 32;
+
+// Clients and ports get ids that are unique among all servers, so that the pointer of a client or
+// port that is gone is never mistaken for a newer one.
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+thread_local! {
+    static SERVER: RefCell<Server> = RefCell::new(Server::new());
+    static IS_DISPATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f` with the server of the current thread.
+///
+/// No callback may be called from `f`, as callbacks call back into the server.
+pub(crate) fn with_server<R>(f: impl FnOnce(&mut Server) -> R) -> R {
+    SERVER.with(|server| f(&mut server.borrow_mut()))
+}
+
+/// Replace the server of the current thread with a new one.
+pub(crate) fn replace_server() {
+    let old = SERVER.with(|server| server.replace(Server::new()));
+    drop(old);
+}
+
+fn next_id() -> u32 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub(crate) fn client_ptr(id: u32) -> *mut jack_client_t {
+    id as usize as *mut jack_client_t
+}
+
+pub(crate) fn port_ptr(id: jack_port_id_t) -> *mut jack_port_t {
+    id as usize as *mut jack_port_t
+}
+
+pub(crate) fn id_of<T>(ptr: *const T) -> u32 {
+    u32::try_from(ptr as usize).unwrap_or(0)
+}
+
+/// A callback set by a client, and the argument it is called with.
+pub(crate) struct Slot<T> {
+    callback: T,
+    arg: *mut libc::c_void,
+}
+
+impl<F: Copy> Slot<Option<F>> {
+    pub(crate) fn set(&mut self, callback: Option<F>, arg: *mut libc::c_void) {
+        self.callback = callback;
+        self.arg = arg;
+    }
+
+    pub(crate) fn get(&self) -> Option<(F, *mut libc::c_void)> {
+        self.callback.map(|f| (f, self.arg))
+    }
+}
+
+impl<F> Default for Slot<Option<F>> {
+    fn default() -> Self {
+        Slot {
+            callback: None,
+            arg: ptr::null_mut(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Callbacks {
+    pub(crate) process: Slot<JackProcessCallback>,
+    pub(crate) thread_init: Slot<JackThreadInitCallback>,
+    pub(crate) shutdown: Slot<JackShutdownCallback>,
+    pub(crate) info_shutdown: Slot<JackInfoShutdownCallback>,
+    pub(crate) freewheel: Slot<JackFreewheelCallback>,
+    pub(crate) buffer_size: Slot<JackBufferSizeCallback>,
+    pub(crate) sample_rate: Slot<JackSampleRateCallback>,
+    pub(crate) client_registration: Slot<JackClientRegistrationCallback>,
+    pub(crate) port_registration: Slot<JackPortRegistrationCallback>,
+    pub(crate) port_connect: Slot<JackPortConnectCallback>,
+    pub(crate) port_rename: Slot<JackPortRenameCallback>,
+    pub(crate) graph_order: Slot<JackGraphOrderCallback>,
+    pub(crate) xrun: Slot<JackXRunCallback>,
+    pub(crate) latency: Slot<JackLatencyCallback>,
+    pub(crate) sync: Slot<JackSyncCallback>,
+}
+
+pub(crate) struct Client {
+    pub(crate) name: CString,
+    pub(crate) is_active: bool,
+    // Clients are processed in the order of their activation, unless their connections require
+    // otherwise.
+    activation: u64,
+    pub(crate) callbacks: Callbacks,
+}
+
+pub(crate) enum Buffer {
+    Audio(Vec<f32>),
+    Midi(Box<MidiBuffer>),
+}
+
+impl Buffer {
+    fn new(port_type: &CStr, n_frames: jack_nframes_t) -> Buffer {
+        match port_type.to_bytes() == RAW_MIDI_TYPE.as_bytes() {
+            true => Buffer::Midi(Box::new(MidiBuffer::new(n_frames))),
+            false => Buffer::Audio(vec![0.0; n_frames as usize]),
+        }
+    }
+
+    fn resize(&mut self, n_frames: jack_nframes_t) {
+        match self {
+            Buffer::Audio(samples) => *samples = vec![0.0; n_frames as usize],
+            Buffer::Midi(midi) => midi.reset(n_frames),
+        }
+    }
+
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut libc::c_void {
+        match self {
+            Buffer::Audio(samples) => samples.as_mut_ptr() as *mut libc::c_void,
+            Buffer::Midi(midi) => midi.as_mut() as *mut MidiBuffer as *mut libc::c_void,
+        }
+    }
+}
+
+pub(crate) struct Port {
+    pub(crate) client: u32,
+    pub(crate) name: CString,
+    short_name_start: usize,
+    pub(crate) port_type: CString,
+    pub(crate) flags: libc::c_ulong,
+    pub(crate) aliases: Vec<CString>,
+    pub(crate) monitor_requests: u32,
+    // Indexed by `jack_latency_callback_mode_t`.
+    pub(crate) latency: [jack_latency_range_t; 2],
+    pub(crate) buffer: Buffer,
+}
+
+impl Port {
+    pub(crate) fn short_name(&self) -> &CStr {
+        let bytes = self.name.as_bytes_with_nul();
+        CStr::from_bytes_with_nul(&bytes[self.short_name_start..]).unwrap()
+    }
+
+    pub(crate) fn has_name(&self, name: &CStr) -> bool {
+        self.name.as_c_str() == name || self.aliases.iter().any(|a| a.as_c_str() == name)
+    }
+
+    pub(crate) fn is_input(&self) -> bool {
+        self.flags & libc::c_ulong::from(JackPortIsInput) != 0
+    }
+
+    pub(crate) fn is_output(&self) -> bool {
+        self.flags & libc::c_ulong::from(JackPortIsOutput) != 0
+    }
+}
+
+/// A notification, queued until it can be delivered to the clients.
+pub(crate) enum Event {
+    /// A client was activated. Only that client is notified.
+    Activated(u32),
+    BufferSize(jack_nframes_t),
+    SampleRate(jack_nframes_t),
+    Freewheel(bool),
+    ClientRegistration(CString, bool),
+    PortRegistration(jack_port_id_t, bool),
+    PortRename(jack_port_id_t, CString, CString),
+    PortConnect(jack_port_id_t, jack_port_id_t, bool),
+    GraphOrder,
+    Latency,
+    Xrun,
+}
+
+pub(crate) struct Server {
+    pub(crate) is_running: bool,
+    pub(crate) sample_rate: jack_nframes_t,
+    pub(crate) buffer_size: jack_nframes_t,
+    /// The number of frames processed since the server started.
+    pub(crate) frames: u64,
+    /// The frame at which the current, or else the last, cycle started.
+    pub(crate) cycle_start: u64,
+    pub(crate) is_freewheeling: bool,
+    pub(crate) clients: BTreeMap<u32, Client>,
+    pub(crate) ports: BTreeMap<jack_port_id_t, Port>,
+    /// Connections as pairs of an output and an input port.
+    pub(crate) connections: BTreeSet<(jack_port_id_t, jack_port_id_t)>,
+    pub(crate) transport: Transport,
+    events: VecDeque<Event>,
+    activations: u64,
+}
+
+impl Server {
+    /// A running server with the ports of the dummy backend of `jackd`.
+    fn new() -> Server {
+        let mut server = Server {
+            is_running: true,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            frames: 0,
+            cycle_start: 0,
+            is_freewheeling: false,
+            clients: BTreeMap::new(),
+            ports: BTreeMap::new(),
+            connections: BTreeSet::new(),
+            transport: Transport::new(),
+            events: VecDeque::new(),
+            activations: 0,
+        };
+        let system = next_id();
+        server.clients.insert(
+            system,
+            Client {
+                name: CString::new("system").unwrap(),
+                is_active: true,
+                activation: 0,
+                callbacks: Callbacks::default(),
+            },
+        );
+        let audio = CString::new(crate::FLOAT_MONO_AUDIO).unwrap();
+        let physical = JackPortIsPhysical | JackPortIsTerminal;
+        for (name, flags) in &[
+            ("capture_1", JackPortIsOutput | physical),
+            ("capture_2", JackPortIsOutput | physical),
+            ("playback_1", JackPortIsInput | physical),
+            ("playback_2", JackPortIsInput | physical),
+        ] {
+            let name = CString::new(*name).unwrap();
+            server.register_port(system, &name, &audio, (*flags).into());
+        }
+        server
+    }
+
+    /// Queue `event` for delivery by `dispatch`.
+    pub(crate) fn notify(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+    pub(crate) fn client(&self, client: *const jack_client_t) -> Option<&Client> {
+        self.clients.get(&id_of(client))
+    }
+
+    pub(crate) fn client_mut(&mut self, client: *const jack_client_t) -> Option<&mut Client> {
+        self.clients.get_mut(&id_of(client))
+    }
+
+    pub(crate) fn client_by_name(&self, name: &CStr) -> Option<u32> {
+        self.clients
+            .iter()
+            .find(|(_, c)| c.name.as_c_str() == name)
+            .map(|(&id, _)| id)
+    }
+
+    pub(crate) fn port(&self, port: *const jack_port_t) -> Option<&Port> {
+        self.ports.get(&id_of(port))
+    }
+
+    pub(crate) fn port_mut(&mut self, port: *const jack_port_t) -> Option<&mut Port> {
+        self.ports.get_mut(&id_of(port))
+    }
+
+    pub(crate) fn port_id(&self, port: *const jack_port_t) -> Option<jack_port_id_t> {
+        let id = id_of(port);
+        self.ports.get(&id).map(|_| id)
+    }
+
+    /// Find a port by its full name or one of its aliases.
+    pub(crate) fn port_by_name(&self, name: &CStr) -> Option<jack_port_id_t> {
+        self.ports
+            .iter()
+            .find(|(_, p)| p.has_name(name))
+            .map(|(&id, _)| id)
+    }
+
+    /// Open a client, and return its id and the status bits of opening it.
+    ///
+    /// On failure, only the status bits are returned.
+    pub(crate) fn open_client(
+        &mut self,
+        name: &CStr,
+        options: jack_options_t,
+    ) -> Result<(u32, jack_status_t), jack_status_t> {
+        if !self.is_running {
+            return Err(JackFailure | JackServerFailed);
+        }
+        if name.to_bytes().is_empty() || name.to_bytes().len() >= CLIENT_NAME_SIZE {
+            return Err(JackFailure | JackInvalidOption);
+        }
+        let mut status = 0;
+        let mut name = name.to_owned();
+        if self.client_by_name(&name).is_some() {
+            if options & JackUseExactName != 0 {
+                return Err(JackFailure | JackNameNotUnique);
+            }
+            // Like JACK, append a number to make the name unique.
+            let base = name.to_string_lossy().into_owned();
+            name = (1..)
+                .map(|n| CString::new(format!("{}-{:02}", base, n)).unwrap())
+                .find(|n| self.client_by_name(n).is_none())
+                .unwrap();
+            if name.to_bytes().len() >= CLIENT_NAME_SIZE {
+                return Err(JackFailure | JackNameNotUnique);
+            }
+            status |= JackNameNotUnique;
+        }
+        let id = next_id();
+        self.notify(Event::ClientRegistration(name.clone(), true));
+        self.clients.insert(
+            id,
+            Client {
+                name,
+                is_active: false,
+                activation: 0,
+                callbacks: Callbacks::default(),
+            },
+        );
+        Ok((id, status))
+    }
+
+    pub(crate) fn close_client(&mut self, id: u32) {
+        self.deactivate_client(id);
+        let ports: Vec<_> = self
+            .ports
+            .iter()
+            .filter(|(_, p)| p.client == id)
+            .map(|(&port, _)| port)
+            .collect();
+        for port in ports {
+            self.unregister_port(port);
+        }
+        if let Some(client) = self.clients.remove(&id) {
+            if self.is_running {
+                self.notify(Event::ClientRegistration(client.name, false));
+            }
+        }
+    }
+
+    pub(crate) fn activate_client(&mut self, id: u32) -> bool {
+        if !self.is_running {
+            return false;
+        }
+        self.activations += 1;
+        let activation = self.activations;
+        match self.clients.get_mut(&id) {
+            Some(client) if client.is_active => true,
+            Some(client) => {
+                client.is_active = true;
+                client.activation = activation;
+                self.notify(Event::Activated(id));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn deactivate_client(&mut self, id: u32) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.is_active = false;
+        }
+        if self.transport.timebase_master == Some(id) {
+            self.transport.timebase_master = None;
+        }
+    }
+
+    pub(crate) fn register_port(
+        &mut self,
+        client: u32,
+        short_name: &CStr,
+        port_type: &CStr,
+        flags: libc::c_ulong,
+    ) -> Option<jack_port_id_t> {
+        let client_name = self.clients.get(&client)?.name.to_bytes();
+        let mut name = Vec::with_capacity(client_name.len() + 1 + short_name.to_bytes().len());
+        name.extend_from_slice(client_name);
+        name.push(b':');
+        name.extend_from_slice(short_name.to_bytes());
+        let short_name_start = client_name.len() + 1;
+        let name = CString::new(name).unwrap();
+        let is_valid = !short_name.to_bytes().is_empty()
+            && name.to_bytes().len() < PORT_NAME_SIZE
+            && !port_type.to_bytes().is_empty()
+            && port_type.to_bytes().len() < PORT_TYPE_SIZE;
+        if !is_valid || self.port_by_name(&name).is_some() {
+            return None;
+        }
+        let id = next_id();
+        let port = Port {
+            client,
+            name,
+            short_name_start,
+            port_type: port_type.to_owned(),
+            flags,
+            aliases: Vec::new(),
+            monitor_requests: 0,
+            latency: Default::default(),
+            buffer: Buffer::new(port_type, self.buffer_size),
+        };
+        self.ports.insert(id, port);
+        self.notify(Event::PortRegistration(id, true));
+        Some(id)
+    }
+
+    pub(crate) fn unregister_port(&mut self, port: jack_port_id_t) {
+        self.disconnect_all(port);
+        if self.ports.remove(&port).is_some() {
+            self.notify(Event::PortRegistration(port, false));
+        }
+    }
+
+    pub(crate) fn rename_port(&mut self, port: jack_port_id_t, short_name: &CStr) -> bool {
+        let client = match self.ports.get(&port) {
+            Some(p) => p.client,
+            None => return false,
+        };
+        let old_name = self.ports[&port].name.clone();
+        let mut name = self.clients[&client].name.to_bytes().to_vec();
+        name.push(b':');
+        name.extend_from_slice(short_name.to_bytes());
+        let name = CString::new(name).unwrap();
+        let is_taken = match self.port_by_name(&name) {
+            Some(other) => other != port,
+            None => false,
+        };
+        if short_name.to_bytes().is_empty() || name.to_bytes().len() >= PORT_NAME_SIZE || is_taken {
+            return false;
+        }
+        self.ports.get_mut(&port).unwrap().name = name.clone();
+        self.notify(Event::PortRename(port, old_name, name));
+        true
+    }
+
+    /// Connect an output to an input port.
+    ///
+    /// Returns 0 on success, `EEXIST` if the ports are already connected and -1 on other failures.
+    pub(crate) fn connect(&mut self, output: jack_port_id_t, input: jack_port_id_t) -> libc::c_int {
+        let (out_port, in_port) = match (self.ports.get(&output), self.ports.get(&input)) {
+            (Some(o), Some(i)) => (o, i),
+            _ => return -1,
+        };
+        if !out_port.is_output() || !in_port.is_input() || out_port.port_type != in_port.port_type {
+            return -1;
+        }
+        if !self.connections.insert((output, input)) {
+            return libc::EEXIST;
+        }
+        self.notify(Event::PortConnect(output, input, true));
+        self.notify(Event::GraphOrder);
+        self.notify(Event::Latency);
+        0
+    }
+
+    pub(crate) fn disconnect(
+        &mut self,
+        output: jack_port_id_t,
+        input: jack_port_id_t,
+    ) -> libc::c_int {
+        if !self.connections.remove(&(output, input)) {
+            return -1;
+        }
+        self.notify(Event::PortConnect(output, input, false));
+        self.notify(Event::GraphOrder);
+        self.notify(Event::Latency);
+        0
+    }
+
+    pub(crate) fn disconnect_all(&mut self, port: jack_port_id_t) {
+        for (output, input) in self.connections_of(port) {
+            self.disconnect(output, input);
+        }
+    }
+
+    /// The connections `port` is part of.
+    pub(crate) fn connections_of(
+        &self,
+        port: jack_port_id_t,
+    ) -> Vec<(jack_port_id_t, jack_port_id_t)> {
+        self.connections
+            .iter()
+            .filter(|&&(output, input)| output == port || input == port)
+            .cloned()
+            .collect()
+    }
+
+    /// The ports connected to `port`.
+    pub(crate) fn connected_ports(&self, port: jack_port_id_t) -> Vec<jack_port_id_t> {
+        self.connections_of(port)
+            .into_iter()
+            .map(|(output, input)| if output == port { input } else { output })
+            .collect()
+    }
+
+    pub(crate) fn set_buffer_size(&mut self, n_frames: jack_nframes_t) -> bool {
+        if !self.is_running || n_frames == 0 || n_frames > MAX_BUFFER_SIZE {
+            return false;
+        }
+        if n_frames != self.buffer_size {
+            self.buffer_size = n_frames;
+            for port in self.ports.values_mut() {
+                port.buffer.resize(n_frames);
+            }
+            self.notify(Event::BufferSize(n_frames));
+        }
+        true
+    }
+
+    /// Convert frames since the server started to microseconds.
+    pub(crate) fn frames_to_usecs(&self, frames: u64) -> jack_time_t {
+        frames * 1_000_000 / u64::from(self.sample_rate)
+    }
+
+    pub(crate) fn usecs_to_frames(&self, usecs: jack_time_t) -> u64 {
+        usecs * u64::from(self.sample_rate) / 1_000_000
+    }
+
+    /// The callbacks that `get` selects from all active clients.
+    pub(crate) fn callbacks<F>(
+        &self,
+        get: impl Fn(&Callbacks) -> Option<(F, *mut libc::c_void)>,
+    ) -> Vec<(F, *mut libc::c_void)> {
+        self.clients
+            .values()
+            .filter(|c| c.is_active)
+            .filter_map(|c| get(&c.callbacks))
+            .collect()
+    }
+
+    /// Stop the server. Clients stay open but become inactive, and everything else is removed.
+    fn stop(&mut self) {
+        self.is_running = false;
+        for client in self.clients.values_mut() {
+            client.is_active = false;
+            client.callbacks = Callbacks::default();
+        }
+        self.ports.clear();
+        self.connections.clear();
+        self.events.clear();
+        self.transport = Transport::new();
+    }
+
+    /// The active clients in the order they are processed in.
+    ///
+    /// A client is processed after the clients that feed its input ports, and otherwise in the
+    /// order of activation. Clients in a feedback loop are processed in the order of activation.
+    fn process_order(&self) -> Vec<u32> {
+        let mut pending: Vec<u32> = self
+            .clients
+            .iter()
+            .filter(|(_, c)| c.is_active)
+            .map(|(&id, _)| id)
+            .collect();
+        pending.sort_by_key(|id| self.clients[id].activation);
+        let feeds = |from: u32, to: u32| {
+            from != to
+                && self.connections.iter().any(|(output, input)| {
+                    self.ports[output].client == from && self.ports[input].client == to
+                })
+        };
+        let mut order = Vec::with_capacity(pending.len());
+        while !pending.is_empty() {
+            let next = pending
+                .iter()
+                .position(|&to| !pending.iter().any(|&from| feeds(from, to)))
+                .unwrap_or(0);
+            order.push(pending.remove(next));
+        }
+        order
+    }
+
+    /// Fill the buffers of the input ports of `client` from the ports connected to them.
+    fn mix_inputs(&mut self, client: u32) {
+        let inputs: Vec<jack_port_id_t> = self
+            .ports
+            .iter()
+            .filter(|(_, p)| p.client == client && p.is_input())
+            .map(|(&id, _)| id)
+            .collect();
+        for input in inputs {
+            let sources: Vec<jack_port_id_t> = self
+                .connections
+                .iter()
+                .filter(|&&(_, i)| i == input)
+                .map(|&(output, _)| output)
+                .collect();
+            let mut buffer = match &self.ports[&input].buffer {
+                Buffer::Audio(_) => Buffer::Audio(vec![0.0; self.buffer_size as usize]),
+                Buffer::Midi(_) => Buffer::Midi(Box::new(MidiBuffer::new(self.buffer_size))),
+            };
+            for source in sources {
+                match (&mut buffer, &self.ports[&source].buffer) {
+                    (Buffer::Audio(mix), Buffer::Audio(samples)) => {
+                        for (m, s) in mix.iter_mut().zip(samples) {
+                            *m += s;
+                        }
+                    }
+                    (Buffer::Midi(mix), Buffer::Midi(events)) => mix.merge(events),
+                    _ => (),
+                }
+            }
+            self.ports.get_mut(&input).unwrap().buffer = buffer;
+        }
+    }
+}
+
+/// Marks that notifications are being delivered on this thread. Notifications that are caused
+/// in the meantime are queued, and delivered once the callback that caused them returns.
+struct Dispatching;
+
+impl Dispatching {
+    /// Returns `None` if notifications are already being delivered.
+    fn start() -> Option<Dispatching> {
+        match IS_DISPATCHING.with(|d| d.replace(true)) {
+            true => None,
+            false => Some(Dispatching),
+        }
+    }
+}
+
+impl Drop for Dispatching {
+    fn drop(&mut self) {
+        IS_DISPATCHING.with(|d| d.set(false));
+    }
+}
+
+/// Deliver the queued notifications.
+pub(crate) fn dispatch() {
+    if let Some(_dispatching) = Dispatching::start() {
+        unsafe { deliver_queued() };
+    }
+}
+
+unsafe fn deliver_queued() {
+    while let Some(event) = with_server(|s| s.events.pop_front()) {
+        deliver(event);
+    }
+}
+
+unsafe fn deliver(event: Event) {
+    // The callbacks are collected before they are called, as they may call back into the server.
+    match event {
+        Event::Activated(id) => {
+            let (thread_init, buffer_size, n_frames) = with_server(|s| match s.clients.get(&id) {
+                Some(c) if c.is_active => (
+                    c.callbacks.thread_init.get(),
+                    c.callbacks.buffer_size.get(),
+                    s.buffer_size,
+                ),
+                _ => (None, None, 0),
+            });
+            if let Some((f, arg)) = thread_init {
+                f(arg);
+            }
+            if let Some((f, arg)) = buffer_size {
+                f(n_frames, arg);
+            }
+        }
+        Event::BufferSize(n_frames) => {
+            for (f, arg) in with_server(|s| s.callbacks(|c| c.buffer_size.get())) {
+                f(n_frames, arg);
+            }
+        }
+        Event::SampleRate(n_frames) => {
+            for (f, arg) in with_server(|s| s.callbacks(|c| c.sample_rate.get())) {
+                f(n_frames, arg);
+            }
+        }
+        Event::Freewheel(is_starting) => {
+            for (f, arg) in with_server(|s| s.callbacks(|c| c.freewheel.get())) {
+                f(is_starting as libc::c_int, arg);
+            }
+        }
+        Event::ClientRegistration(name, is_registered) => {
+            for (f, arg) in with_server(|s| s.callbacks(|c| c.client_registration.get())) {
+                f(name.as_ptr(), is_registered as libc::c_int, arg);
+            }
+        }
+        Event::PortRegistration(port, is_registered) => {
+            for (f, arg) in with_server(|s| s.callbacks(|c| c.port_registration.get())) {
+                f(port, is_registered as libc::c_int, arg);
+            }
+        }
+        Event::PortRename(port, old_name, new_name) => {
+            for (f, arg) in with_server(|s| s.callbacks(|c| c.port_rename.get())) {
+                f(port, old_name.as_ptr(), new_name.as_ptr(), arg);
+            }
+        }
+        Event::PortConnect(a, b, are_connected) => {
+            for (f, arg) in with_server(|s| s.callbacks(|c| c.port_connect.get())) {
+                f(a, b, are_connected as libc::c_int, arg);
+            }
+        }
+        Event::GraphOrder => {
+            for (f, arg) in with_server(|s| s.callbacks(|c| c.graph_order.get())) {
+                f(arg);
+            }
+        }
+        Event::Latency => {
+            for (f, arg) in with_server(|s| s.callbacks(|c| c.latency.get())) {
+                f(JackCaptureLatency, arg);
+                f(JackPlaybackLatency, arg);
+            }
+        }
+        Event::Xrun => {
+            for (f, arg) in with_server(|s| s.callbacks(|c| c.xrun.get())) {
+                f(arg);
+            }
+        }
+    }
+}
+
+/// Stop the server of the current thread, and call the shutdown callbacks of its active clients.
+pub(crate) fn shutdown(reason: &CStr) {
+    let (info_shutdowns, shutdowns) = with_server(|s| {
+        let mut info_shutdowns = Vec::new();
+        let mut shutdowns = Vec::new();
+        for client in s.clients.values().filter(|c| c.is_active) {
+            match client.callbacks.info_shutdown.get() {
+                Some(info_shutdown) => info_shutdowns.push(info_shutdown),
+                None => shutdowns.extend(client.callbacks.shutdown.get()),
+            }
+        }
+        s.stop();
+        (info_shutdowns, shutdowns)
+    });
+    unsafe {
+        for (f, arg) in info_shutdowns {
+            f(JackFailure | JackServerError, reason.as_ptr(), arg);
+        }
+        for (f, arg) in shutdowns {
+            f(arg);
+        }
+    }
+}
+
+/// Run `count` process cycles, or fewer if the server stops.
+pub(crate) fn run_cycles(count: usize) {
+    let dispatching = Dispatching::start();
+    for _ in 0..count {
+        if !unsafe { run_cycle() } {
+            break;
+        }
+        if dispatching.is_some() {
+            unsafe { deliver_queued() };
+        }
+    }
+}
+
+/// Run a single process cycle. Returns `false` if the server is not running.
+unsafe fn run_cycle() -> bool {
+    let order = with_server(|s| {
+        if !s.is_running {
+            return None;
+        }
+        s.cycle_start = s.frames;
+        Some(s.process_order())
+    });
+    let order = match order {
+        Some(order) => order,
+        None => return false,
+    };
+    super::transport::sync();
+    for client in order {
+        let process = with_server(|s| {
+            s.mix_inputs(client);
+            let process = s
+                .clients
+                .get(&client)
+                .filter(|c| c.is_active)?
+                .callbacks
+                .process
+                .get()?;
+            Some((process, s.buffer_size))
+        });
+        if let Some(((f, arg), n_frames)) = process {
+            if f(n_frames, arg) != 0 {
+                // Like JACK, stop processing a client that returns an error.
+                with_server(|s| s.deactivate_client(client));
+            }
+        }
+    }
+    let n_frames = with_server(|s| {
+        s.frames += u64::from(s.buffer_size);
+        s.transport.advance(s.buffer_size);
+        s.buffer_size
+    });
+    super::transport::call_timebase(n_frames);
+    true
+}
@@ -0,0 +1,264 @@
+//! Transport and timebase functions of the fake server.
+use super::server::{id_of, with_server, Server, Slot};
+use crate::types::*;
+
+/// The sync timeout of JACK, in microseconds.
+const DEFAULT_SYNC_TIMEOUT: jack_time_t = 2_000_000;
+
+pub(crate) struct Transport {
+    state: jack_transport_state_t,
+    // Only the frame and the extended information are kept up to date, the rest is filled in by
+    // `Server::position`.
+    position: jack_position_t,
+    // Whether slow-sync clients have to be polled because the position changed.
+    needs_sync: bool,
+    // When the current poll of slow-sync clients started, in frames since the server started.
+    sync_start: u64,
+    sync_timeout: jack_time_t,
+    pub(crate) timebase_master: Option<u32>,
+    timebase: Slot<TimebaseCallback>,
+    is_new_position: bool,
+    unique: jack_unique_t,
+}
+
+impl Transport {
+    pub(crate) fn new() -> Transport {
+        Transport {
+            state: JackTransportStopped,
+            position: jack_position_t::default(),
+            needs_sync: false,
+            sync_start: 0,
+            sync_timeout: DEFAULT_SYNC_TIMEOUT,
+            timebase_master: None,
+            timebase: Slot::default(),
+            is_new_position: false,
+            unique: 0,
+        }
+    }
+
+    /// Move the transport forward after a cycle of `n_frames`.
+    pub(crate) fn advance(&mut self, n_frames: jack_nframes_t) {
+        if self.state == JackTransportRolling {
+            self.position.frame = self.position.frame.wrapping_add(n_frames);
+        }
+    }
+}
+
+impl Server {
+    /// The current transport position, as returned by `jack_transport_query`.
+    fn position(&mut self) -> jack_position_t {
+        self.transport.unique += 1;
+        let mut position = self.transport.position;
+        position.unique_1 = self.transport.unique;
+        position.unique_2 = self.transport.unique;
+        position.usecs = self.frames_to_usecs(self.cycle_start);
+        position.frame_rate = self.sample_rate;
+        position
+    }
+
+    /// Move the transport to `position`, and poll the slow-sync clients before it rolls on.
+    fn reposition(&mut self, position: jack_position_t) {
+        self.transport.position = position;
+        self.transport.needs_sync = true;
+        self.transport.sync_start = self.frames;
+        self.transport.is_new_position = true;
+        if self.transport.state == JackTransportRolling {
+            self.transport.state = JackTransportStarting;
+        }
+    }
+}
+
+/// Poll the slow-sync clients at the start of a cycle if the transport is starting or was
+/// repositioned, and start rolling once they are ready or the sync timeout expired.
+pub(crate) unsafe fn sync() {
+    let polled = with_server(|s| {
+        let state = s.transport.state;
+        match state == JackTransportStarting || s.transport.needs_sync {
+            true => Some((s.callbacks(|c| c.sync.get()), state, s.position())),
+            false => None,
+        }
+    });
+    let (syncs, state, position) = match polled {
+        Some(polled) => polled,
+        None => return,
+    };
+    let mut is_ready = true;
+    for (f, arg) in syncs {
+        let mut position = position;
+        is_ready &= f(state, &mut position, arg) != 0;
+    }
+    with_server(|s| {
+        let waited = s.frames_to_usecs(s.frames - s.transport.sync_start);
+        if is_ready || waited >= s.transport.sync_timeout {
+            s.transport.needs_sync = false;
+            if s.transport.state == JackTransportStarting {
+                s.transport.state = JackTransportRolling;
+            }
+        }
+    });
+}
+
+/// Let the timebase master fill in the position of the next cycle.
+pub(crate) unsafe fn call_timebase(n_frames: jack_nframes_t) {
+    let timebase = with_server(|s| {
+        let master = s.transport.timebase_master?;
+        if !s.clients.get(&master)?.is_active {
+            return None;
+        }
+        let (f, arg) = s.transport.timebase.get()?;
+        Some((
+            f,
+            arg,
+            s.transport.state,
+            s.position(),
+            s.transport.is_new_position,
+        ))
+    });
+    if let Some((f, arg, state, mut position, is_new_position)) = timebase {
+        let frame = position.frame;
+        f(
+            state,
+            n_frames,
+            &mut position,
+            is_new_position as libc::c_int,
+            arg,
+        );
+        with_server(|s| {
+            position.frame = frame;
+            s.transport.position = position;
+            s.transport.is_new_position = false;
+        });
+    }
+}
+
+pub(crate) unsafe extern "C" fn jack_release_timebase(client: *mut jack_client_t) -> libc::c_int {
+    with_server(|s| {
+        let is_master =
+            s.client(client).is_some() && s.transport.timebase_master == Some(id_of(client));
+        match is_master {
+            true => {
+                s.transport.timebase_master = None;
+                s.transport.timebase = Slot::default();
+                0
+            }
+            false => libc::EINVAL,
+        }
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_set_sync_callback(
+    client: *mut jack_client_t,
+    sync_callback: JackSyncCallback,
+    sync_arg: *mut libc::c_void,
+) -> libc::c_int {
+    with_server(|s| match s.client_mut(client) {
+        Some(c) => {
+            c.callbacks.sync.set(sync_callback, sync_arg);
+            0
+        }
+        None => -1,
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_set_sync_timeout(
+    client: *mut jack_client_t,
+    timeout: jack_time_t,
+) -> libc::c_int {
+    with_server(|s| match s.client(client) {
+        Some(_) => {
+            s.transport.sync_timeout = timeout;
+            0
+        }
+        None => -1,
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_set_timebase_callback(
+    client: *mut jack_client_t,
+    conditional: libc::c_int,
+    timebase_callback: TimebaseCallback,
+    arg: *mut libc::c_void,
+) -> libc::c_int {
+    with_server(|s| {
+        let id = match s.client(client) {
+            Some(_) => id_of(client),
+            None => return libc::EINVAL,
+        };
+        match s.transport.timebase_master {
+            Some(master) if master != id && conditional != 0 => return libc::EBUSY,
+            _ => (),
+        }
+        s.transport.timebase_master = Some(id);
+        s.transport.timebase.set(timebase_callback, arg);
+        s.transport.is_new_position = true;
+        0
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_transport_locate(
+    client: *mut jack_client_t,
+    frame: jack_nframes_t,
+) -> libc::c_int {
+    with_server(|s| {
+        if s.client(client).is_none() {
+            return -1;
+        }
+        let position = jack_position_t {
+            frame,
+            ..jack_position_t::default()
+        };
+        s.reposition(position);
+        0
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_transport_query(
+    client: *const jack_client_t,
+    pos: *mut jack_position_t,
+) -> jack_transport_state_t {
+    with_server(|s| {
+        if !pos.is_null() {
+            *pos = match s.client(client) {
+                Some(_) => s.position(),
+                None => jack_position_t::default(),
+            };
+        }
+        s.transport.state
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_get_current_transport_frame(
+    _client: *const jack_client_t,
+) -> jack_nframes_t {
+    with_server(|s| s.transport.position.frame)
+}
+
+pub(crate) unsafe extern "C" fn jack_transport_reposition(
+    client: *mut jack_client_t,
+    pos: *const jack_position_t,
+) -> libc::c_int {
+    with_server(|s| {
+        if s.client(client).is_none() || pos.is_null() {
+            return libc::EINVAL;
+        }
+        s.reposition(*pos);
+        0
+    })
+}
+
+pub(crate) unsafe extern "C" fn jack_transport_start(client: *mut jack_client_t) {
+    with_server(|s| {
+        if s.client(client).is_some() && s.transport.state == JackTransportStopped {
+            s.transport.state = JackTransportStarting;
+            s.transport.sync_start = s.frames;
+        }
+    });
+}
+
+pub(crate) unsafe extern "C" fn jack_transport_stop(client: *mut jack_client_t) {
+    with_server(|s| {
+        if s.client(client).is_some() {
+            s.transport.state = JackTransportStopped;
+        }
+    });
+}
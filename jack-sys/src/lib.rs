@@ -2,7 +2,10 @@
 use lazy_static::lazy_static;
 
 mod consts;
+#[cfg(feature = "fake-server")]
+pub mod fake;
 mod types;
+mod varargs;
 
 mod functions {
     include!(concat!(env!("OUT_DIR"), "/functions.rs"));
@@ -11,10 +14,33 @@ mod functions {
 pub use consts::*;
 pub use functions::*;
 pub use types::*;
+pub use varargs::*;
 
 lazy_static! {
-    static ref LIB_RESULT: Result<libloading::Library, libloading::Error> =
-        unsafe { libloading::Library::new(JACK_LIB) };
+    static ref LIB_RESULT: Result<libloading::Library, libloading::Error> = unsafe {
+        match loaded_server_library() {
+            Some(library) => Ok(library),
+            None => libloading::Library::new(JACK_LIB),
+        }
+    };
+}
+
+/// Get `JACK_SERVER_LIB` if it is already loaded into this process, which is the case for internal
+/// clients that run inside the JACK server. They have to call the server library, the functions
+/// of `JACK_LIB` only work for clients that connect to a server.
+#[cfg(unix)]
+unsafe fn loaded_server_library() -> Option<libloading::Library> {
+    use libloading::os::unix::{Library, RTLD_NOW};
+    Library::open(Some(JACK_SERVER_LIB), RTLD_NOW | libc::RTLD_NOLOAD)
+        .ok()
+        .map(libloading::Library::from)
+}
+
+#[cfg(windows)]
+unsafe fn loaded_server_library() -> Option<libloading::Library> {
+    libloading::os::windows::Library::open_already_loaded(JACK_SERVER_LIB)
+        .ok()
+        .map(libloading::Library::from)
 }
 
 /// Get the underlying library handle. Can be used to extract symbols from the library.
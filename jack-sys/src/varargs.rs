@@ -0,0 +1,123 @@
+//! Bindings for JACK functions that take optional variadic arguments.
+//!
+//! The generated function table can not express C varargs, so these symbols are looked up by hand
+//! with their true variadic signature and wrapped in functions that pass the optional arguments in
+//! the order JACK expects them.
+use crate::types::*;
+#[cfg(not(feature = "fake"))]
+use lazy_static::lazy_static;
+
+#[cfg(not(feature = "fake"))]
+type ClientOpenFn = unsafe extern "C" fn(
+    client_name: *const ::libc::c_char,
+    options: jack_options_t,
+    status: *mut jack_status_t,
+    ...
+) -> *mut jack_client_t;
+
+#[cfg(not(feature = "fake"))]
+type InternalClientLoadFn = unsafe extern "C" fn(
+    client: *mut jack_client_t,
+    client_name: *const ::libc::c_char,
+    options: jack_options_t,
+    status: *mut jack_status_t,
+    ...
+) -> jack_intclient_t;
+
+#[cfg(not(feature = "fake"))]
+lazy_static! {
+    static ref CLIENT_OPEN: ClientOpenFn = unsafe {
+        let library = crate::library().unwrap();
+        *library.get::<ClientOpenFn>(b"jack_client_open").unwrap()
+    };
+    static ref INTERNAL_CLIENT_LOAD: Option<InternalClientLoadFn> = unsafe {
+        let library = crate::library().unwrap();
+        library
+            .get::<InternalClientLoadFn>(b"jack_internal_client_load")
+            .ok()
+            .map(|f| *f)
+    };
+}
+
+/// Call `jack_client_open` with its optional variadic arguments.
+///
+/// `server_name` is only passed to JACK if `options` contains `JackServerName`, and `session_id`
+/// only if `options` contains `JackSessionID`. A null `server_name` selects the default server.
+///
+/// # Safety
+///
+/// All pointers must be valid for the duration of the call.
+pub unsafe fn jack_client_open_with_args(
+    client_name: *const ::libc::c_char,
+    options: jack_options_t,
+    status: *mut jack_status_t,
+    server_name: *const ::libc::c_char,
+    session_id: *const ::libc::c_char,
+) -> *mut jack_client_t {
+    #[cfg(feature = "fake")]
+    {
+        crate::fake::client_open(client_name, options, status, server_name, session_id)
+    }
+    #[cfg(not(feature = "fake"))]
+    {
+        #[cfg(feature = "fake-server")]
+        if crate::fake::is_enabled() {
+            return crate::fake::client_open(client_name, options, status, server_name, session_id);
+        }
+        let f = *CLIENT_OPEN;
+        let has_server_name = options & crate::JackServerName != 0;
+        let has_session_id = options & crate::JackSessionID != 0;
+        match (has_server_name, has_session_id) {
+            (true, true) => f(client_name, options, status, server_name, session_id),
+            (true, false) => f(client_name, options, status, server_name),
+            (false, true) => f(client_name, options, status, session_id),
+            (false, false) => f(client_name, options, status),
+        }
+    }
+}
+
+/// Call `jack_internal_client_load` with its optional variadic arguments.
+///
+/// `load_name` is only passed to JACK if `options` contains `JackLoadName`, and `load_init` only
+/// if `options` contains `JackLoadInit`. Returns `None` if the JACK library does not export
+/// `jack_internal_client_load`, which is always the case for the fake server of `crate::fake`.
+///
+/// # Safety
+///
+/// All pointers must be valid for the duration of the call.
+pub unsafe fn jack_internal_client_load_with_args(
+    client: *mut jack_client_t,
+    client_name: *const ::libc::c_char,
+    options: jack_options_t,
+    status: *mut jack_status_t,
+    load_name: *const ::libc::c_char,
+    load_init: *const ::libc::c_char,
+) -> Option<jack_intclient_t> {
+    #[cfg(feature = "fake")]
+    {
+        crate::fake::internal_client_load(client, client_name, options, status, load_name, load_init)
+    }
+    #[cfg(not(feature = "fake"))]
+    {
+        #[cfg(feature = "fake-server")]
+        if crate::fake::is_enabled() {
+            return crate::fake::internal_client_load(
+                client,
+                client_name,
+                options,
+                status,
+                load_name,
+                load_init,
+            );
+        }
+        let f = (*INTERNAL_CLIENT_LOAD)?;
+        let has_load_name = options & crate::JackLoadName != 0;
+        let has_load_init = options & crate::JackLoadInit != 0;
+        Some(match (has_load_name, has_load_init) {
+            (true, true) => f(client, client_name, options, status, load_name, load_init),
+            (true, false) => f(client, client_name, options, status, load_name),
+            (false, true) => f(client, client_name, options, status, load_init),
+            (false, false) => f(client, client_name, options, status),
+        })
+    }
+}
@@ -1,13 +1,22 @@
 use jack_sys as j;
+use std::any::Any;
 use std::fmt;
 use std::fmt::Debug;
 use std::mem;
+use std::sync::PoisonError;
 
-use super::callbacks::clear_callbacks;
+use super::callbacks::{clear_callbacks, lock_notification, sync, timebase};
 use super::callbacks::{CallbackContext, NotificationHandler, ProcessHandler};
 use crate::client::client_impl::Client;
 use crate::client::common::{sleep_on_test, CREATE_OR_DESTROY_CLIENT_MUTEX};
-use crate::Error;
+use crate::{Error, PanicPolicy};
+
+// Registers the process model of a callback context, see `AsyncClient::activate`.
+pub(crate) type RegisterProcessFn<N, P> =
+    unsafe fn(&mut Box<CallbackContext<N, P>>) -> Result<(), Error>;
+
+// The error of `AsyncClient::activate_context`, with the context if it can be dropped.
+pub(crate) type ActivationError<N, P> = (Error, Option<Box<CallbackContext<N, P>>>);
 
 /// A JACK client that is processing data asynchronously, in real-time.
 ///
@@ -51,34 +60,184 @@ where
     /// `notification_handler` and `process_handler` are consumed, but they are returned when
     /// `Client::deactivate` is called.
     pub fn new(client: Client, notification_handler: N, process_handler: P) -> Result<Self, Error> {
+        Self::activate(
+            client,
+            notification_handler,
+            process_handler,
+            CallbackContext::register_process_callback,
+        )
+    }
+
+    // Registers all callbacks, using `register_process` for the process model, and activates the
+    // client.
+    pub(crate) fn activate(
+        client: Client,
+        notification_handler: N,
+        process_handler: P,
+        register_process: RegisterProcessFn<N, P>,
+    ) -> Result<Self, Error> {
+        let callback_context = Box::new(CallbackContext::new(
+            client,
+            notification_handler,
+            process_handler,
+        ));
+        // Dropping the context closes the client, which takes the lock that is held during
+        // activation.
+        unsafe { Self::activate_context(callback_context, register_process) }
+            .map_err(|(err, _callback_context)| err)
+    }
+
+    // Like `AsyncClient::activate`, for a context that was already created.
+    //
+    // If the callbacks can not be registered, the ones that were are cleared again, and the context
+    // is returned with the error.
+    pub(crate) unsafe fn activate_context(
+        mut callback_context: Box<CallbackContext<N, P>>,
+        register_process: RegisterProcessFn<N, P>,
+    ) -> Result<Self, ActivationError<N, P>> {
         let _m = CREATE_OR_DESTROY_CLIENT_MUTEX.lock().unwrap();
-        unsafe {
-            sleep_on_test();
-            let mut callback_context = Box::new(CallbackContext {
-                client,
-                notification: notification_handler,
-                process: process_handler,
-            });
-            CallbackContext::register_callbacks(&mut callback_context)?;
+        sleep_on_test();
+        let registered = CallbackContext::register_callbacks(&mut callback_context)
+            .and_then(|_| register_process(&mut callback_context));
+        if let Err(err) = registered {
+            // The callbacks that were registered point to the context.
+            let _ = clear_callbacks(callback_context.client.raw());
+            return Err((err, Some(callback_context)));
+        }
+        sleep_on_test();
+        let res = j::jack_activate(callback_context.client.raw());
+        for _ in 0..4 {
             sleep_on_test();
-            let res = j::jack_activate(callback_context.client.raw());
-            for _ in 0..4 {
-                sleep_on_test();
-            }
-            match res {
-                0 => Ok(AsyncClient {
-                    callback: Some(callback_context),
-                }),
-                _ => {
-                    mem::forget(callback_context);
-                    Err(Error::ClientActivationError)
-                }
+        }
+        match res {
+            0 => Ok(AsyncClient {
+                callback: Some(callback_context),
+            }),
+            _ => {
+                mem::forget(callback_context);
+                Err((Error::ClientActivationError, None))
             }
         }
     }
+
+    /// Enable or disable slow-sync for this client, regardless of what `ProcessHandler::slow_sync`
+    /// returned on activation.
+    ///
+    /// While enabled, `ProcessHandler::sync` is polled whenever the transport is repositioned, and
+    /// the transport does not start rolling until it returns `true` or the sync timeout expires.
+    /// See `Transport::set_sync_timeout`.
+    ///
+    /// Returns `Err(Error::CallbackRegistrationError)` or
+    /// `Err(Error::CallbackDeregistrationError)` on failure.
+    pub fn set_slow_sync(&self, enable: bool) -> Result<(), Error> {
+        let ctx = self.callback.as_ref().unwrap();
+        let data_ptr = ctx.as_ref() as *const CallbackContext<N, P> as *mut libc::c_void;
+        let callback: j::JackSyncCallback = match enable {
+            true => Some(sync::<N, P>),
+            false => None,
+        };
+        let res = unsafe { j::jack_set_sync_callback(ctx.client.raw(), callback, data_ptr) };
+        match (res, enable) {
+            (0, _) => Ok(()),
+            (_, true) => Err(Error::CallbackRegistrationError),
+            (_, false) => Err(Error::CallbackDeregistrationError),
+        }
+    }
+
+    /// Replace the `ProcessHandler` while the client keeps processing, and return the previous
+    /// one.
+    ///
+    /// The new handler takes over at the start of a cycle, so no cycle is skipped and port
+    /// connections stay intact. The process thread does not allocate, deallocate or block for the
+    /// handover. The previous handler is returned once the process thread has picked up the new
+    /// one, and is dropped on the calling thread. Before its first `process`, the new handler's
+    /// `ProcessHandler::buffer_size` is called with the current buffer size on the process thread,
+    /// as for a handler that is activated.
+    ///
+    /// The `slow_sync` setting of the previous handler stays in effect, see
+    /// `AsyncClient::set_slow_sync`. If the outputs were silenced after a panic, see
+    /// `PanicPolicy::Silence`, the new handler is called again.
+    ///
+    /// Returns `Err(Error::ProcessHandlerReplacementError)` if the process callback does not run
+    /// within a second, for example because the client stopped processing after `Control::Quit`
+    /// or was activated with `Client::activate_process_thread`. `process_handler` is dropped in
+    /// that case.
+    pub fn replace_process_handler(&self, process_handler: P) -> Result<P, Error> {
+        let ctx = self.callback.as_ref().unwrap();
+        ctx.process_handover.offer(process_handler)
+    }
+
+    /// Replace the `NotificationHandler` while the client is active, and return the previous one.
+    ///
+    /// This waits for any notification that is being handled to finish. It must not be called
+    /// from within a method of the `NotificationHandler`.
+    pub fn replace_notification_handler(&self, notification_handler: N) -> N {
+        let ctx = self.callback.as_ref().unwrap();
+        mem::replace(
+            &mut *lock_notification(&ctx.notification),
+            notification_handler,
+        )
+    }
+
+    /// Make this client the timebase master, so that `ProcessHandler::timebase` is called every
+    /// cycle to publish the transport position (for example bar, beat and tick) to other clients.
+    ///
+    /// If `conditional` is `true`, this fails with `Err(Error::TimebaseMasterExists)` when another
+    /// client is already the timebase master. Otherwise, the current master is replaced. Other
+    /// failures return `Err(Error::TimebaseError)`.
+    ///
+    /// Mastership is given up with `Transport::release_timebase`, when another client takes over,
+    /// or when this client is deactivated.
+    pub fn become_timebase_master(&self, conditional: bool) -> Result<(), Error> {
+        let ctx = self.callback.as_ref().unwrap();
+        let data_ptr = ctx.as_ref() as *const CallbackContext<N, P> as *mut libc::c_void;
+        let res = unsafe {
+            j::jack_set_timebase_callback(
+                ctx.client.raw(),
+                conditional as libc::c_int,
+                Some(timebase::<N, P>),
+                data_ptr,
+            )
+        };
+        match res {
+            0 => Ok(()),
+            libc::EBUSY => Err(Error::TimebaseMasterExists),
+            _ => Err(Error::TimebaseError),
+        }
+    }
 }
 
 impl<N, P> AsyncClient<N, P> {
+    /// Set what happens when a handler panics while JACK calls into it. Defaults to
+    /// `PanicPolicy::Quit` every time the client is activated.
+    ///
+    /// This includes the `SessionHandler` and `PropertyChangeHandler` registered on the client.
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        let ctx = self.callback.as_ref().unwrap();
+        if policy == PanicPolicy::Silence {
+            ctx.panics.collect_outputs(&ctx.client);
+        }
+        ctx.panics.set_policy(policy)
+    }
+
+    /// The current `PanicPolicy`, see `AsyncClient::set_panic_policy`.
+    pub fn panic_policy(&self) -> PanicPolicy {
+        self.callback.as_ref().unwrap().panics.policy()
+    }
+
+    /// Returns `true` if a handler panicked while JACK called into it.
+    pub fn has_panicked(&self) -> bool {
+        self.callback.as_ref().unwrap().panics.has_panicked()
+    }
+
+    /// Take the payload of the first panic of a handler, if there is one. It can be inspected, or
+    /// passed to `std::panic::resume_unwind`.
+    ///
+    /// Later panics are only logged. `AsyncClient::has_panicked` keeps returning `true`.
+    pub fn take_panic(&self) -> Option<Box<dyn Any + Send + 'static>> {
+        self.callback.as_ref().unwrap().panics.take_payload()
+    }
+
     /// Return the underlying `jack::Client`.
     #[inline(always)]
     pub fn as_client(&self) -> &Client {
@@ -92,14 +251,45 @@ impl<N, P> AsyncClient<N, P> {
     /// The `handler` that was used for `Client::activate` is returned on success. Its state may
     /// have changed due to JACK calling its methods.
     ///
-    /// In the case of error, the `Client` is destroyed because its state is unknown, and it is
-    /// therefore unsafe to continue using.
+    /// Once this returns `Ok`, JACK no longer calls any of the handler methods. In the case of
+    /// error, the `Client` and the handlers are leaked instead of destroyed, because JACK may
+    /// still call into them.
     pub fn deactivate(self) -> Result<(Client, N, P), Error> {
         let mut c = self;
         unsafe {
             c.maybe_deactivate()
-                .map(|c| (c.client, c.notification, c.process))
+                .map(|c| {
+                    let notification = c.notification.into_inner();
+                    (
+                        c.client,
+                        notification.unwrap_or_else(PoisonError::into_inner),
+                        c.process,
+                    )
+                })
+        }
+    }
+
+    /// Close a client the server shut down, which can no longer be deactivated. Closing it ends
+    /// the threads JACK calls the handlers on, even if closing fails, so the handlers are dropped
+    /// afterwards.
+    pub(crate) fn close_after_shutdown(mut self) -> Result<(), Error> {
+        let callback = match self.callback.take() {
+            Some(callback) => callback,
+            None => return Err(Error::ClientIsNoLongerAlive),
+        };
+        if !callback.process_loop.stop() {
+            mem::forget(callback);
+            return Err(Error::ClientDeactivationError);
         }
+        let CallbackContext {
+            client,
+            notification,
+            process,
+            ..
+        } = *callback;
+        let res = client.close();
+        drop((notification, process));
+        res
     }
 
     // Helper function for deactivating. Any function that calls this should
@@ -110,8 +300,15 @@ impl<N, P> AsyncClient<N, P> {
             return Err(Error::ClientIsNoLongerAlive);
         }
         let client = self.callback.as_ref().unwrap().client.raw();
-        // Prevent the callback from being deallocated in case deactivation
-        // fails.
+
+        // a process loop has to return before JACK ends its thread, the callback stays in place
+        // while it still runs
+        if !self.callback.as_ref().unwrap().process_loop.stop() {
+            return Err(Error::ClientDeactivationError);
+        }
+
+        // Prevent the callback from being deallocated in case deactivation or clearing the
+        // callbacks fails, JACK may still call into it.
         let callback = Box::into_raw(self.callback.take().unwrap());
 
         // deactivate
@@ -133,7 +330,14 @@ impl<N, P> AsyncClient<N, P> {
 impl<N, P> Drop for AsyncClient<N, P> {
     /// Deactivate and close the client.
     fn drop(&mut self) {
-        let _ = unsafe { self.maybe_deactivate() };
+        match unsafe { self.maybe_deactivate() } {
+            Ok(_) | Err(Error::ClientIsNoLongerAlive) => (),
+            Err(e) => {
+                log::error!("failed to deactivate JACK client: {:?}", e);
+                // A process loop that did not return still uses the callback.
+                mem::forget(self.callback.take());
+            }
+        }
     }
 }
 
@@ -1,10 +1,33 @@
 use jack_sys as j;
-use std::ffi;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::{ffi, ptr};
 
-use crate::{Client, ClientStatus, Control, Error, Frames, PortId, ProcessScope};
+use super::handover::Handover;
+use super::notification_mask::NotificationMask;
+use super::panic_policy::PanicState;
+use super::process_thread::LoopState;
+
+use crate::{Client, ClientStatus, Control, Error, Frames, LatencyType, PortId, ProcessScope};
 
 /// Specifies callbacks for JACK.
 pub trait NotificationHandler: Send {
+    /// The notifications JACK should deliver to this handler. Methods that are not selected are
+    /// never called.
+    ///
+    /// Every registered notification wakes up the client, so handlers that only care about a few
+    /// notifications should select just those. `freewheel` is always registered, as it is needed
+    /// for `ProcessScope::is_freewheeling`, but it is only called if selected.
+    const NOTIFICATIONS: NotificationMask = NotificationMask::all();
+
+    /// Indicates whether or not this handler takes part in JACK's latency computation through
+    /// `latency`.
+    ///
+    /// When `false`, JACK propagates the latency of the client's input ports to its output ports
+    /// (and vice versa) by itself.
+    const LATENCY: bool = false;
+
     /// Called just once after the creation of the thread in which all other
     /// callbacks will be
     /// handled.
@@ -22,7 +45,7 @@ pub trait NotificationHandler: Send {
     /// thread has shut down.
     fn shutdown(&mut self, _status: ClientStatus, _reason: &str) {}
 
-    /// Called whenever "freewheel" mode is entered or leaving.
+    /// Called whenever "freewheel" mode is entered or leaving. See `Client::set_freewheel`.
     fn freewheel(&mut self, _: &Client, _is_freewheel_enabled: bool) {}
 
     /// Called whenever the system sample rate changes.
@@ -33,10 +56,21 @@ pub trait NotificationHandler: Send {
     /// Called whenever a client is registered or unregistered
     fn client_registration(&mut self, _: &Client, _name: &str, _is_registered: bool) {}
 
+    /// Called whenever a client is registered or unregistered, with the name exactly as JACK
+    /// reports it. JACK does not require names to be valid UTF-8.
+    ///
+    /// The default implementation calls `client_registration` with the name converted by
+    /// `CStr::to_string_lossy`.
+    fn client_registration_cstr(&mut self, c: &Client, name: &ffi::CStr, is_registered: bool) {
+        self.client_registration(c, &name.to_string_lossy(), is_registered)
+    }
+
     /// Called whenever a port is registered or unregistered
     fn port_registration(&mut self, _: &Client, _port_id: PortId, _is_registered: bool) {}
 
     /// Called whenever a port is renamed.
+    ///
+    /// This is only called if the JACK library exports `jack_set_port_rename_callback`.
     fn port_rename(
         &mut self,
         _: &Client,
@@ -47,6 +81,26 @@ pub trait NotificationHandler: Send {
         Control::Continue
     }
 
+    /// Called whenever a port is renamed, with the names exactly as JACK reports them. JACK does
+    /// not require names to be valid UTF-8.
+    ///
+    /// The default implementation calls `port_rename` with the names converted by
+    /// `CStr::to_string_lossy`.
+    fn port_rename_cstr(
+        &mut self,
+        c: &Client,
+        port_id: PortId,
+        old_name: &ffi::CStr,
+        new_name: &ffi::CStr,
+    ) -> Control {
+        self.port_rename(
+            c,
+            port_id,
+            &old_name.to_string_lossy(),
+            &new_name.to_string_lossy(),
+        )
+    }
+
     /// Called whenever ports are connected/disconnected to/from each other.
     fn ports_connected(
         &mut self,
@@ -69,14 +123,39 @@ pub trait NotificationHandler: Send {
     fn xrun(&mut self, _: &Client) -> Control {
         Control::Continue
     }
+
+    /// Called whenever JACK recomputes the latencies of the graph, once for each `LatencyType`.
+    ///
+    /// For `LatencyType::Capture`, the handler should set the capture latency of its output ports
+    /// to the capture latency of the input ports feeding them plus the latency it adds. For
+    /// `LatencyType::Playback`, it should set the playback latency of its input ports to the
+    /// playback latency of the output ports they feed plus the latency it adds. See
+    /// `Port::get_latency_range` and `Port::set_latency_range`.
+    ///
+    /// Like the other notifications, it is not called on the process thread, so state shared with
+    /// the `ProcessHandler`, such as the latency it adds, has to be synchronized. Call
+    /// `Client::recompute_total_latencies` after that latency changes.
+    ///
+    /// Ignored unless Self::LATENCY == true.
+    fn latency(&mut self, _: &Client, _mode: LatencyType) {}
 }
 
 /// Specifies real-time processing.
 pub trait ProcessHandler: Send {
     /// Indicates whether or not this process handler represents a
     /// slow-sync client
+    ///
+    /// This is the default returned by `slow_sync`.
     const SLOW_SYNC:bool = false;
 
+    /// Decides whether or not this process handler represents a slow-sync client when the client
+    /// is activated. Defaults to `Self::SLOW_SYNC`.
+    ///
+    /// This can be changed after activation with `AsyncClient::set_slow_sync`.
+    fn slow_sync(&self) -> bool {
+        Self::SLOW_SYNC
+    }
+
     /// Called whenever there is work to be done.
     ///
     /// It needs to be suitable for real-time execution. That means that it
@@ -107,7 +186,8 @@ pub trait ProcessHandler: Send {
     ///
     /// It should return `false` until the handler is ready process audio.
     ///
-    /// Ignored unless Self::SLOW_SYNC == true.
+    /// Ignored unless `slow_sync` returned `true` on activation, or slow-sync was enabled with
+    /// `AsyncClient::set_slow_sync`.
     fn sync(&mut self,
             _: &Client,
             _state: crate::TransportState,
@@ -115,6 +195,31 @@ pub trait ProcessHandler: Send {
     )->bool {
         true
     }
+
+    /// Called on the process thread while this client is the timebase master, after `process`,
+    /// to fill in `pos` for the next cycle. See `AsyncClient::become_timebase_master`.
+    ///
+    /// `pos` holds the frame and frame rate of the next cycle. Other fields, such as the BBT
+    /// information set with `TransportPosition::set_bbt`, should be updated to match it.
+    /// `is_new_pos` is `true` for a new position, or on the first call after becoming the master,
+    /// in which case `pos` does not carry over any BBT information from previous cycles.
+    ///
+    /// It needs to be suitable for real-time execution.
+    fn timebase(
+        &mut self,
+        _: &Client,
+        _state: crate::TransportState,
+        _n_frames: Frames,
+        _pos: &mut crate::TransportPosition,
+        _is_new_pos: bool,
+    ) {
+    }
+}
+
+// Locks `CallbackContext::notification`. A handler that panicked while it was locked is still
+// called, the same as a handler that panicked in any other callback.
+pub(crate) fn lock_notification<N>(notification: &Mutex<N>) -> MutexGuard<'_, N> {
+    notification.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 unsafe extern "C" fn thread_init_callback<N, P>(data: *mut libc::c_void)
@@ -122,8 +227,9 @@ where
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    ctx.notification.thread_init(&ctx.client)
+    CallbackContext::<N, P>::guard(data, "thread_init", |ctx| {
+        lock_notification(&ctx.notification).thread_init(&ctx.client)
+    });
 }
 
 unsafe extern "C" fn shutdown<N, P>(
@@ -134,13 +240,14 @@ unsafe extern "C" fn shutdown<N, P>(
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    let cstr = ffi::CStr::from_ptr(reason);
-    let reason_str = cstr.to_str().unwrap_or("Failed to interpret error.");
-    ctx.notification.shutdown(
-        ClientStatus::from_bits(code).unwrap_or_else(ClientStatus::empty),
-        reason_str,
-    )
+    CallbackContext::<N, P>::guard(data, "shutdown", |ctx| {
+        let mut notification = lock_notification(&ctx.notification);
+        let reason = ffi::CStr::from_ptr(reason).to_string_lossy();
+        notification.shutdown(
+            ClientStatus::from_bits(code).unwrap_or_else(ClientStatus::empty),
+            &reason,
+        )
+    });
 }
 
 unsafe extern "C" fn process<N, P>(n_frames: Frames, data: *mut libc::c_void) -> libc::c_int
@@ -149,11 +256,36 @@ where
     P: 'static + Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
-    let scope = ProcessScope::from_raw(n_frames, ctx.client.raw());
-    ctx.process.process(&ctx.client, &scope).to_ffi()
+    if ctx.process_handover.take_pending(&mut ctx.process) {
+        // A new handler is called again, even if the previous one panicked.
+        ctx.panics.unsilence();
+        CallbackContext::<N, P>::guard(data, "buffer_size", |ctx| {
+            ctx.process.buffer_size(&ctx.client, n_frames)
+        });
+    }
+    let ctx = CallbackContext::<N, P>::from_raw(data);
+    if ctx.panics.is_silenced() {
+        ctx.panics.silence_outputs(n_frames);
+        return Control::Continue.to_ffi();
+    }
+    let res = CallbackContext::<N, P>::guard(data, "process", |ctx| {
+        let scope = ProcessScope::from_raw(n_frames, ctx.client.raw())
+            .with_freewheeling(ctx.freewheeling.load(Ordering::Relaxed));
+        ctx.process.process(&ctx.client, &scope)
+    });
+    let ctx = CallbackContext::<N, P>::from_raw(data);
+    match res {
+        Some(control) => control.to_ffi(),
+        None => {
+            if ctx.panics.is_silenced() {
+                ctx.panics.silence_outputs(n_frames);
+            }
+            ctx.panics.control().to_ffi()
+        }
+    }
 }
 
-unsafe extern "C" fn sync<N, P>(
+pub(crate) unsafe extern "C" fn sync<N, P>(
     state: jack_sys::jack_transport_state_t,
     pos: *mut jack_sys::jack_position_t,
     data: *mut libc::c_void
@@ -162,25 +294,55 @@ where
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    match ctx.process.sync(
-        &ctx.client,
-        crate::Transport::state_from_ffi(state),
-        &*(pos as *mut crate::TransportPosition)
-    ) {
+    let is_ready = CallbackContext::<N, P>::guard(data, "sync", |ctx| {
+        ctx.process.sync(
+            &ctx.client,
+            crate::Transport::state_from_ffi(state),
+            &*(pos as *mut crate::TransportPosition)
+        )
+    });
+    // A handler that panicked is not going to become ready, so it should not hold up the
+    // transport.
+    match is_ready.unwrap_or(true) {
         true => 1,
         false => 0
     }
 }
 
+pub(crate) unsafe extern "C" fn timebase<N, P>(
+    state: j::jack_transport_state_t,
+    n_frames: Frames,
+    pos: *mut j::jack_position_t,
+    new_pos: libc::c_int,
+    data: *mut libc::c_void,
+) where
+    N: 'static + Send + Sync + NotificationHandler,
+    P: 'static + Send + ProcessHandler,
+{
+    CallbackContext::<N, P>::guard(data, "timebase", |ctx| {
+        ctx.process.timebase(
+            &ctx.client,
+            crate::Transport::state_from_ffi(state),
+            n_frames,
+            &mut *(pos as *mut crate::TransportPosition),
+            !matches!(new_pos, 0),
+        )
+    });
+}
+
 unsafe extern "C" fn freewheel<N, P>(starting: libc::c_int, data: *mut libc::c_void)
 where
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    let is_starting = !matches!(starting, 0);
-    ctx.notification.freewheel(&ctx.client, is_starting)
+    CallbackContext::<N, P>::guard(data, "freewheel", |ctx| {
+        let mut notification = lock_notification(&ctx.notification);
+        let is_starting = !matches!(starting, 0);
+        ctx.freewheeling.store(is_starting, Ordering::Relaxed);
+        if N::NOTIFICATIONS.contains(NotificationMask::FREEWHEEL) {
+            notification.freewheel(&ctx.client, is_starting)
+        }
+    });
 }
 
 unsafe extern "C" fn buffer_size<N, P>(n_frames: Frames, data: *mut libc::c_void) -> libc::c_int
@@ -188,8 +350,9 @@ where
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    ctx.process.buffer_size(&ctx.client, n_frames).to_ffi()
+    CallbackContext::<N, P>::guard_control(data, "buffer_size", |ctx| {
+        ctx.process.buffer_size(&ctx.client, n_frames)
+    })
 }
 
 unsafe extern "C" fn sample_rate<N, P>(n_frames: Frames, data: *mut libc::c_void) -> libc::c_int
@@ -197,8 +360,10 @@ where
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    ctx.notification.sample_rate(&ctx.client, n_frames).to_ffi()
+    CallbackContext::<N, P>::guard_control(data, "sample_rate", |ctx| {
+        let mut notification = lock_notification(&ctx.notification);
+        notification.sample_rate(&ctx.client, n_frames)
+    })
 }
 
 unsafe extern "C" fn client_registration<N, P>(
@@ -209,11 +374,13 @@ unsafe extern "C" fn client_registration<N, P>(
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    let name = ffi::CStr::from_ptr(name).to_str().unwrap();
-    let register = !matches!(register, 0);
-    ctx.notification
-        .client_registration(&ctx.client, name, register)
+    CallbackContext::<N, P>::guard(data, "client_registration", |ctx| {
+        let mut notification = lock_notification(&ctx.notification);
+        let name = ffi::CStr::from_ptr(name);
+        let register = !matches!(register, 0);
+        notification
+            .client_registration_cstr(&ctx.client, name, register)
+    });
 }
 
 unsafe extern "C" fn port_registration<N, P>(
@@ -224,13 +391,14 @@ unsafe extern "C" fn port_registration<N, P>(
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    let register = !matches!(register, 0);
-    ctx.notification
-        .port_registration(&ctx.client, port_id, register)
+    CallbackContext::<N, P>::guard(data, "port_registration", |ctx| {
+        let mut notification = lock_notification(&ctx.notification);
+        let register = !matches!(register, 0);
+        notification
+            .port_registration(&ctx.client, port_id, register)
+    });
 }
 
-#[allow(dead_code)] // TODO: remove once it can be registered
 unsafe extern "C" fn port_rename<N, P>(
     port_id: PortId,
     old_name: *const libc::c_char,
@@ -241,12 +409,13 @@ where
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    let old_name = ffi::CStr::from_ptr(old_name).to_str().unwrap();
-    let new_name = ffi::CStr::from_ptr(new_name).to_str().unwrap();
-    ctx.notification
-        .port_rename(&ctx.client, port_id, old_name, new_name)
-        .to_ffi()
+    CallbackContext::<N, P>::guard_control(data, "port_rename", |ctx| {
+        let mut notification = lock_notification(&ctx.notification);
+        let old_name = ffi::CStr::from_ptr(old_name);
+        let new_name = ffi::CStr::from_ptr(new_name);
+        notification
+            .port_rename_cstr(&ctx.client, port_id, old_name, new_name)
+    })
 }
 
 unsafe extern "C" fn port_connect<N, P>(
@@ -258,10 +427,12 @@ unsafe extern "C" fn port_connect<N, P>(
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    let are_connected = !matches!(connect, 0);
-    ctx.notification
-        .ports_connected(&ctx.client, port_id_a, port_id_b, are_connected)
+    CallbackContext::<N, P>::guard(data, "port_connect", |ctx| {
+        let mut notification = lock_notification(&ctx.notification);
+        let are_connected = !matches!(connect, 0);
+        notification
+            .ports_connected(&ctx.client, port_id_a, port_id_b, are_connected)
+    });
 }
 
 unsafe extern "C" fn graph_order<N, P>(data: *mut libc::c_void) -> libc::c_int
@@ -269,8 +440,10 @@ where
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    ctx.notification.graph_reorder(&ctx.client).to_ffi()
+    CallbackContext::<N, P>::guard_control(data, "graph_order", |ctx| {
+        let mut notification = lock_notification(&ctx.notification);
+        notification.graph_reorder(&ctx.client)
+    })
 }
 
 unsafe extern "C" fn xrun<N, P>(data: *mut libc::c_void) -> libc::c_int
@@ -278,33 +451,81 @@ where
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
-    let ctx = CallbackContext::<N, P>::from_raw(data);
-    ctx.notification.xrun(&ctx.client).to_ffi()
+    CallbackContext::<N, P>::guard_control(data, "xrun", |ctx| {
+        let mut notification = lock_notification(&ctx.notification);
+        notification.xrun(&ctx.client)
+    })
+}
+
+unsafe extern "C" fn latency<N, P>(mode: j::jack_latency_callback_mode_t, data: *mut libc::c_void)
+where
+    N: 'static + Send + Sync + NotificationHandler,
+    P: 'static + Send + ProcessHandler,
+{
+    CallbackContext::<N, P>::guard(data, "latency", |ctx| {
+        let mut notification = lock_notification(&ctx.notification);
+        notification
+            .latency(&ctx.client, LatencyType::from_ffi(mode))
+    });
 }
 
 /// Unsafe ffi wrapper that clears the callbacks registered to `client`.
 ///
-/// This is mostly for use within the jack crate itself.
+/// This is mostly for use within the jack crate itself. JACK only allows changing the callbacks of
+/// an inactive client, so `client` must be deactivated first. Afterwards, JACK no longer calls into
+/// the `CallbackContext` that the callbacks were registered with, and it can be deallocated.
 ///
 /// Returns `Err(Error::CallbackDeregistrationError)` on failure.
 ///
 /// # Unsafe
 ///
 /// * Uses ffi calls, be careful.
-///
-/// # TODO
-///
-/// * Implement correctly. Freezes on my system.
-pub unsafe fn clear_callbacks(_client: *mut j::jack_client_t) -> Result<(), Error> {
-    // j::jack_set_thread_init_callback(client, None, ptr::null_mut());
-    // j::jack_set_process_callback(client, None, ptr::null_mut());
-    Ok(())
+pub unsafe fn clear_callbacks(client: *mut j::jack_client_t) -> Result<(), Error> {
+    let data_ptr = ptr::null_mut();
+    // JACK refuses to clear the process callback while a process thread is registered, see
+    // `Client::activate_process_thread`. In that case the process thread is cleared instead.
+    let process_res = match j::jack_set_process_callback(client, None, data_ptr) {
+        0 => 0,
+        _ => j::jack_set_process_thread(client, None, data_ptr),
+    };
+    j::jack_on_info_shutdown(client, None, data_ptr);
+    // The timebase callback can only be cleared by giving up mastership, which fails if this
+    // client is not the timebase master.
+    j::jack_release_timebase(client);
+    let results = [
+        process_res,
+        j::jack_set_thread_init_callback(client, None, data_ptr),
+        j::jack_set_sync_callback(client, None, data_ptr),
+        j::jack_set_freewheel_callback(client, None, data_ptr),
+        j::jack_set_buffer_size_callback(client, None, data_ptr),
+        j::jack_set_sample_rate_callback(client, None, data_ptr),
+        j::jack_set_client_registration_callback(client, None, data_ptr),
+        j::jack_set_port_registration_callback(client, None, data_ptr),
+        j::jack_set_port_rename_callback(client, None, data_ptr).unwrap_or(0),
+        j::jack_set_port_connect_callback(client, None, data_ptr),
+        j::jack_set_graph_order_callback(client, None, data_ptr),
+        j::jack_set_xrun_callback(client, None, data_ptr),
+        j::jack_set_latency_callback(client, None, data_ptr),
+    ];
+    match results.iter().all(|&res| res == 0) {
+        true => Ok(()),
+        false => Err(Error::CallbackDeregistrationError),
+    }
 }
 
 pub struct CallbackContext<N, P> {
     pub client: Client,
-    pub notification: N,
+    /// Locked while JACK calls into it, so that it can be replaced in between.
+    pub notification: Mutex<N>,
     pub process: P,
+    /// Whether JACK is in freewheel mode, as last reported by the freewheel callback.
+    pub freewheeling: AtomicBool,
+    /// Replacements for `process`, picked up by the process callback.
+    pub(crate) process_handover: Handover<P>,
+    /// Panics caught in the handlers.
+    pub(crate) panics: Arc<PanicState>,
+    /// The state of the process loop, if activated with `Client::activate_process_thread`.
+    pub(crate) process_loop: LoopState,
 }
 
 impl<N, P> CallbackContext<N, P>
@@ -312,13 +533,56 @@ where
     N: 'static + Send + Sync + NotificationHandler,
     P: 'static + Send + ProcessHandler,
 {
+    pub(crate) fn new(client: Client, notification: N, process: P) -> Self {
+        // Shared with the property change and session handlers of the client.
+        let panics = client.panics().clone();
+        panics.reset();
+        CallbackContext {
+            client,
+            notification: Mutex::new(notification),
+            process,
+            freewheeling: AtomicBool::new(false),
+            process_handover: Handover::new(),
+            panics,
+            process_loop: LoopState::default(),
+        }
+    }
+
+    /// Calls `f` with the context behind `data`, without letting a panic unwind into JACK.
+    ///
+    /// A panic is recorded in `CallbackContext::panics` for the `callback`, and `None` is
+    /// returned.
+    pub(crate) unsafe fn guard<R, F>(data: *mut libc::c_void, callback: &str, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        match panic::catch_unwind(AssertUnwindSafe(|| f(Self::from_raw(data)))) {
+            Ok(res) => Some(res),
+            Err(payload) => {
+                Self::from_raw(data).panics.record(callback, payload);
+                None
+            }
+        }
+    }
+
+    // Like `CallbackContext::guard`, for callbacks that return a `Control` to JACK.
+    unsafe fn guard_control<F>(data: *mut libc::c_void, callback: &str, f: F) -> libc::c_int
+    where
+        F: FnOnce(&mut Self) -> Control,
+    {
+        let control = Self::guard(data, callback, f);
+        control
+            .unwrap_or_else(|| Self::from_raw(data).panics.control())
+            .to_ffi()
+    }
+
     pub unsafe fn from_raw<'a>(ptr: *mut libc::c_void) -> &'a mut CallbackContext<N, P> {
         debug_assert!(!ptr.is_null());
         let obj_ptr = ptr as *mut CallbackContext<N, P>;
         &mut *obj_ptr
     }
 
-    fn raw(b: &mut Box<Self>) -> *mut libc::c_void {
+    pub(crate) fn raw(b: &mut Box<Self>) -> *mut libc::c_void {
         let ptr: *mut Self = b.as_mut();
         ptr as *mut libc::c_void
     }
@@ -343,7 +607,6 @@ where
     /// # TODO
     ///
     /// * Handled failed registrations
-    /// * Fix `jack_set_port_rename_callback`
     ///
     /// # Unsafe
     ///
@@ -352,26 +615,69 @@ where
     pub unsafe fn register_callbacks(b: &mut Box<Self>) -> Result<(), Error> {
         let data_ptr = CallbackContext::raw(b);
         let client = b.client.raw();
-        j::jack_set_thread_init_callback(client, Some(thread_init_callback::<N, P>), data_ptr);
-        j::jack_on_info_shutdown(client, Some(shutdown::<N, P>), data_ptr);
-        j::jack_set_process_callback(client, Some(process::<N, P>), data_ptr);
-        if P::SLOW_SYNC {
+        let notifications = N::NOTIFICATIONS;
+        if notifications.contains(NotificationMask::THREAD_INIT) {
+            j::jack_set_thread_init_callback(client, Some(thread_init_callback::<N, P>), data_ptr);
+        }
+        if notifications.contains(NotificationMask::SHUTDOWN) {
+            j::jack_on_info_shutdown(client, Some(shutdown::<N, P>), data_ptr);
+        }
+        if b.process.slow_sync() {
             j::jack_set_sync_callback(client, Some(sync::<N, P>), data_ptr);
         }
         j::jack_set_freewheel_callback(client, Some(freewheel::<N, P>), data_ptr);
         j::jack_set_buffer_size_callback(client, Some(buffer_size::<N, P>), data_ptr);
-        j::jack_set_sample_rate_callback(client, Some(sample_rate::<N, P>), data_ptr);
-        j::jack_set_client_registration_callback(
-            client,
-            Some(client_registration::<N, P>),
-            data_ptr,
-        );
-        j::jack_set_port_registration_callback(client, Some(port_registration::<N, P>), data_ptr);
-        // doesn't compile for testing since it is a weak export
-        // j::jack_set_port_rename_callback(client, Some(port_rename::<N, P), data_ptr);
-        j::jack_set_port_connect_callback(client, Some(port_connect::<N, P>), data_ptr);
-        j::jack_set_graph_order_callback(client, Some(graph_order::<N, P>), data_ptr);
-        j::jack_set_xrun_callback(client, Some(xrun::<N, P>), data_ptr);
+        if notifications.contains(NotificationMask::SAMPLE_RATE) {
+            j::jack_set_sample_rate_callback(client, Some(sample_rate::<N, P>), data_ptr);
+        }
+        if notifications.contains(NotificationMask::CLIENT_REGISTRATION) {
+            j::jack_set_client_registration_callback(
+                client,
+                Some(client_registration::<N, P>),
+                data_ptr,
+            );
+        }
+        if notifications.contains(NotificationMask::PORT_REGISTRATION) {
+            j::jack_set_port_registration_callback(
+                client,
+                Some(port_registration::<N, P>),
+                data_ptr,
+            );
+        }
+        // A weak export, not every JACK implementation provides it.
+        if notifications.contains(NotificationMask::PORT_RENAME)
+            && j::jack_set_port_rename_callback(client, Some(port_rename::<N, P>), data_ptr)
+                .is_none()
+        {
+            log::debug!("jack_set_port_rename_callback not found, port renames are not reported");
+        }
+        if notifications.contains(NotificationMask::PORTS_CONNECTED) {
+            j::jack_set_port_connect_callback(client, Some(port_connect::<N, P>), data_ptr);
+        }
+        if notifications.contains(NotificationMask::GRAPH_REORDER) {
+            j::jack_set_graph_order_callback(client, Some(graph_order::<N, P>), data_ptr);
+        }
+        if notifications.contains(NotificationMask::XRUN) {
+            j::jack_set_xrun_callback(client, Some(xrun::<N, P>), data_ptr);
+        }
+        if N::LATENCY {
+            j::jack_set_latency_callback(client, Some(latency::<N, P>), data_ptr);
+        }
         Ok(())
     }
+
+    /// Registers `ProcessHandler::process` to be called by JACK for every cycle.
+    ///
+    /// This is mostly for use within the jack crate itself.
+    ///
+    /// # Unsafe
+    ///
+    /// * makes ffi calls
+    pub unsafe fn register_process_callback(b: &mut Box<Self>) -> Result<(), Error> {
+        let data_ptr = CallbackContext::raw(b);
+        match j::jack_set_process_callback(b.client.raw(), Some(process::<N, P>), data_ptr) {
+            0 => Ok(()),
+            _ => Err(Error::CallbackRegistrationError),
+        }
+    }
 }
@@ -1,17 +1,22 @@
 use jack_sys as j;
 use std::fmt::Debug;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::{ffi, fmt, ptr};
 
 use crate::client::common::{sleep_on_test, CREATE_OR_DESTROY_CLIENT_MUTEX};
+use crate::client::PanicState;
 use crate::jack_utils::collect_strs;
-use crate::properties::PropertyChangeHandler;
+#[cfg(feature = "metadata")]
+use crate::properties::{PropertyChangeHandler, PropertyContext};
+use crate::session::{SessionContext, SessionHandler};
 use crate::transport::Transport;
 use crate::{
-    AsyncClient, ClientOptions, ClientStatus, Error, Frames, NotificationHandler, Port, PortFlags,
+    AsyncClient, ClientOptions, InternalClientOptions, ClientStatus, Error, Frames, NotificationHandler, Port, PortFlags,
     PortId, PortSpec, ProcessHandler, Time, Unowned,
 };
 
+pub type InternalClientID = j::jack_intclient_t;
+
 /// A client to interact with a JACK server.
 ///
 /// # Example
@@ -27,13 +32,22 @@ use crate::{
 ///     Err(e) => println!("Failed to open client because of error: {:?}", e),
 /// };
 /// ```
-
-pub type InternalClientID = j::jack_intclient_t;
-
+///
+/// # Fake server
+/// With the `fake` feature, each thread has its own server, see `jack_sys::fake`. A client must
+/// only be used on the thread that opened it, even though it is `Send` and `Sync`. On other
+/// threads the JACK functions fail as if the server had gone away, so for example port buffers
+/// are null. Its callbacks only run on the thread that opened it.
 pub struct Client(
     *mut j::jack_client_t,
     Arc<()>,
-    Option<Box<dyn PropertyChangeHandler>>,
+    // Keeps the context of a registered `PropertyChangeHandler` alive.
+    Option<Box<dyn Send>>,
+    Option<String>,
+    // Keeps the context of a registered `SessionHandler` alive.
+    Option<Box<dyn Send>>,
+    // The panics of all handlers, shared with their contexts.
+    Arc<PanicState>,
 );
 
 unsafe impl Send for Client {}
@@ -47,23 +61,94 @@ impl Client {
     /// Although the client may be successful in opening, there still may be some errors minor
     /// errors when attempting to opening. To access these, check the returned `ClientStatus`.
     pub fn new(client_name: &str, options: ClientOptions) -> Result<(Self, ClientStatus), Error> {
+        Self::open(client_name, None, None, options)
+    }
+
+    /// Opens a JACK client on the JACK server named `server_name`. This behaves like `Client::new`,
+    /// except that `ClientOptions::SERVER_NAME` is always set and `server_name` is passed to JACK
+    /// to select the server instead of the default one.
+    ///
+    /// # Example
+    /// ```no_run
+    /// let (client, _status) = jack::Client::new_with_server(
+    ///     "rusty_client",
+    ///     "dummy",
+    ///     jack::ClientOptions::NO_START_SERVER,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(client.server_name(), Some("dummy"));
+    /// ```
+    pub fn new_with_server(
+        client_name: &str,
+        server_name: &str,
+        options: ClientOptions,
+    ) -> Result<(Self, ClientStatus), Error> {
+        Self::open(
+            client_name,
+            Some(server_name),
+            None,
+            options | ClientOptions::SERVER_NAME,
+        )
+    }
+
+    /// Opens a JACK client that is being restored by a session manager. This behaves like
+    /// `Client::new`, except that `ClientOptions::SESSION_ID` is always set and `session_id` is
+    /// passed to JACK so that the client gets back the UUID it had when the session was saved.
+    ///
+    /// The session manager provides `session_id` on the command line it was given through
+    /// `SessionEvent::reply`.
+    pub fn new_with_session_id(
+        client_name: &str,
+        session_id: &str,
+        options: ClientOptions,
+    ) -> Result<(Self, ClientStatus), Error> {
+        Self::open(
+            client_name,
+            None,
+            Some(session_id),
+            options | ClientOptions::SESSION_ID,
+        )
+    }
+
+    fn open(
+        client_name: &str,
+        server_name: Option<&str>,
+        session_id: Option<&str>,
+        mut options: ClientOptions,
+    ) -> Result<(Self, ClientStatus), Error> {
         let _m = CREATE_OR_DESTROY_CLIENT_MUTEX.lock().unwrap();
         unsafe {
             jack_sys::jack_set_error_function(Some(error_handler));
             jack_sys::jack_set_info_function(Some(info_handler));
         }
+        // JACK reads a variadic argument for each of these flags, so they may only be set when
+        // there is a value to pass.
+        options.set(ClientOptions::SERVER_NAME, server_name.is_some());
+        options.set(ClientOptions::SESSION_ID, session_id.is_some());
         sleep_on_test();
         let mut status_bits = 0;
         let client = unsafe {
             let client_name = ffi::CString::new(client_name).unwrap();
-            j::jack_client_open(client_name.as_ptr(), options.bits(), &mut status_bits)
+            let server_name = server_name.map(|s| ffi::CString::new(s).unwrap());
+            let session_id = session_id.map(|s| ffi::CString::new(s).unwrap());
+            j::jack_client_open_with_args(
+                client_name.as_ptr(),
+                options.bits(),
+                &mut status_bits,
+                server_name.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                session_id.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            )
         };
         sleep_on_test();
         let status = ClientStatus::from_bits(status_bits).unwrap_or_else(ClientStatus::empty);
         if client.is_null() {
             Err(Error::ClientError(status))
         } else {
-            Ok((Client(client, Arc::default(), None), status))
+            let server_name = server_name.map(str::to_string);
+            Ok((
+                Client(client, Arc::default(), None, server_name, None, Arc::default()),
+                status,
+            ))
         }
     }
 
@@ -98,6 +183,47 @@ impl Client {
         load as f32
     }
 
+    /// The delay in microseconds that caused the most recent xrun.
+    ///
+    /// This is meant to be called from `NotificationHandler::xrun`.
+    pub fn xrun_delayed_usecs(&self) -> f32 {
+        unsafe { j::jack_get_xrun_delayed_usecs(self.raw()) }
+    }
+
+    /// The largest delay in microseconds of the process cycle since the client was activated, or
+    /// since `Client::reset_max_delayed_usecs` was called.
+    pub fn max_delayed_usecs(&self) -> f32 {
+        unsafe { j::jack_get_max_delayed_usecs(self.raw()) }
+    }
+
+    /// Reset the value returned by `Client::max_delayed_usecs`.
+    pub fn reset_max_delayed_usecs(&self) {
+        unsafe { j::jack_reset_max_delayed_usecs(self.raw()) }
+    }
+
+    /// Returns `true` if JACK is running with real-time scheduling.
+    pub fn is_realtime(&self) -> bool {
+        unsafe { j::jack_is_realtime(self.raw()) != 0 }
+    }
+
+    /// The real-time priority of the process thread of this client, or `None` if JACK is not
+    /// running with real-time scheduling.
+    pub fn real_time_priority(&self) -> Option<i32> {
+        match unsafe { j::jack_client_real_time_priority(self.raw()) } {
+            p if p < 0 => None,
+            p => Some(p),
+        }
+    }
+
+    /// The maximum real-time priority that threads created by clients can use, or `None` if JACK
+    /// is not running with real-time scheduling.
+    pub fn max_real_time_priority(&self) -> Option<i32> {
+        match unsafe { j::jack_client_max_real_time_priority(self.raw()) } {
+            p if p < 0 => None,
+            p => Some(p),
+        }
+    }
+
     /// Get the name of the current client. This may differ from the name requested by `Client::new`
     /// as JACK will may rename a client if necessary (ie: name collision, name too long). The name
     /// will only the be different than the one passed to `Client::new` if the `ClientStatus` was
@@ -110,6 +236,12 @@ impl Client {
         }
     }
 
+    /// The name of the JACK server this client was opened on with `Client::new_with_server`, or
+    /// `None` if it was opened on the default server.
+    pub fn server_name(&self) -> Option<&str> {
+        self.3.as_deref()
+    }
+
     /// The current maximum size that will every be passed to the process
     /// callback.
     pub fn buffer_size(&self) -> Frames {
@@ -249,6 +381,73 @@ impl Client {
         }
     }
 
+    /// List the clients of the JACK server, including this one.
+    ///
+    /// JACK has no API to enumerate clients, so they are collected from the port names of the
+    /// server. Other clients that have no ports are therefore never listed, track
+    /// `NotificationHandler::client_registration` to learn about them. This client is always
+    /// listed. Clients that close while they are being listed are left out.
+    ///
+    /// # Remarks
+    /// * Not realtime safe
+    pub fn clients(&self) -> Vec<ClientInfo> {
+        let mut clients: Vec<(String, Vec<String>)> = Vec::new();
+        for port in self.ports(None, None, PortFlags::empty()) {
+            let client_name = match port.split_once(':') {
+                Some((client_name, _)) => client_name.to_string(),
+                None => continue,
+            };
+            match clients.iter_mut().find(|(name, _)| *name == client_name) {
+                Some((_, ports)) => ports.push(port),
+                None => clients.push((client_name, vec![port])),
+            }
+        }
+        let name = self.name();
+        if clients.iter().all(|(client_name, _)| client_name != name) {
+            clients.push((name.to_string(), Vec::new()));
+        }
+        clients
+            .into_iter()
+            .filter_map(|(name, ports)| {
+                let uuid = self.uuid_string_of_client_by_name(&name)?;
+                let pid = self.pid_of_client_by_name(&name);
+                Some(ClientInfo {
+                    name,
+                    uuid,
+                    pid,
+                    ports,
+                })
+            })
+            .collect()
+    }
+
+    /// Get the `uuid` string of a client by name; returns None if client does not exist
+    /// # Remarks
+    /// * Not realtime safe
+    pub fn uuid_string_of_client_by_name(&self, name: &str) -> Option<String> {
+        let name = ffi::CString::new(name).unwrap();
+        unsafe {
+            let uuid_s = j::jack_get_uuid_for_client_name(self.raw(), name.as_ptr());
+            if uuid_s.is_null() {
+                return None;
+            }
+            let uuid = ffi::CStr::from_ptr(uuid_s).to_string_lossy().into_owned();
+            j::jack_free(uuid_s as _);
+            Some(uuid)
+        }
+    }
+
+    /// Get the id of the process that owns a client by name, in the format of
+    /// `std::process::id`; returns None if client does not exist, or if the JACK library can not
+    /// report it.
+    pub fn pid_of_client_by_name(&self, name: &str) -> Option<u32> {
+        let name = ffi::CString::new(name).unwrap();
+        match unsafe { j::jack_get_client_pid(name.as_ptr()) } {
+            Some(pid) if pid > 0 => Some(pid as u32),
+            _ => None,
+        }
+    }
+
     /// Create a new port for the client. This is an object used for moving data of any type in or
     /// out of the client. Ports may be connected in various ways.
     ///
@@ -327,32 +526,11 @@ impl Client {
         client_bin_name: &str,
         client_args: &str,
     ) -> Result<InternalClientID, Error> {
-        let ffi_client_name = ffi::CString::new(client_name).unwrap();
-        let ffi_client_bin = ffi::CString::new(client_bin_name).unwrap();
-        let ffi_client_args = ffi::CString::new(client_args).unwrap();
-
-        let mut status_bits = 0;
-        let options: j::Enum_JackOptions = j::JackLoadName | j::JackLoadInit;
-
-        let intclient = unsafe {
-            j::jack_internal_client_load(
-                self.raw(),
-                ffi_client_name.as_ptr(),
-                options,
-                &mut status_bits,
-                ffi_client_bin.as_ptr(),
-                ffi_client_args.as_ptr(),
-            )
-        };
-
-        match intclient {
-            Some(0) => {
-                let status = ClientStatus::from_bits(status_bits).unwrap_or_else(ClientStatus::empty);
-                Err(Error::ClientError(status))
-            },
-            Some(i) => Ok(i),
-            None => Err(Error::WeakFunctionNotFound("jack_internal_client_load")),
-        }
+        InternalClientOptions::new(client_name)
+            .with_load_name(client_bin_name)
+            .with_load_init(client_args)
+            .load(self)
+            .map(|internal_client| internal_client.id())
     }
 
     /// Unload a (server) internal client
@@ -364,12 +542,16 @@ impl Client {
     /// This call will return `Ok(())` on success.
     /// It returns a ClientError on error.
     pub fn unload_internal_client(&self, client: InternalClientID) -> Result<(), Error> {
-        let status = unsafe {
-            let status = match j::jack_internal_client_unload(self.raw(), client) {
-                Some(s) => s,
-                None => return Err(Error::WeakFunctionNotFound("jack_internal_client_unload")),
-            };
-            ClientStatus::from_bits_unchecked(status)
+        unsafe { Self::unload_internal_client_raw(self.raw(), client) }
+    }
+
+    pub(crate) unsafe fn unload_internal_client_raw(
+        raw: *mut j::jack_client_t,
+        client: InternalClientID,
+    ) -> Result<(), Error> {
+        let status = match j::jack_internal_client_unload(raw, client) {
+            Some(s) => ClientStatus::from_bits_unchecked(s),
+            None => return Err(Error::WeakFunctionNotFound("jack_internal_client_unload")),
         };
         if status.is_empty() {
             Ok(())
@@ -440,30 +622,43 @@ impl Client {
         }
     }
 
-    // TODO implement
-    // /// Start/Stop JACK's "freewheel" mode.
-    // ///
-    // /// When in "freewheel" mode, JACK no longer waits for any external event to
-    // /// begin the start of the next process cycle. As a result, freewheel mode
-    // /// causes "faster than real-time" execution of a JACK graph. If possessed,
-    // /// real-time scheduling is dropped when entering freewheel mode, and if
-    // /// appropriate it is reacquired when stopping.
-    // ///
-    // /// IMPORTANT: on systems using capabilities to provide real-time scheduling
-    // /// (i.e. Linux Kernel 2.4), if enabling freewheel, this function must be
-    // /// called from the thread that originally called `self.activate()`. This
-    // /// restriction does not apply to other systems (e.g. Linux Kernel 2.6 or OS
-    // /// X).
-    // pub pub fn set_freewheel(&self, enable: bool) -> Result<(), Error> {
-    //     let onoff = match enable {
-    //         true => 0,
-    //         false => 1,
-    //     };
-    //     match unsafe { j::jack_set_freewheel(self.raw(), onoff) } {
-    //         0 => Ok(()),
-    //         _ => Err(Error::FreewheelError),
-    //     }
-    // }
+    /// Start/Stop JACK's "freewheel" mode.
+    ///
+    /// When in "freewheel" mode, JACK no longer waits for any external event to
+    /// begin the start of the next process cycle. As a result, freewheel mode
+    /// causes "faster than real-time" execution of a JACK graph. If possessed,
+    /// real-time scheduling is dropped when entering freewheel mode, and if
+    /// appropriate it is reacquired when stopping.
+    ///
+    /// The change does not take effect immediately. `NotificationHandler::freewheel` is called
+    /// once JACK has actually entered or left freewheel mode, and `ProcessScope::is_freewheeling`
+    /// reports the current mode from within the process callback.
+    ///
+    /// IMPORTANT: on systems using capabilities to provide real-time scheduling
+    /// (i.e. Linux Kernel 2.4), if enabling freewheel, this function must be
+    /// called from the thread that originally called `self.activate()`. This
+    /// restriction does not apply to other systems (e.g. Linux Kernel 2.6 or OS
+    /// X).
+    ///
+    /// `Err(Error::FreewheelError)` is returned on failure.
+    pub fn set_freewheel(&self, enable: bool) -> Result<(), Error> {
+        let onoff = if enable { 1 } else { 0 };
+        match unsafe { j::jack_set_freewheel(self.raw(), onoff) } {
+            0 => Ok(()),
+            _ => Err(Error::FreewheelError),
+        }
+    }
+
+    /// Request a complete recomputation of all port latencies.
+    ///
+    /// This should be called after the latency the client adds has changed, so that JACK calls
+    /// `NotificationHandler::latency` again and all signal pathways in the graph are updated.
+    pub fn recompute_total_latencies(&self) -> Result<(), Error> {
+        match unsafe { j::jack_recompute_total_latencies(self.raw()) } {
+            0 => Ok(()),
+            _ => Err(Error::UnknownError),
+        }
+    }
 
     /// Establish a connection between two ports by their full name.
     ///
@@ -593,7 +788,29 @@ impl Client {
     /// # Safety
     /// It is unsafe to create a `Client` from a raw pointer.
     pub unsafe fn from_raw(p: *mut j::jack_client_t) -> Self {
-        Client(p, Arc::default(), None)
+        Client(p, Arc::default(), None, None, None, Arc::default())
+    }
+
+    /// Close the client, and disconnect it from the JACK server.
+    ///
+    /// Dropping a `Client` closes it too, but any error is only logged. Returns
+    /// `Err(Error::ClientCloseError)` if JACK fails to close the client, for example because the
+    /// server is gone. The client can not be used afterwards either way.
+    pub fn close(mut self) -> Result<(), Error> {
+        unsafe { self.close_raw() }
+    }
+
+    // Closes the client, and clears the pointer so that it is not closed again.
+    unsafe fn close_raw(&mut self) -> Result<(), Error> {
+        let _m = CREATE_OR_DESTROY_CLIENT_MUTEX.lock().unwrap();
+        sleep_on_test();
+        let res = j::jack_client_close(self.raw());
+        sleep_on_test();
+        self.0 = ptr::null_mut();
+        match res {
+            0 => Ok(()),
+            _ => Err(Error::ClientCloseError),
+        }
     }
 
     /// Get a `Transport` object associated with this client.
@@ -607,6 +824,16 @@ impl Client {
         }
     }
 
+    /// A handle that can be upgraded for as long as this client is alive.
+    pub(crate) fn life(&self) -> Weak<()> {
+        Arc::downgrade(&self.1)
+    }
+
+    /// The panics of the handlers of this client, see `AsyncClient::set_panic_policy`.
+    pub(crate) fn panics(&self) -> &Arc<PanicState> {
+        &self.5
+    }
+
     /// Register a property change handler for this client.
     ///
     /// # Remarks
@@ -620,13 +847,17 @@ impl Client {
         handler: H,
     ) -> Result<(), Error> {
         assert!(self.2.is_none());
-        let handler = Box::into_raw(Box::new(handler));
+        let mut ctx = Box::new(PropertyContext {
+            handler,
+            panics: self.panics().clone(),
+        });
+        let ctx_ptr: *mut PropertyContext<H> = ctx.as_mut();
+        self.2 = Some(ctx);
         unsafe {
-            self.2 = Some(Box::from_raw(handler));
             if j::jack_set_property_change_callback(
                 self.raw(),
                 Some(crate::properties::property_changed::<H>),
-                std::mem::transmute::<_, _>(handler),
+                ctx_ptr as *mut libc::c_void,
             ) == 0
             {
                 Ok(())
@@ -635,20 +866,51 @@ impl Client {
             }
         }
     }
+
+    /// Register a session handler for this client.
+    ///
+    /// # Remarks
+    /// * Must be called before the client is activated.
+    ///
+    /// # Panics
+    /// Calling this method more than once on any given client with cause a panic.
+    pub fn register_session_handler<H: SessionHandler + 'static>(
+        &mut self,
+        handler: H,
+    ) -> Result<(), Error> {
+        assert!(self.4.is_none());
+        let mut ctx = Box::new(SessionContext {
+            handler,
+            client_ptr: self.raw(),
+            client_life: Arc::downgrade(&self.1),
+            panics: self.panics().clone(),
+        });
+        let ctx_ptr: *mut SessionContext<H> = ctx.as_mut();
+        self.4 = Some(ctx);
+        let res = unsafe {
+            j::jack_set_session_callback(
+                self.raw(),
+                Some(crate::session::session_callback::<H>),
+                ctx_ptr as *mut libc::c_void,
+            )
+        };
+        match res {
+            0 => Ok(()),
+            _ => Err(Error::CallbackRegistrationError),
+        }
+    }
 }
 
 /// Close the client.
 impl Drop for Client {
     fn drop(&mut self) {
-        let _m = CREATE_OR_DESTROY_CLIENT_MUTEX.lock().unwrap();
-
-        debug_assert!(!self.raw().is_null()); // Rep invariant
-                                              // Close the client
-        sleep_on_test();
-        let res = unsafe { j::jack_client_close(self.raw()) }; // close the client
-        sleep_on_test();
-        assert_eq!(res, 0);
-        self.0 = ptr::null_mut();
+        if self.raw().is_null() {
+            // Already closed by `Client::close`.
+            return;
+        }
+        if let Err(e) = unsafe { self.close_raw() } {
+            log::error!("failed to close JACK client: {:?}", e);
+        }
     }
 }
 
@@ -656,6 +918,7 @@ impl Debug for Client {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         f.debug_struct("Client")
             .field("name", &self.name())
+            .field("server_name", &self.server_name())
             .field("sample_rate", &self.sample_rate())
             .field("buffer_size", &self.buffer_size())
             .field("cpu_usage", &format!("{}%", self.cpu_load() / 100.0))
@@ -672,6 +935,8 @@ pub struct ProcessScope {
 
     // Used to allow safe access to IO port buffers
     n_frames: Frames,
+
+    freewheeling: bool,
 }
 
 impl ProcessScope {
@@ -681,6 +946,14 @@ impl ProcessScope {
         self.n_frames
     }
 
+    /// Returns `true` if JACK is running in freewheel mode for this cycle, see
+    /// `Client::set_freewheel`. Handlers may use this to trade real-time safety for throughput,
+    /// for example when bouncing a session to disk.
+    #[inline(always)]
+    pub fn is_freewheeling(&self) -> bool {
+        self.freewheeling
+    }
+
     /// The precise time at the start of the current process cycle. This function may only be used
     /// from the process callback, and can be used to interpret timestamps generated by
     /// `self.frame_time()` in other threads, with respect to the current process cycle.
@@ -749,8 +1022,31 @@ impl ProcessScope {
         ProcessScope {
             n_frames,
             client_ptr,
+            freewheeling: false,
         }
     }
+
+    /// Mark whether the cycle described by this scope runs in freewheel mode.
+    #[inline(always)]
+    pub(crate) fn with_freewheeling(mut self, freewheeling: bool) -> Self {
+        self.freewheeling = freewheeling;
+        self
+    }
+}
+
+/// A client of the JACK server, as listed by `Client::clients`.
+///
+/// Only the listing client and clients with ports are listed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClientInfo {
+    /// The name of the client.
+    pub name: String,
+    /// The UUID of the client, in the format of `Client::uuid_string`.
+    pub uuid: String,
+    /// The id of the process that owns the client, if JACK reports it.
+    pub pid: Option<u32>,
+    /// The full names of the ports of the client.
+    pub ports: Vec<String>,
 }
 
 /// Internal cycle timing information.
@@ -13,22 +13,21 @@ bitflags! {
         /// automatically generates a unique one if needed.
         const USE_EXACT_NAME  = j::JackUseExactName;
 
-        /// Open with optional `server_name` parameter.
-        ///
-        /// TODO: implement
+        /// Open with optional `server_name` parameter. This is set automatically by
+        /// `Client::new_with_server`, and ignored by `Client::new`.
         const SERVER_NAME     = j::JackServerName;
 
-        /// Load internal client from optional `load_name`, otherwise use the `client_name`.
-        ///
-        /// TODO(wmedrano): Implement `load_name` functionality
+        /// Load internal client from optional `load_name`, otherwise use the `client_name`. This
+        /// is set automatically by `InternalClientOptions::with_load_name`.
         const LOAD_NAME       = j::JackLoadName;
 
         /// Pass optional `load_init` to `jack_initialize()` entry point of an internal client.
-        ///
-        /// TODO: implement
+        /// This is set automatically by `InternalClientOptions::with_load_init`.
         const LOAD_INIT       = j::JackLoadInit;
 
         /// Pass a SessionID token. This allows the session manager to identify the client again.
+        /// This is set automatically by `Client::new_with_session_id`, and ignored by
+        /// `Client::new`.
         const SESSION_ID      = j::JackSessionID;
     }
 }
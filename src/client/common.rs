@@ -19,11 +19,30 @@ lazy_static! {
     pub static ref CREATE_OR_DESTROY_CLIENT_MUTEX: Mutex<()> = Mutex::new(());
 }
 
+// The fake server of the `fake` feature handles requests before they return, so there is
+// nothing to wait for.
 #[inline(always)]
 pub fn sleep_on_test() {
-    #[cfg(test)]
+    #[cfg(all(test, not(feature = "fake")))]
     {
         use std::{thread, time};
         thread::sleep(time::Duration::from_millis(150));
     }
 }
+
+/// Let the server run process cycles for `duration`. The fake server of the `fake` feature runs
+/// the cycles that fit into `duration` on the calling thread instead.
+#[cfg(test)]
+pub fn run_cycles_on_test(client: &crate::Client, duration: std::time::Duration) {
+    #[cfg(feature = "fake")]
+    {
+        let cycle_secs = client.buffer_size() as f64 / client.sample_rate() as f64;
+        let cycles = (duration.as_secs_f64() / cycle_secs).ceil() as usize;
+        jack_sys::fake::run_cycles(cycles);
+    }
+    #[cfg(not(feature = "fake"))]
+    {
+        let _ = client;
+        std::thread::sleep(duration);
+    }
+}
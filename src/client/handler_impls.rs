@@ -1,7 +1,11 @@
-use crate::{Client, Control, NotificationHandler, ProcessHandler, ProcessScope};
+use crate::{
+    Client, Control, NotificationHandler, NotificationMask, ProcessHandler, ProcessScope,
+};
 
-/// A trivial handler that does nothing.
-impl NotificationHandler for () {}
+/// A trivial handler that does nothing, and therefore does not receive any notifications.
+impl NotificationHandler for () {
+    const NOTIFICATIONS: NotificationMask = NotificationMask::empty();
+}
 
 /// A trivial handler that does nothing.
 impl ProcessHandler for () {
@@ -0,0 +1,142 @@
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{ptr, thread};
+
+use crate::Error;
+
+/// How long `Handover::offer` waits for the realtime thread to pick up a value.
+const HANDOVER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Hands a value over to a realtime thread, and the value it replaces back, without allocating,
+/// deallocating or blocking on the realtime thread.
+pub(crate) struct Handover<T> {
+    pending: AtomicPtr<T>,
+    retired: AtomicPtr<T>,
+    // Only one value can be in flight at a time.
+    offer_lock: Mutex<()>,
+}
+
+impl<T> Handover<T> {
+    pub(crate) fn new() -> Handover<T> {
+        Handover {
+            pending: AtomicPtr::new(ptr::null_mut()),
+            retired: AtomicPtr::new(ptr::null_mut()),
+            offer_lock: Mutex::new(()),
+        }
+    }
+
+    /// Offer `value` to the realtime thread and wait until it is swapped in by
+    /// `Handover::take_pending`. Returns the value it replaced.
+    ///
+    /// If the realtime thread does not pick it up in time, `value` is dropped and
+    /// `Err(Error::ProcessHandlerReplacementError)` is returned.
+    pub(crate) fn offer(&self, value: T) -> Result<T, Error> {
+        let _lock = self.offer_lock.lock().unwrap();
+        let offered = Box::into_raw(Box::new(value));
+        self.pending.store(offered, Ordering::Release);
+
+        let start = Instant::now();
+        while start.elapsed() < HANDOVER_TIMEOUT {
+            if let Some(old) = self.take_retired() {
+                return Ok(old);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // Take the offer back, unless the realtime thread got to it in the meantime.
+        let taken_back = self
+            .pending
+            .compare_exchange(
+                offered,
+                ptr::null_mut(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok();
+        if taken_back {
+            drop(unsafe { Box::from_raw(offered) });
+            return Err(Error::ProcessHandlerReplacementError);
+        }
+        loop {
+            if let Some(old) = self.take_retired() {
+                return Ok(old);
+            }
+            thread::yield_now();
+        }
+    }
+
+    fn take_retired(&self) -> Option<T> {
+        let retired = self.retired.swap(ptr::null_mut(), Ordering::Acquire);
+        match retired.is_null() {
+            true => None,
+            false => Some(*unsafe { Box::from_raw(retired) }),
+        }
+    }
+
+    /// Swap `current` with the pending value, if there is one. Returns `true` if it was swapped.
+    ///
+    /// This function is realtime-safe.
+    pub(crate) fn take_pending(&self, current: &mut T) -> bool {
+        let pending = self.pending.swap(ptr::null_mut(), Ordering::Acquire);
+        if pending.is_null() {
+            return false;
+        }
+        // The allocation of the offered value now holds the replaced one, and is freed by
+        // `Handover::offer`.
+        unsafe { ptr::swap(current, pending) };
+        self.retired.store(pending, Ordering::Release);
+        true
+    }
+}
+
+impl<T> Drop for Handover<T> {
+    fn drop(&mut self) {
+        for &value in &[*self.pending.get_mut(), *self.retired.get_mut()] {
+            if !value.is_null() {
+                drop(unsafe { Box::from_raw(value) });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    #[test]
+    fn handover_returns_replaced_value() {
+        let handover = Arc::new(Handover::new());
+        let running = Arc::new(AtomicBool::new(true));
+        let rt = {
+            let handover = handover.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                let mut current = 1;
+                while running.load(Ordering::Relaxed) {
+                    handover.take_pending(&mut current);
+                    thread::yield_now();
+                }
+                current
+            })
+        };
+        assert_eq!(handover.offer(2), Ok(1));
+        assert_eq!(handover.offer(3), Ok(2));
+        running.store(false, Ordering::Relaxed);
+        assert_eq!(rt.join().unwrap(), 3);
+    }
+
+    #[test]
+    fn handover_times_out_without_realtime_thread() {
+        let handover = Handover::new();
+        assert_eq!(
+            handover.offer(1),
+            Err(Error::ProcessHandlerReplacementError)
+        );
+        let mut current = 0;
+        assert!(!handover.take_pending(&mut current));
+        assert_eq!(current, 0);
+    }
+}
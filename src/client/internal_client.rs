@@ -0,0 +1,397 @@
+use jack_sys as j;
+use std::mem::ManuallyDrop;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, PoisonError, Weak};
+use std::{ffi, fmt, mem, ptr};
+
+use super::callbacks::CallbackContext;
+use crate::{
+    AsyncClient, Client, ClientOptions, ClientStatus, Error, InternalClientID, NotificationHandler,
+    ProcessHandler,
+};
+
+/// Options for loading an internal client into the JACK server with `InternalClientOptions::load`.
+///
+/// # Example
+/// ```no_run
+/// let (client, _status) =
+///     jack::Client::new("rusty_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let adapter = jack::InternalClientOptions::new("audioadapter")
+///     .with_load_init("-d hw:1")
+///     .with_unload_on_drop(true)
+///     .load(&client)
+///     .unwrap();
+/// assert_eq!(adapter.name().unwrap(), "audioadapter");
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InternalClientOptions {
+    client_name: String,
+    load_name: Option<String>,
+    load_init: Option<String>,
+    options: ClientOptions,
+    unload_on_drop: bool,
+}
+
+impl InternalClientOptions {
+    /// Load the internal client named `client_name`. Unless a load name is given, JACK also loads
+    /// it from the shared object named `client_name`.
+    pub fn new(client_name: &str) -> InternalClientOptions {
+        InternalClientOptions {
+            client_name: client_name.to_string(),
+            load_name: None,
+            load_init: None,
+            options: ClientOptions::empty(),
+            unload_on_drop: false,
+        }
+    }
+
+    /// Load the internal client from the shared object named `load_name` instead of the client
+    /// name. This sets `ClientOptions::LOAD_NAME`.
+    pub fn with_load_name(&'_ mut self, load_name: &str) -> &'_ mut Self {
+        self.load_name = Some(load_name.to_string());
+        self
+    }
+
+    /// Pass `load_init` to the `jack_initialize()` entry point of the internal client. This sets
+    /// `ClientOptions::LOAD_INIT`.
+    pub fn with_load_init(&'_ mut self, load_init: &str) -> &'_ mut Self {
+        self.load_init = Some(load_init.to_string());
+        self
+    }
+
+    /// Additional options to load the client with, such as `ClientOptions::USE_EXACT_NAME`.
+    /// `LOAD_NAME` and `LOAD_INIT` are ignored, they follow `with_load_name` and `with_load_init`.
+    pub fn with_options(&'_ mut self, options: ClientOptions) -> &'_ mut Self {
+        self.options = options;
+        self
+    }
+
+    /// Unload the internal client when the returned `InternalClient` is dropped. Defaults to
+    /// `false`, so that the internal client keeps running in the server.
+    pub fn with_unload_on_drop(&'_ mut self, unload_on_drop: bool) -> &'_ mut Self {
+        self.unload_on_drop = unload_on_drop;
+        self
+    }
+
+    /// Load the internal client into the server `client` is connected to.
+    ///
+    /// Returns `Err(Error::ClientError(status))` if the server fails to load the client, or
+    /// `Err(Error::WeakFunctionNotFound)` if the JACK library does not support internal clients.
+    pub fn load(&self, client: &Client) -> Result<InternalClient, Error> {
+        let client_name = ffi::CString::new(self.client_name.as_str()).unwrap();
+        let load_name = self
+            .load_name
+            .as_ref()
+            .map(|n| ffi::CString::new(n.as_str()).unwrap());
+        let load_init = self
+            .load_init
+            .as_ref()
+            .map(|i| ffi::CString::new(i.as_str()).unwrap());
+        let mut options = self.options;
+        options.set(ClientOptions::LOAD_NAME, load_name.is_some());
+        options.set(ClientOptions::LOAD_INIT, load_init.is_some());
+
+        let mut status_bits = 0;
+        let id = unsafe {
+            j::jack_internal_client_load_with_args(
+                client.raw(),
+                client_name.as_ptr(),
+                options.bits(),
+                &mut status_bits,
+                load_name.as_ref().map_or(ptr::null(), |n| n.as_ptr()),
+                load_init.as_ref().map_or(ptr::null(), |i| i.as_ptr()),
+            )
+        };
+        match id {
+            Some(0) => Err(Error::ClientError(
+                ClientStatus::from_bits(status_bits).unwrap_or_else(ClientStatus::empty),
+            )),
+            Some(id) => Ok(InternalClient {
+                id,
+                client_ptr: client.raw(),
+                client_life: client.life(),
+                unload_on_drop: self.unload_on_drop,
+            }),
+            None => Err(Error::WeakFunctionNotFound("jack_internal_client_load")),
+        }
+    }
+}
+
+/// A handle to an internal client running inside the JACK server.
+///
+/// Handles are created by loading a client with `InternalClientOptions::load`, or by looking up a
+/// running one with `Client::internal_client_by_name`. They can only be used while the `Client`
+/// they were created with is alive.
+pub struct InternalClient {
+    id: InternalClientID,
+    client_ptr: *mut j::jack_client_t,
+    client_life: Weak<()>,
+    unload_on_drop: bool,
+}
+
+unsafe impl Send for InternalClient {}
+unsafe impl Sync for InternalClient {}
+
+impl InternalClient {
+    fn with_client<F: FnOnce(*mut j::jack_client_t) -> R, R>(&self, func: F) -> Result<R, Error> {
+        match self.client_life.upgrade() {
+            Some(_) => Ok(func(self.client_ptr)),
+            None => Err(Error::ClientIsNoLongerAlive),
+        }
+    }
+
+    /// The id JACK uses for the internal client.
+    pub fn id(&self) -> InternalClientID {
+        self.id
+    }
+
+    /// The name of the internal client.
+    pub fn name(&self) -> Result<String, Error> {
+        self.with_client(|client| unsafe {
+            let name = j::jack_get_internal_client_name(client, self.id)
+                .ok_or(Error::WeakFunctionNotFound("jack_get_internal_client_name"))?;
+            if name.is_null() {
+                return Err(Error::ClientError(ClientStatus::NO_SUCH_CLIENT));
+            }
+            let s = ffi::CStr::from_ptr(name).to_string_lossy().into_owned();
+            j::jack_free(name as *mut libc::c_void);
+            Ok(s)
+        })?
+    }
+
+    /// Whether the internal client is unloaded when this handle is dropped.
+    pub fn unload_on_drop(&self) -> bool {
+        self.unload_on_drop
+    }
+
+    /// Set whether the internal client is unloaded when this handle is dropped.
+    pub fn set_unload_on_drop(&mut self, unload_on_drop: bool) {
+        self.unload_on_drop = unload_on_drop;
+    }
+
+    /// Unload the internal client from the server.
+    pub fn unload(mut self) -> Result<(), Error> {
+        self.unload_on_drop = false;
+        let id = self.id;
+        self.with_client(|client| unsafe { Client::unload_internal_client_raw(client, id) })?
+    }
+}
+
+impl Drop for InternalClient {
+    fn drop(&mut self) {
+        if self.unload_on_drop {
+            let id = self.id;
+            let res = self
+                .with_client(|client| unsafe { Client::unload_internal_client_raw(client, id) });
+            if let Err(e) | Ok(Err(e)) = res {
+                log::error!("failed to unload internal client {}: {:?}", id, e);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for InternalClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InternalClient")
+            .field("id", &self.id)
+            .field("name", &self.name().ok())
+            .field("unload_on_drop", &self.unload_on_drop)
+            .finish()
+    }
+}
+
+impl Client {
+    /// Look up the internal client named `client_name` that is running in the server, for example
+    /// to find it again after this program restarted.
+    ///
+    /// The returned handle does not unload the internal client on drop, see
+    /// `InternalClient::set_unload_on_drop`.
+    ///
+    /// Returns `Err(Error::ClientError(status))` if there is no such internal client.
+    pub fn internal_client_by_name(&self, client_name: &str) -> Result<InternalClient, Error> {
+        let client_name = ffi::CString::new(client_name).unwrap();
+        let mut status_bits = 0;
+        let id = unsafe {
+            j::jack_internal_client_handle(self.raw(), client_name.as_ptr(), &mut status_bits)
+        };
+        match id {
+            Some(0) => Err(Error::ClientError(
+                ClientStatus::from_bits(status_bits).unwrap_or_else(ClientStatus::empty),
+            )),
+            Some(id) => Ok(InternalClient {
+                id,
+                client_ptr: self.raw(),
+                client_life: self.life(),
+                unload_on_drop: false,
+            }),
+            None => Err(Error::WeakFunctionNotFound("jack_internal_client_handle")),
+        }
+    }
+}
+
+/// Export the `jack_initialize` and `jack_finish` entry points that make a `cdylib` crate loadable
+/// as a JACK internal client, see `InternalClientOptions::load`.
+///
+/// `$init` is a function or closure of type `FnOnce(&Client, &str) -> Result<(N, P), Error>`. It
+/// is called when the server loads the internal client, with the `Client` the server created for
+/// it and the `load_init` string. It may register ports, and returns the `NotificationHandler` and
+/// `ProcessHandler` to activate the client with. Both handlers are dropped when the internal
+/// client is unloaded. Returning an error, or panicking, makes loading fail.
+///
+/// The macro may be used only once in a crate. The JACK functions the internal client calls are
+/// resolved from the server library the JACK server already loaded, `jack_sys::JACK_SERVER_LIB`,
+/// instead of the client library.
+///
+/// # Example
+/// ```no_run
+/// struct Silence(jack::Port<jack::AudioOut>);
+///
+/// impl jack::ProcessHandler for Silence {
+///     fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+///         self.0.as_mut_slice(ps).iter_mut().for_each(|v| *v = 0.0);
+///         jack::Control::Continue
+///     }
+/// }
+///
+/// fn init(client: &jack::Client, _load_init: &str) -> Result<((), Silence), jack::Error> {
+///     let out = client.register_port("out", jack::AudioOut::default())?;
+///     Ok(((), Silence(out)))
+/// }
+///
+/// jack::internal_client!(init);
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! internal_client {
+    ($init:expr) => {
+        static JACK_FINISH: $crate::InternalClientFinish = $crate::InternalClientFinish::new();
+
+        #[no_mangle]
+        pub unsafe extern "C" fn jack_initialize(
+            client: *mut $crate::jack_sys::jack_client_t,
+            load_init: *const ::std::os::raw::c_char,
+        ) -> ::std::os::raw::c_int {
+            $crate::internal_client_initialize(client, load_init, &JACK_FINISH, $init)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn jack_finish(arg: *mut ::std::os::raw::c_void) {
+            $crate::internal_client_finish(arg, &JACK_FINISH)
+        }
+    };
+}
+
+/// How `jack_finish` drops the handlers of the internal client of `internal_client!`. It is set by
+/// `internal_client_initialize`, which knows their types, and kept for every client the shared
+/// object is loaded as. The state of each client is in the argument JACK passes to `jack_finish`.
+#[doc(hidden)]
+pub struct InternalClientFinish(Mutex<Option<unsafe fn(*mut libc::c_void)>>);
+
+impl InternalClientFinish {
+    pub const fn new() -> InternalClientFinish {
+        InternalClientFinish(Mutex::new(None))
+    }
+}
+
+impl Default for InternalClientFinish {
+    fn default() -> InternalClientFinish {
+        InternalClientFinish::new()
+    }
+}
+
+// Drops the context of an internal client, except for the client, which the server owns.
+unsafe fn finish_context<N, P>(arg: *mut libc::c_void) {
+    let ctx = Box::from_raw(arg as *mut CallbackContext<N, P>);
+    drop_handlers(*ctx);
+}
+
+fn drop_handlers<N, P>(ctx: CallbackContext<N, P>) {
+    let CallbackContext {
+        client,
+        notification,
+        process,
+        ..
+    } = ctx;
+    mem::forget(client);
+    let res = panic::catch_unwind(AssertUnwindSafe(move || drop((notification, process))));
+    if res.is_err() {
+        log::error!("internal client panicked while finishing");
+    }
+}
+
+/// Implementation of `jack_initialize` for `internal_client!`.
+///
+/// # Safety
+///
+/// `client` must be the client JACK passes to `jack_initialize`, and `load_init` null or a valid
+/// C string.
+#[doc(hidden)]
+pub unsafe fn internal_client_initialize<N, P, F>(
+    client: *mut j::jack_client_t,
+    load_init: *const libc::c_char,
+    finish: &InternalClientFinish,
+    init: F,
+) -> libc::c_int
+where
+    N: 'static + Send + Sync + NotificationHandler,
+    P: 'static + Send + ProcessHandler,
+    F: FnOnce(&Client, &str) -> Result<(N, P), Error>,
+{
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        // The server owns the client, so it must never be closed from here.
+        let client = ManuallyDrop::new(Client::from_raw(client));
+        let load_init = match load_init.is_null() {
+            true => String::new(),
+            false => ffi::CStr::from_ptr(load_init)
+                .to_string_lossy()
+                .into_owned(),
+        };
+        let (notification_handler, process_handler) = init(&client, &load_init)?;
+        let ctx = Box::new(CallbackContext::new(
+            ManuallyDrop::into_inner(client),
+            notification_handler,
+            process_handler,
+        ));
+        match AsyncClient::activate_context(ctx, CallbackContext::register_process_callback) {
+            Ok(async_client) => {
+                // The context is reclaimed by `internal_client_finish`.
+                *finish.0.lock().unwrap_or_else(PoisonError::into_inner) =
+                    Some(finish_context::<N, P>);
+                mem::forget(async_client);
+                Ok(())
+            }
+            Err((e, ctx)) => {
+                if let Some(ctx) = ctx {
+                    drop_handlers(*ctx);
+                }
+                Err(e)
+            }
+        }
+    }));
+    match res {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => {
+            log::error!("failed to initialize internal client: {:?}", e);
+            1
+        }
+        Err(_) => {
+            log::error!("internal client panicked during initialization");
+            1
+        }
+    }
+}
+
+/// Implementation of `jack_finish` for `internal_client!`.
+///
+/// # Safety
+///
+/// `arg` must be the argument JACK passes to `jack_finish` for a client initialized by
+/// `internal_client_initialize` with the same `finish`.
+#[doc(hidden)]
+pub unsafe fn internal_client_finish(arg: *mut libc::c_void, finish: &InternalClientFinish) {
+    // JACK passes the argument of the process callback, which is the callback context.
+    let finish = *finish.0.lock().unwrap_or_else(PoisonError::into_inner);
+    if let (false, Some(finish)) = (arg.is_null(), finish) {
+        finish(arg);
+    }
+}
@@ -3,6 +3,13 @@ mod callbacks;
 mod client_impl;
 mod common;
 mod handler_impls;
+mod handover;
+mod internal_client;
+mod notification_channel;
+mod panic_policy;
+mod process_thread;
+mod supervisor;
+mod xrun;
 
 /// Contains `ClientOptions` flags used when opening a client.
 mod client_options;
@@ -10,14 +17,34 @@ mod client_options;
 /// Contains `ClientStatus` flags which describe the status of a Client.
 mod client_status;
 
+/// Contains `NotificationMask` flags which select the notifications a handler receives.
+mod notification_mask;
+
+pub(crate) use self::panic_policy::PanicState;
+
 pub use self::async_client::AsyncClient;
 pub use self::callbacks::{NotificationHandler, ProcessHandler};
-pub use self::client_impl::{Client, CycleTimes, InternalClientID, ProcessScope};
+pub use self::client_impl::{Client, ClientInfo, CycleTimes, InternalClientID, ProcessScope};
 pub use self::client_options::ClientOptions;
 pub use self::client_status::ClientStatus;
 pub use self::common::CLIENT_NAME_SIZE;
+#[cfg(test)]
+pub(crate) use self::common::run_cycles_on_test;
 
 pub use self::handler_impls::ClosureProcessHandler;
+pub use self::internal_client::{InternalClient, InternalClientOptions};
+#[doc(hidden)]
+pub use self::internal_client::{
+    internal_client_finish, internal_client_initialize, InternalClientFinish,
+};
+pub use self::notification_channel::{
+    ChannelNotificationHandler, Notification, NotificationReceiver, RecvNotification,
+};
+pub use self::notification_mask::NotificationMask;
+pub use self::panic_policy::PanicPolicy;
+pub use self::process_thread::{ProcessLoop, ProcessThread};
+pub use self::supervisor::Supervisor;
+pub use self::xrun::{XrunEvent, XrunHistory, XrunRecorder};
 
 // client.rs excluding functionality that involves ports or callbacks
 #[cfg(test)]
@@ -25,3 +52,7 @@ mod test;
 
 #[cfg(test)]
 mod test_callback;
+
+// Tests that drive the fake server of the `fake` feature.
+#[cfg(all(test, feature = "fake"))]
+mod test_fake;
@@ -0,0 +1,399 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use crate::{Client, ClientStatus, Control, Frames, NotificationHandler, NotificationMask, PortId};
+
+/// An owned notification from JACK, as sent by `ChannelNotificationHandler`.
+///
+/// Port names are resolved when the notification is handled. They are `None` if the port no
+/// longer exists by then, for example for a port that is being unregistered.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Notification {
+    /// The JACK server shut the client down, see `NotificationHandler::shutdown`.
+    Shutdown {
+        status: ClientStatus,
+        reason: String,
+    },
+    /// Freewheel mode was entered or left.
+    Freewheel { is_enabled: bool },
+    /// The sample rate changed.
+    SampleRate(Frames),
+    /// A client was registered or unregistered.
+    ClientRegistration { name: String, is_registered: bool },
+    /// A port was registered or unregistered.
+    PortRegistration {
+        port_id: PortId,
+        name: Option<String>,
+        is_registered: bool,
+    },
+    /// A port was renamed.
+    PortRename {
+        port_id: PortId,
+        old_name: String,
+        new_name: String,
+    },
+    /// Two ports were connected or disconnected.
+    PortsConnected {
+        port_id_a: PortId,
+        port_id_b: PortId,
+        name_a: Option<String>,
+        name_b: Option<String>,
+        are_connected: bool,
+    },
+    /// The order of the process graph changed.
+    GraphReorder,
+    /// An xrun occured.
+    Xrun,
+}
+
+#[derive(Default)]
+struct Channel {
+    queue: Mutex<Queue>,
+    available: Condvar,
+}
+
+#[derive(Default)]
+struct Queue {
+    notifications: VecDeque<Notification>,
+    waker: Option<Waker>,
+    is_closed: bool,
+}
+
+impl Channel {
+    fn send(&self, notification: Notification) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.notifications.push_back(notification);
+        self.wake(queue);
+    }
+
+    fn close(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.is_closed = true;
+        self.wake(queue);
+    }
+
+    fn wake(&self, mut queue: std::sync::MutexGuard<'_, Queue>) {
+        let waker = queue.waker.take();
+        drop(queue);
+        self.available.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// A `NotificationHandler` that sends every notification as a `Notification` to a
+/// `NotificationReceiver`.
+///
+/// This makes it possible to react to changes of the JACK graph on any thread, without
+/// implementing `NotificationHandler`.
+///
+/// # Example
+/// ```
+/// let (client, _status) =
+///     jack::Client::new("my_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let (handler, notifications) = jack::ChannelNotificationHandler::new();
+/// let active_client = client.activate_async(handler, ()).unwrap();
+/// while let Some(notification) = notifications.try_recv() {
+///     println!("{:?}", notification);
+/// }
+/// active_client.deactivate().unwrap();
+/// ```
+pub struct ChannelNotificationHandler {
+    channel: Arc<Channel>,
+}
+
+impl ChannelNotificationHandler {
+    /// Create a handler, and the receiver for the notifications it handles.
+    pub fn new() -> (ChannelNotificationHandler, NotificationReceiver) {
+        let channel = Arc::new(Channel::default());
+        let receiver = NotificationReceiver {
+            channel: channel.clone(),
+        };
+        (ChannelNotificationHandler { channel }, receiver)
+    }
+}
+
+impl Drop for ChannelNotificationHandler {
+    fn drop(&mut self) {
+        self.channel.close();
+    }
+}
+
+impl std::fmt::Debug for ChannelNotificationHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChannelNotificationHandler").finish()
+    }
+}
+
+fn port_name(c: &Client, port_id: PortId) -> Option<String> {
+    c.port_by_id(port_id).and_then(|p| p.name().ok())
+}
+
+impl NotificationHandler for ChannelNotificationHandler {
+    const NOTIFICATIONS: NotificationMask = NotificationMask::from_bits_truncate(
+        NotificationMask::all().bits() & !NotificationMask::THREAD_INIT.bits(),
+    );
+
+    fn shutdown(&mut self, status: ClientStatus, reason: &str) {
+        self.channel.send(Notification::Shutdown {
+            status,
+            reason: reason.to_string(),
+        })
+    }
+
+    fn freewheel(&mut self, _: &Client, is_freewheel_enabled: bool) {
+        self.channel.send(Notification::Freewheel {
+            is_enabled: is_freewheel_enabled,
+        })
+    }
+
+    fn sample_rate(&mut self, _: &Client, srate: Frames) -> Control {
+        self.channel.send(Notification::SampleRate(srate));
+        Control::Continue
+    }
+
+    fn client_registration(&mut self, _: &Client, name: &str, is_registered: bool) {
+        self.channel.send(Notification::ClientRegistration {
+            name: name.to_string(),
+            is_registered,
+        })
+    }
+
+    fn port_registration(&mut self, c: &Client, port_id: PortId, is_registered: bool) {
+        self.channel.send(Notification::PortRegistration {
+            port_id,
+            name: port_name(c, port_id),
+            is_registered,
+        })
+    }
+
+    fn port_rename(
+        &mut self,
+        _: &Client,
+        port_id: PortId,
+        old_name: &str,
+        new_name: &str,
+    ) -> Control {
+        self.channel.send(Notification::PortRename {
+            port_id,
+            old_name: old_name.to_string(),
+            new_name: new_name.to_string(),
+        });
+        Control::Continue
+    }
+
+    fn ports_connected(
+        &mut self,
+        c: &Client,
+        port_id_a: PortId,
+        port_id_b: PortId,
+        are_connected: bool,
+    ) {
+        self.channel.send(Notification::PortsConnected {
+            port_id_a,
+            port_id_b,
+            name_a: port_name(c, port_id_a),
+            name_b: port_name(c, port_id_b),
+            are_connected,
+        })
+    }
+
+    fn graph_reorder(&mut self, _: &Client) -> Control {
+        self.channel.send(Notification::GraphReorder);
+        Control::Continue
+    }
+
+    fn xrun(&mut self, _: &Client) -> Control {
+        self.channel.send(Notification::Xrun);
+        Control::Continue
+    }
+}
+
+/// Receives the notifications sent by a `ChannelNotificationHandler`.
+///
+/// Notifications are queued until they are received, the queue is not bounded. Once the handler
+/// is dropped and the queue is empty, receiving returns `None`.
+pub struct NotificationReceiver {
+    channel: Arc<Channel>,
+}
+
+impl NotificationReceiver {
+    /// Return the next notification, waiting for one if the queue is empty.
+    pub fn recv(&self) -> Option<Notification> {
+        let mut queue = self.channel.queue.lock().unwrap();
+        loop {
+            if let Some(notification) = queue.notifications.pop_front() {
+                return Some(notification);
+            }
+            if queue.is_closed {
+                return None;
+            }
+            queue = self.channel.available.wait(queue).unwrap();
+        }
+    }
+
+    /// Return the next notification, waiting at most `timeout` for one if the queue is empty.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Notification> {
+        let deadline = Instant::now() + timeout;
+        let mut queue = self.channel.queue.lock().unwrap();
+        loop {
+            if let Some(notification) = queue.notifications.pop_front() {
+                return Some(notification);
+            }
+            let now = Instant::now();
+            if queue.is_closed || now >= deadline {
+                return None;
+            }
+            queue = self
+                .channel
+                .available
+                .wait_timeout(queue, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Return the next notification if there is one, without waiting.
+    pub fn try_recv(&self) -> Option<Notification> {
+        self.channel.queue.lock().unwrap().notifications.pop_front()
+    }
+
+    /// Return a future that resolves to the next notification, for use in async code.
+    ///
+    /// Calling it repeatedly produces a stream of notifications, which ends with `None`. With the
+    /// `stream` feature, the receiver itself is a `futures_core::Stream` of the notifications.
+    pub fn recv_async(&self) -> RecvNotification<'_> {
+        RecvNotification { receiver: self }
+    }
+
+    fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<Notification>> {
+        let mut queue = self.channel.queue.lock().unwrap();
+        if let Some(notification) = queue.notifications.pop_front() {
+            return Poll::Ready(Some(notification));
+        }
+        if queue.is_closed {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Iterator for NotificationReceiver {
+    type Item = Notification;
+
+    /// Wait for the next notification, see `NotificationReceiver::recv`.
+    fn next(&mut self) -> Option<Notification> {
+        self.recv()
+    }
+}
+
+impl std::fmt::Debug for NotificationReceiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let queue = self.channel.queue.lock().unwrap();
+        f.debug_struct("NotificationReceiver")
+            .field("queued", &queue.notifications.len())
+            .field("is_closed", &queue.is_closed)
+            .finish()
+    }
+}
+
+/// The future returned by `NotificationReceiver::recv_async`.
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct RecvNotification<'a> {
+    receiver: &'a NotificationReceiver,
+}
+
+impl<'a> Future for RecvNotification<'a> {
+    type Output = Option<Notification>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Notification>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[cfg(feature = "stream")]
+impl futures_core::Stream for NotificationReceiver {
+    type Item = Notification;
+
+    /// Poll for the next notification, see `NotificationReceiver::recv_async`.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Notification>> {
+        self.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::task::{RawWaker, RawWakerVTable};
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { Waker::from_raw(clone(std::ptr::null())) }
+    }
+
+    #[test]
+    fn notification_receiver_receives_in_order_until_closed() {
+        let (handler, receiver) = ChannelNotificationHandler::new();
+        handler.channel.send(Notification::Xrun);
+        handler.channel.send(Notification::SampleRate(48000));
+        assert_eq!(receiver.try_recv(), Some(Notification::Xrun));
+        assert_eq!(receiver.recv(), Some(Notification::SampleRate(48000)));
+        assert_eq!(receiver.recv_timeout(Duration::from_millis(1)), None);
+        drop(handler);
+        assert_eq!(receiver.recv(), None);
+    }
+
+    #[test]
+    fn notification_receiver_future_resolves_when_sent() {
+        let (handler, receiver) = ChannelNotificationHandler::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = receiver.recv_async();
+        assert_eq!(Pin::new(&mut future).poll(&mut cx), Poll::Pending);
+        handler.channel.send(Notification::GraphReorder);
+        assert_eq!(
+            Pin::new(&mut future).poll(&mut cx),
+            Poll::Ready(Some(Notification::GraphReorder))
+        );
+        drop(handler);
+        let mut future = receiver.recv_async();
+        assert_eq!(Pin::new(&mut future).poll(&mut cx), Poll::Ready(None));
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn notification_receiver_streams_until_closed() {
+        use futures_core::Stream;
+
+        let (handler, mut receiver) = ChannelNotificationHandler::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut receiver).poll_next(&mut cx), Poll::Pending);
+        handler.channel.send(Notification::Xrun);
+        handler.channel.send(Notification::GraphReorder);
+        assert_eq!(
+            Pin::new(&mut receiver).poll_next(&mut cx),
+            Poll::Ready(Some(Notification::Xrun))
+        );
+        drop(handler);
+        assert_eq!(
+            Pin::new(&mut receiver).poll_next(&mut cx),
+            Poll::Ready(Some(Notification::GraphReorder))
+        );
+        assert_eq!(
+            Pin::new(&mut receiver).poll_next(&mut cx),
+            Poll::Ready(None)
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// The notifications a `NotificationHandler` wants to receive, see
+    /// `NotificationHandler::NOTIFICATIONS`.
+    pub struct NotificationMask: u32 {
+        /// `NotificationHandler::thread_init`.
+        const THREAD_INIT         = 0x0001;
+
+        /// `NotificationHandler::shutdown`.
+        const SHUTDOWN            = 0x0002;
+
+        /// `NotificationHandler::freewheel`.
+        const FREEWHEEL           = 0x0004;
+
+        /// `NotificationHandler::sample_rate`.
+        const SAMPLE_RATE         = 0x0008;
+
+        /// `NotificationHandler::client_registration`.
+        const CLIENT_REGISTRATION = 0x0010;
+
+        /// `NotificationHandler::port_registration`.
+        const PORT_REGISTRATION   = 0x0020;
+
+        /// `NotificationHandler::port_rename`.
+        const PORT_RENAME         = 0x0040;
+
+        /// `NotificationHandler::ports_connected`.
+        const PORTS_CONNECTED     = 0x0080;
+
+        /// `NotificationHandler::graph_reorder`.
+        const GRAPH_REORDER       = 0x0100;
+
+        /// `NotificationHandler::xrun`.
+        const XRUN                = 0x0200;
+    }
+}
@@ -0,0 +1,222 @@
+use jack_sys as j;
+use std::any::Any;
+use std::ffi;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use crate::{Client, Control, Frames, PortFlags};
+
+/// What happens when a handler panics while JACK calls into it.
+///
+/// Panics are never allowed to unwind into JACK. The payload of the first panic is kept, see
+/// `AsyncClient::take_panic`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PanicPolicy {
+    /// Return `Control::Quit` to JACK, which stops processing. Panics in callbacks that do not
+    /// return a `Control` are only recorded.
+    Quit,
+    /// Keep the client running, but stop calling `ProcessHandler::process` and write silence to the
+    /// audio and MIDI output ports of the client instead, until the process handler is replaced
+    /// with `AsyncClient::replace_process_handler`.
+    ///
+    /// The output ports are collected when the policy is set with `AsyncClient::set_panic_policy`,
+    /// so that the process thread does not have to look them up. Ports registered afterwards are
+    /// only silenced once the policy is set again.
+    Silence,
+    /// Abort the process.
+    Abort,
+}
+
+// Deriving it with `#[default]` would require Rust 1.62.
+#[allow(clippy::derivable_impls)]
+impl Default for PanicPolicy {
+    fn default() -> Self {
+        PanicPolicy::Quit
+    }
+}
+
+impl PanicPolicy {
+    fn to_u8(self) -> u8 {
+        match self {
+            PanicPolicy::Quit => 0,
+            PanicPolicy::Silence => 1,
+            PanicPolicy::Abort => 2,
+        }
+    }
+
+    fn from_u8(policy: u8) -> PanicPolicy {
+        match policy {
+            1 => PanicPolicy::Silence,
+            2 => PanicPolicy::Abort,
+            _ => PanicPolicy::Quit,
+        }
+    }
+}
+
+type PanicPayload = Box<dyn Any + Send + 'static>;
+
+struct SilencedPort {
+    port: *mut j::jack_port_t,
+    is_midi: bool,
+}
+
+unsafe impl Send for SilencedPort {}
+
+/// The panics of the handlers of a `CallbackContext`.
+#[derive(Default)]
+pub(crate) struct PanicState {
+    policy: AtomicU8,
+    has_panicked: AtomicBool,
+    // Set by a panic, and cleared when the process handler is replaced.
+    silenced: AtomicBool,
+    payload: Mutex<Option<PanicPayload>>,
+    // The output ports to silence, collected off the process thread by `collect_outputs`.
+    silenced_ports: Mutex<Vec<SilencedPort>>,
+}
+
+impl PanicState {
+    pub(crate) fn policy(&self) -> PanicPolicy {
+        PanicPolicy::from_u8(self.policy.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set_policy(&self, policy: PanicPolicy) {
+        self.policy.store(policy.to_u8(), Ordering::Relaxed);
+    }
+
+    /// Forget the policy and the panics of previous handlers, when a client is activated again.
+    pub(crate) fn reset(&self) {
+        self.set_policy(PanicPolicy::default());
+        self.has_panicked.store(false, Ordering::Release);
+        self.silenced.store(false, Ordering::Release);
+        *self.payload.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        self.silenced_ports
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    pub(crate) fn has_panicked(&self) -> bool {
+        self.has_panicked.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn take_payload(&self) -> Option<PanicPayload> {
+        self.payload.lock().unwrap().take()
+    }
+
+    /// Record the panic of a handler, and abort if the policy says so.
+    pub(crate) fn record(&self, callback: &str, payload: PanicPayload) {
+        log::error!("handler panicked in the {} callback", callback);
+        if self.policy() == PanicPolicy::Abort {
+            std::process::abort();
+        }
+        // Never block the calling thread, only the first panic needs to be kept.
+        if let Ok(mut stored) = self.payload.try_lock() {
+            stored.get_or_insert(payload);
+        }
+        self.has_panicked.store(true, Ordering::Release);
+        self.silenced.store(true, Ordering::Release);
+    }
+
+    /// The value to return to JACK from a callback that panicked.
+    pub(crate) fn control(&self) -> Control {
+        match self.policy() {
+            PanicPolicy::Silence => Control::Continue,
+            _ => Control::Quit,
+        }
+    }
+
+    /// Returns `true` if the process handler should be skipped, because a handler panicked before
+    /// and the outputs are silenced instead.
+    pub(crate) fn is_silenced(&self) -> bool {
+        self.silenced.load(Ordering::Acquire) && self.policy() == PanicPolicy::Silence
+    }
+
+    /// Call the process handler again, after it was replaced.
+    pub(crate) fn unsilence(&self) {
+        self.silenced.store(false, Ordering::Release);
+    }
+
+    /// Collect the audio and MIDI output ports of `client`, for `PanicState::silence_outputs`.
+    ///
+    /// This is not realtime-safe.
+    pub(crate) fn collect_outputs(&self, client: &Client) {
+        let ports = own_output_ports(client);
+        *self
+            .silenced_ports
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = ports;
+    }
+
+    /// Write silence to the output ports collected by `PanicState::collect_outputs` for this
+    /// cycle.
+    ///
+    /// This function is realtime-safe.
+    pub(crate) fn silence_outputs(&self, n_frames: Frames) {
+        let ports = match self.silenced_ports.try_lock() {
+            Ok(ports) => ports,
+            Err(_) => return,
+        };
+        for p in ports.iter() {
+            unsafe {
+                let buffer = j::jack_port_get_buffer(p.port, n_frames);
+                if buffer.is_null() {
+                    continue;
+                }
+                match p.is_midi {
+                    true => j::jack_midi_clear_buffer(buffer),
+                    false => std::ptr::write_bytes(buffer as *mut f32, 0, n_frames as usize),
+                }
+            }
+        }
+    }
+}
+
+fn own_output_ports(client: &Client) -> Vec<SilencedPort> {
+    client
+        .ports(None, None, PortFlags::IS_OUTPUT)
+        .iter()
+        .filter_map(|name| client.port_by_name(name))
+        .filter(|port| client.is_mine(port))
+        .filter_map(|port| {
+            let port_type = unsafe { ffi::CStr::from_ptr(j::jack_port_type(port.raw())) };
+            let port_type = port_type.to_str().ok()?;
+            let is_midi = match port_type {
+                t if t == j::FLOAT_MONO_AUDIO => false,
+                t if t == j::RAW_MIDI_TYPE => true,
+                _ => return None,
+            };
+            Some(SilencedPort {
+                port: port.raw(),
+                is_midi,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn panic_state_keeps_first_payload() {
+        let state = PanicState::default();
+        assert_eq!(state.policy(), PanicPolicy::Quit);
+        assert!(!state.has_panicked());
+        state.record("process", Box::new("first"));
+        state.record("process", Box::new("second"));
+        assert!(state.has_panicked());
+        assert_eq!(state.control(), Control::Quit);
+        let payload = state.take_payload().unwrap();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"first"));
+        assert!(state.take_payload().is_none());
+    }
+
+    #[test]
+    fn panic_policy_roundtrips() {
+        let state = PanicState::default();
+        for policy in [PanicPolicy::Quit, PanicPolicy::Silence, PanicPolicy::Abort].iter() {
+            state.set_policy(*policy);
+            assert_eq!(state.policy(), *policy);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use jack_sys as j;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::callbacks::CallbackContext;
+use crate::{
+    AsyncClient, Client, Control, Error, NotificationHandler, ProcessHandler, ProcessScope,
+};
+
+/// Runs a user owned process loop on the JACK process thread, as an alternative to the
+/// `ProcessHandler::process` callback. See `Client::activate_process_thread`.
+pub struct ProcessThread<F>
+where
+    F: 'static + Send + FnMut(&Client, &mut ProcessLoop),
+{
+    pub loop_fn: F,
+}
+
+/// The process loop is driven by `ProcessThread::loop_fn`, so `process` is never called by JACK.
+/// Other `ProcessHandler` methods keep their default behavior.
+impl<F> ProcessHandler for ProcessThread<F>
+where
+    F: 'static + Send + FnMut(&Client, &mut ProcessLoop),
+{
+    fn process(&mut self, _: &Client, _: &ProcessScope) -> Control {
+        Control::Quit
+    }
+}
+
+/// How long deactivation waits for a process loop to return.
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Lets deactivation stop a process loop, so that JACK never has to cancel the thread it runs on.
+#[derive(Default)]
+pub(crate) struct LoopState {
+    // Set on deactivation to make `ProcessLoop::wait_cycle` return `None`.
+    stop: AtomicBool,
+    // Set from registration until `loop_fn` returned.
+    running: AtomicBool,
+}
+
+impl LoopState {
+    /// Ask the process loop to return, and wait until it did.
+    ///
+    /// Returns `false` if it is still running after `STOP_TIMEOUT`.
+    pub(crate) fn stop(&self) -> bool {
+        self.stop.store(true, Ordering::Release);
+        let start = Instant::now();
+        while self.running.load(Ordering::Acquire) {
+            if start.elapsed() > STOP_TIMEOUT {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+}
+
+/// Gives a process loop control over when JACK cycles start and end.
+///
+/// Each iteration of the loop calls `ProcessLoop::wait_cycle`, processes the cycle with the
+/// returned `ProcessScope`, and then hands it back with `ProcessLoop::signal_cycle`. The loop
+/// returns once `ProcessLoop::wait_cycle` returns `None`.
+pub struct ProcessLoop<'a> {
+    client: &'a Client,
+    freewheeling: &'a AtomicBool,
+    state: &'a LoopState,
+    // Set once `Control::Quit` was signaled.
+    quit: bool,
+}
+
+impl<'a> ProcessLoop<'a> {
+    /// Wait until JACK starts the next cycle, and return the scope of the cycle.
+    ///
+    /// Returns `None` once the client is being deactivated or `Control::Quit` was signaled. The
+    /// loop has to return then, deactivation waits for it.
+    ///
+    /// This function is realtime-safe.
+    pub fn wait_cycle(&mut self) -> Option<ProcessScope> {
+        if self.quit || self.state.stop.load(Ordering::Acquire) {
+            return None;
+        }
+        let n_frames = unsafe { j::jack_cycle_wait(self.client.raw()) };
+        if self.state.stop.load(Ordering::Acquire) {
+            // End the cycle right away, the clients that depend on this one still have to run.
+            unsafe { j::jack_cycle_signal(self.client.raw(), 0) };
+            return None;
+        }
+        let scope = unsafe { ProcessScope::from_raw(n_frames, self.client.raw()) }
+            .with_freewheeling(self.freewheeling.load(Ordering::Relaxed));
+        Some(scope)
+    }
+
+    /// Signal JACK that the cycle of `scope` is done, so that the clients that depend on this one
+    /// can run.
+    ///
+    /// With `Control::Quit`, the following `ProcessLoop::wait_cycle` returns `None`. Once the loop
+    /// returned, JACK deactivates the client, as with `ProcessHandler::process`.
+    ///
+    /// This function is realtime-safe.
+    pub fn signal_cycle(&mut self, _scope: ProcessScope, control: Control) {
+        if control == Control::Quit {
+            self.quit = true;
+        }
+        // A non-zero status would make JACK end the thread while it runs `loop_fn`, the status is
+        // sent after it returned instead.
+        unsafe { j::jack_cycle_signal(self.client.raw(), 0) };
+    }
+}
+
+unsafe extern "C" fn process_thread<N, F>(data: *mut libc::c_void) -> *mut libc::c_void
+where
+    N: 'static + Send + Sync + NotificationHandler,
+    F: 'static + Send + FnMut(&Client, &mut ProcessLoop),
+{
+    CallbackContext::<N, ProcessThread<F>>::guard(data, "process_thread", |ctx| {
+        let mut process_loop = ProcessLoop {
+            client: &ctx.client,
+            freewheeling: &ctx.freewheeling,
+            state: &ctx.process_loop,
+            quit: false,
+        };
+        (ctx.process.loop_fn)(&ctx.client, &mut process_loop)
+    });
+    let ctx = CallbackContext::<N, ProcessThread<F>>::from_raw(data);
+    let client = ctx.client.raw();
+    let stopped = ctx.process_loop.stop.load(Ordering::Acquire);
+    // Nothing is left to drop, so JACK may end the thread from here on.
+    ctx.process_loop.running.store(false, Ordering::Release);
+    if !stopped {
+        // The loop ended on its own. Without a thread that ends the cycles of this client, the
+        // clients that depend on it would stall, so JACK is told to deactivate it and end the
+        // thread instead.
+        j::jack_cycle_wait(client);
+        j::jack_cycle_signal(client, 1);
+    }
+    std::ptr::null_mut()
+}
+
+unsafe fn register_process_thread<N, F>(
+    b: &mut Box<CallbackContext<N, ProcessThread<F>>>,
+) -> Result<(), Error>
+where
+    N: 'static + Send + Sync + NotificationHandler,
+    F: 'static + Send + FnMut(&Client, &mut ProcessLoop),
+{
+    let data_ptr = CallbackContext::raw(b);
+    match j::jack_set_process_thread(b.client.raw(), Some(process_thread::<N, F>), data_ptr) {
+        0 => {
+            b.process_loop.running.store(true, Ordering::Release);
+            Ok(())
+        }
+        _ => Err(Error::CallbackRegistrationError),
+    }
+}
+
+impl Client {
+    /// Begin processing in real-time using the specified `NotificationHandler` and a process loop.
+    ///
+    /// Instead of calling `ProcessHandler::process` for every cycle, JACK runs `loop_fn` once on
+    /// its process thread. `loop_fn` drives the cycles itself through `ProcessLoop`, which makes it
+    /// possible to integrate an existing engine loop. Processing ends when `loop_fn` returns: JACK
+    /// deactivates the client at the next cycle, so the clients that depend on it keep running. It
+    /// still has to be deactivated with `AsyncClient::deactivate` to get the handlers back.
+    ///
+    /// # Remarks
+    /// * `loop_fn` has to return once `ProcessLoop::wait_cycle` returns `None`. Deactivation waits
+    ///   for it before JACK ends the process thread, and fails with
+    ///   `Err(Error::ClientDeactivationError)` if it does not return within a second.
+    ///
+    /// # Example
+    /// ```
+    /// let (client, _status) =
+    ///     jack::Client::new("my_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// let active_client = client
+    ///     .activate_process_thread((), |_: &jack::Client, process_loop: &mut jack::ProcessLoop| {
+    ///         while let Some(scope) = process_loop.wait_cycle() {
+    ///             process_loop.signal_cycle(scope, jack::Control::Continue);
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// active_client.deactivate().unwrap();
+    /// ```
+    pub fn activate_process_thread<N, F>(
+        self,
+        notification_handler: N,
+        loop_fn: F,
+    ) -> Result<AsyncClient<N, ProcessThread<F>>, Error>
+    where
+        N: 'static + Send + Sync + NotificationHandler,
+        F: 'static + Send + FnMut(&Client, &mut ProcessLoop),
+    {
+        AsyncClient::activate(
+            self,
+            notification_handler,
+            ProcessThread { loop_fn },
+            register_process_thread::<N, F>,
+        )
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::CStr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::{
+    AsyncClient, Client, ClientOptions, ClientStatus, Control, Error, Frames, LatencyType,
+    NotificationHandler, NotificationMask, Port, PortFlags, PortId, ProcessHandler, Unowned,
+};
+
+/// The delay before the first attempt to reopen the client after the server went away.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The longest delay between attempts to reopen the client.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Keeps a client running across JACK server shutdowns and restarts.
+///
+/// The supervisor opens the client, calls `factory` to register ports and create the handlers,
+/// and activates it. When the server shuts the client down, the supervisor reopens it with
+/// increasing delays until the server is back, calls `factory` again for the new client, and
+/// restores the connections the ports of the client had.
+///
+/// Connections are tracked through notifications while the client is active. A connection that
+/// is removed while the server is running, for example because the other client quit, is not
+/// restored. A connection to a port of another client that is not back yet is restored once the
+/// port is registered.
+///
+/// # Example
+/// ```
+/// let supervisor = jack::Supervisor::new(
+///     "my_client",
+///     jack::ClientOptions::NO_START_SERVER,
+///     |client: &jack::Client| {
+///         let mut out = client.register_port("out", jack::AudioOut::default())?;
+///         let process = jack::ClosureProcessHandler::new(
+///             move |_: &jack::Client, ps: &jack::ProcessScope| {
+///                 out.as_mut_slice(ps).iter_mut().for_each(|v| *v = 0.0);
+///                 jack::Control::Continue
+///             },
+///         );
+///         Ok(((), process))
+///     },
+/// )
+/// .unwrap();
+/// let sample_rate = supervisor.with_client(|client| client.sample_rate());
+/// ```
+pub struct Supervisor<N, P>
+where
+    N: 'static + Send + Sync + NotificationHandler,
+    P: 'static + Send + ProcessHandler,
+{
+    client: SupervisedClient<N, P>,
+    events: Arc<Events>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+type SupervisedClient<N, P> = Arc<Mutex<Option<AsyncClient<Supervised<N>, P>>>>;
+
+/// What the supervised client reports back to the supervisor.
+#[derive(Default)]
+struct Events {
+    state: Mutex<State>,
+    state_changed: Condvar,
+    connections: Mutex<BTreeSet<Connection>>,
+    /// The ports of the tracked connections of the current client, with whether they are
+    /// outputs. Disconnections are often notified after a port is gone, when it can no longer be
+    /// looked up.
+    ports: Mutex<BTreeMap<PortId, (Endpoint, bool)>>,
+    /// The connections that could not be restored yet, because a port of another client is
+    /// missing.
+    unrestored: Mutex<Vec<Connection>>,
+}
+
+#[derive(Default)]
+struct State {
+    is_shutdown: bool,
+    is_stopping: bool,
+    /// Set when a port is registered while there are unrestored connections.
+    is_port_registered: bool,
+}
+
+/// One end of a connection. Ports of the supervised client are stored by their short name, as
+/// the client may get a different name when it is reopened.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Endpoint {
+    Own(String),
+    Other(String),
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Connection {
+    source: Endpoint,
+    destination: Endpoint,
+}
+
+impl Endpoint {
+    fn of(c: &Client, port: &Port<Unowned>) -> Option<Endpoint> {
+        match c.is_mine(port) {
+            true => port.short_name().ok().map(Endpoint::Own),
+            false => port.name().ok().map(Endpoint::Other),
+        }
+    }
+
+    fn full_name(&self, client_name: &str) -> String {
+        match self {
+            Endpoint::Own(short_name) => format!("{}:{}", client_name, short_name),
+            Endpoint::Other(name) => name.clone(),
+        }
+    }
+}
+
+impl Connection {
+    fn has_endpoint(&self, endpoint: &Endpoint) -> bool {
+        &self.source == endpoint || &self.destination == endpoint
+    }
+
+    /// Whether a port of another client is not registered.
+    fn is_missing_other(&self, c: &Client) -> bool {
+        [&self.source, &self.destination]
+            .iter()
+            .any(|endpoint| match endpoint {
+                Endpoint::Own(_) => false,
+                Endpoint::Other(name) => c.port_by_name(name).is_none(),
+            })
+    }
+}
+
+impl<N, P> Supervisor<N, P>
+where
+    N: 'static + Send + Sync + NotificationHandler,
+    P: 'static + Send + ProcessHandler,
+{
+    /// Open and activate a client named `client_name`, and keep it running until the supervisor is
+    /// dropped.
+    ///
+    /// `factory` is called for every client that is opened, it should register the ports of the
+    /// client and return the handlers to activate it with.
+    ///
+    /// Returns the error of the first attempt to open and activate the client. Later attempts are
+    /// retried until they succeed, starting after 100 milliseconds and doubling the delay up to 5
+    /// seconds.
+    pub fn new<F>(client_name: &str, options: ClientOptions, mut factory: F) -> Result<Self, Error>
+    where
+        F: 'static + Send + FnMut(&Client) -> Result<(N, P), Error>,
+    {
+        let events = Arc::new(Events::default());
+        let client = Self::start(&events, client_name, options, &mut factory)?;
+        let client = Arc::new(Mutex::new(Some(client)));
+
+        let (thread_client, thread_events) = (client.clone(), events.clone());
+        let client_name = client_name.to_string();
+        let thread = thread::Builder::new()
+            .name(format!("{} supervisor", client_name))
+            .spawn(move || {
+                Self::supervise(
+                    &thread_client,
+                    &thread_events,
+                    &client_name,
+                    options,
+                    &mut factory,
+                )
+            })
+            .map_err(|_| Error::ThreadError)?;
+        Ok(Supervisor {
+            client,
+            events,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns `true` while the client is active, and `false` while the supervisor is waiting for
+    /// the server to return.
+    pub fn is_connected(&self) -> bool {
+        self.client.lock().unwrap().is_some()
+    }
+
+    /// Call `f` with the currently active client. Returns `None` without calling `f` while the
+    /// server is gone.
+    ///
+    /// The client may be replaced once `f` returns, so it should not be kept around.
+    pub fn with_client<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&Client) -> R,
+    {
+        let client = self.client.lock().unwrap();
+        client.as_ref().map(|ac| f(ac.as_client()))
+    }
+
+    // Opens and activates a client, and restores the known connections.
+    fn start<F>(
+        events: &Arc<Events>,
+        client_name: &str,
+        options: ClientOptions,
+        factory: &mut F,
+    ) -> Result<AsyncClient<Supervised<N>, P>, Error>
+    where
+        F: FnMut(&Client) -> Result<(N, P), Error>,
+    {
+        let (client, _status) = Client::new(client_name, options)?;
+        // Port ids are only meaningful for the server that assigned them.
+        events.ports.lock().unwrap().clear();
+        let (notification_handler, process_handler) = factory(&client)?;
+        let supervised = Supervised {
+            inner: notification_handler,
+            events: events.clone(),
+        };
+        let active_client = client.activate_async(supervised, process_handler)?;
+        let connections: Vec<_> = events.connections.lock().unwrap().iter().cloned().collect();
+        let unrestored = Self::restore(active_client.as_client(), &connections);
+        *events.unrestored.lock().unwrap() = unrestored;
+        Ok(active_client)
+    }
+
+    // Connects the ports of `connections`, and returns the ones that have to be retried once the
+    // missing ports of other clients are registered.
+    fn restore(c: &Client, connections: &[Connection]) -> Vec<Connection> {
+        let name = c.name();
+        let mut unrestored = Vec::new();
+        for connection in connections {
+            let source = connection.source.full_name(name);
+            let destination = connection.destination.full_name(name);
+            match c.connect_ports_by_name(&source, &destination) {
+                Ok(()) | Err(Error::PortAlreadyConnected(_, _)) => (),
+                Err(_) if connection.is_missing_other(c) => unrestored.push(connection.clone()),
+                Err(e) => log::warn!(
+                    "failed to restore connection from {} to {}: {:?}",
+                    source,
+                    destination,
+                    e
+                ),
+            }
+        }
+        unrestored
+    }
+
+    fn supervise<F>(
+        client: &SupervisedClient<N, P>,
+        events: &Arc<Events>,
+        client_name: &str,
+        options: ClientOptions,
+        factory: &mut F,
+    ) where
+        F: FnMut(&Client) -> Result<(N, P), Error>,
+    {
+        loop {
+            {
+                let mut state = events.state.lock().unwrap();
+                while !state.is_shutdown && !state.is_stopping && !state.is_port_registered {
+                    state = events.state_changed.wait(state).unwrap();
+                }
+                if state.is_stopping {
+                    return;
+                }
+                state.is_port_registered = false;
+                if !state.is_shutdown {
+                    drop(state);
+                    if let Some(ac) = client.lock().unwrap().as_ref() {
+                        let unrestored = events.unrestored.lock().unwrap().clone();
+                        let unrestored = Self::restore(ac.as_client(), &unrestored);
+                        *events.unrestored.lock().unwrap() = unrestored;
+                    }
+                    continue;
+                }
+                state.is_shutdown = false;
+            }
+            // The server is gone, so the old client can no longer be deactivated, only closed.
+            let old_client = client.lock().unwrap().take();
+            if let Some(Err(e)) = old_client.map(AsyncClient::close_after_shutdown) {
+                log::debug!("failed to close JACK client {}: {:?}", client_name, e);
+            }
+
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                {
+                    let state = events.state.lock().unwrap();
+                    let (state, _) = events
+                        .state_changed
+                        .wait_timeout_while(state, backoff, |state| !state.is_stopping)
+                        .unwrap();
+                    if state.is_stopping {
+                        return;
+                    }
+                }
+                match Self::start(events, client_name, options, factory) {
+                    Ok(new_client) => {
+                        log::info!("reopened JACK client {}", client_name);
+                        *client.lock().unwrap() = Some(new_client);
+                        break;
+                    }
+                    Err(e) => log::debug!("failed to reopen JACK client {}: {:?}", client_name, e),
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+impl<N, P> Drop for Supervisor<N, P>
+where
+    N: 'static + Send + Sync + NotificationHandler,
+    P: 'static + Send + ProcessHandler,
+{
+    fn drop(&mut self) {
+        self.events.state.lock().unwrap().is_stopping = true;
+        self.events.state_changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        drop(self.client.lock().unwrap().take());
+    }
+}
+
+impl<N, P> std::fmt::Debug for Supervisor<N, P>
+where
+    N: 'static + Send + Sync + NotificationHandler,
+    P: 'static + Send + ProcessHandler,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Supervisor")
+            .field("is_connected", &self.is_connected())
+            .finish()
+    }
+}
+
+/// Wraps the notification handler of a supervised client to detect shutdowns and track
+/// connections.
+struct Supervised<N> {
+    inner: N,
+    events: Arc<Events>,
+}
+
+impl<N> Supervised<N> {
+    /// The endpoint of the port with `port_id`, and whether it is an output. Ports that are gone
+    /// are looked up in the ports of the tracked connections.
+    fn port(&self, c: &Client, port_id: PortId) -> Option<(Endpoint, bool)> {
+        let port = c.port_by_id(port_id).and_then(|port| {
+            let is_output = port.flags().contains(PortFlags::IS_OUTPUT);
+            Some((Endpoint::of(c, &port)?, is_output))
+        });
+        port.or_else(|| self.events.ports.lock().unwrap().get(&port_id).cloned())
+    }
+}
+
+impl<N: NotificationHandler> NotificationHandler for Supervised<N> {
+    const NOTIFICATIONS: NotificationMask = NotificationMask::from_bits_truncate(
+        N::NOTIFICATIONS.bits()
+            | NotificationMask::SHUTDOWN.bits()
+            | NotificationMask::PORT_REGISTRATION.bits()
+            | NotificationMask::PORTS_CONNECTED.bits(),
+    );
+
+    const LATENCY: bool = N::LATENCY;
+
+    fn thread_init(&self, c: &Client) {
+        self.inner.thread_init(c)
+    }
+
+    fn shutdown(&mut self, status: ClientStatus, reason: &str) {
+        if N::NOTIFICATIONS.contains(NotificationMask::SHUTDOWN) {
+            self.inner.shutdown(status, reason);
+        }
+        self.events.state.lock().unwrap().is_shutdown = true;
+        self.events.state_changed.notify_all();
+    }
+
+    fn freewheel(&mut self, c: &Client, is_freewheel_enabled: bool) {
+        self.inner.freewheel(c, is_freewheel_enabled)
+    }
+
+    fn sample_rate(&mut self, c: &Client, srate: Frames) -> Control {
+        self.inner.sample_rate(c, srate)
+    }
+
+    fn client_registration(&mut self, c: &Client, name: &str, is_registered: bool) {
+        self.inner.client_registration(c, name, is_registered)
+    }
+
+    fn client_registration_cstr(&mut self, c: &Client, name: &CStr, is_registered: bool) {
+        self.inner.client_registration_cstr(c, name, is_registered)
+    }
+
+    fn port_registration(&mut self, c: &Client, port_id: PortId, is_registered: bool) {
+        // Connecting is not possible from a notification, so the supervisor thread retries.
+        if is_registered && !self.events.unrestored.lock().unwrap().is_empty() {
+            self.events.state.lock().unwrap().is_port_registered = true;
+            self.events.state_changed.notify_all();
+        }
+        if N::NOTIFICATIONS.contains(NotificationMask::PORT_REGISTRATION) {
+            self.inner.port_registration(c, port_id, is_registered)
+        }
+    }
+
+    fn port_rename(
+        &mut self,
+        c: &Client,
+        port_id: PortId,
+        old_name: &str,
+        new_name: &str,
+    ) -> Control {
+        self.inner.port_rename(c, port_id, old_name, new_name)
+    }
+
+    fn port_rename_cstr(
+        &mut self,
+        c: &Client,
+        port_id: PortId,
+        old_name: &CStr,
+        new_name: &CStr,
+    ) -> Control {
+        self.inner.port_rename_cstr(c, port_id, old_name, new_name)
+    }
+
+    fn ports_connected(
+        &mut self,
+        c: &Client,
+        port_id_a: PortId,
+        port_id_b: PortId,
+        are_connected: bool,
+    ) {
+        let ends = (self.port(c, port_id_a), self.port(c, port_id_b));
+        if let (Some((a, is_a_source)), Some((b, is_b_source))) = ends {
+            if matches!((&a, &b), (Endpoint::Own(_), _) | (_, Endpoint::Own(_))) {
+                let (source, destination) = match is_a_source {
+                    true => (a.clone(), b.clone()),
+                    false => (b.clone(), a.clone()),
+                };
+                let connection = Connection {
+                    source,
+                    destination,
+                };
+                let mut connections = self.events.connections.lock().unwrap();
+                let mut ports = self.events.ports.lock().unwrap();
+                match are_connected {
+                    true => {
+                        connections.insert(connection);
+                        ports.insert(port_id_a, (a, is_a_source));
+                        ports.insert(port_id_b, (b, is_b_source));
+                    }
+                    false => {
+                        connections.remove(&connection);
+                        // Only the ports of the remaining connections are kept.
+                        ports.retain(|_, (endpoint, _)| {
+                            connections.iter().any(|c| c.has_endpoint(endpoint))
+                        });
+                    }
+                };
+            }
+        }
+        if N::NOTIFICATIONS.contains(NotificationMask::PORTS_CONNECTED) {
+            self.inner
+                .ports_connected(c, port_id_a, port_id_b, are_connected)
+        }
+    }
+
+    fn graph_reorder(&mut self, c: &Client) -> Control {
+        self.inner.graph_reorder(c)
+    }
+
+    fn xrun(&mut self, c: &Client) -> Control {
+        self.inner.xrun(c)
+    }
+
+    fn latency(&mut self, c: &Client, mode: LatencyType) {
+        self.inner.latency(c, mode)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AudioIn, AudioOut, ClosureProcessHandler, ProcessScope};
+
+    #[test]
+    fn supervisor_tracks_connections_of_own_ports() {
+        let supervisor = Supervisor::new(
+            "supervisor_tracks_connections",
+            ClientOptions::NO_START_SERVER,
+            |c: &Client| {
+                let ports = (
+                    c.register_port("in", AudioIn::default())?,
+                    c.register_port("out", AudioOut::default())?,
+                );
+                let process = ClosureProcessHandler::new(move |_: &Client, _: &ProcessScope| {
+                    let _ = &ports;
+                    Control::Continue
+                });
+                Ok(((), process))
+            },
+        )
+        .unwrap();
+        assert!(supervisor.is_connected());
+        supervisor
+            .with_client(|c| {
+                c.connect_ports_by_name(
+                    "supervisor_tracks_connections:out",
+                    "supervisor_tracks_connections:in",
+                )
+            })
+            .unwrap()
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let connections = supervisor.events.connections.lock().unwrap().clone();
+        let expected = Connection {
+            source: Endpoint::Own("out".to_string()),
+            destination: Endpoint::Own("in".to_string()),
+        };
+        assert!(connections.contains(&expected));
+        assert_eq!(
+            expected.source.full_name("renamed"),
+            "renamed:out".to_string()
+        );
+    }
+
+    #[test]
+    fn supervisor_forgets_connections_to_closed_clients() {
+        let supervisor = Supervisor::new(
+            "supervisor_forgets_connections",
+            ClientOptions::NO_START_SERVER,
+            |c: &Client| {
+                let out = c.register_port("out", AudioOut::default())?;
+                let process = ClosureProcessHandler::new(move |_: &Client, _: &ProcessScope| {
+                    let _ = &out;
+                    Control::Continue
+                });
+                Ok(((), process))
+            },
+        )
+        .unwrap();
+        let (other, _) =
+            Client::new("supervisor_forgets_other", ClientOptions::NO_START_SERVER).unwrap();
+        other.register_port("in", AudioIn::default()).unwrap();
+        let other = other.activate_async((), ()).unwrap();
+        other
+            .as_client()
+            .connect_ports_by_name(
+                "supervisor_forgets_connections:out",
+                "supervisor_forgets_other:in",
+            )
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(supervisor.events.connections.lock().unwrap().len(), 1);
+
+        // The ports of the other client are gone by the time the disconnection is handled.
+        drop(other);
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(supervisor.events.connections.lock().unwrap().is_empty());
+        assert!(supervisor.events.ports.lock().unwrap().is_empty());
+    }
+}
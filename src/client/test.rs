@@ -1,6 +1,9 @@
 use crate::client::*;
 use crate::jack_enums::Error;
 use crate::{ClosureProcessHandler, Control, RingBuffer};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::{thread, time};
 
 fn open_test_client(name: &str) -> (Client, ClientStatus) {
     Client::new(name, ClientOptions::NO_START_SERVER).unwrap()
@@ -37,12 +40,77 @@ fn client_can_be_named() {
     assert_eq!(c.name(), name);
 }
 
+#[test]
+fn client_can_open_on_named_server() {
+    // "default" is the name of the server started by dummy_jack_server.sh.
+    let (c, _) = Client::new_with_server(
+        "client_can_open_on_named_server",
+        "default",
+        ClientOptions::NO_START_SERVER,
+    )
+    .unwrap();
+    assert_eq!(c.server_name(), Some("default"));
+    assert_eq!(open_test_client("client_on_default_server").0.server_name(), None);
+}
+
 #[test]
 fn client_can_activate() {
     let (c, _) = open_test_client("client_can_activate");
     let _ac = c.activate_async((), ()).unwrap();
 }
 
+#[test]
+#[cfg_attr(feature = "fake", ignore = "the fake server does not support process threads")]
+fn client_can_activate_process_thread() {
+    static CYCLES: AtomicUsize = AtomicUsize::new(0);
+    let dropped = Arc::new(AtomicBool::new(false));
+    let loop_dropped = dropped.clone();
+    let (c, _) = open_test_client("client_can_activate_process_thread");
+    let ac = c
+        .activate_process_thread((), move |_, process_loop| {
+            // Values owned by the loop are dropped when it returns on deactivation.
+            let _flag = DropFlag(loop_dropped.clone());
+            while let Some(scope) = process_loop.wait_cycle() {
+                assert!(scope.n_frames() > 0);
+                CYCLES.fetch_add(1, Ordering::Relaxed);
+                process_loop.signal_cycle(scope, Control::Continue);
+            }
+        })
+        .unwrap();
+    thread::sleep(time::Duration::from_secs(1));
+    ac.deactivate().unwrap();
+    assert!(CYCLES.load(Ordering::Relaxed) > 0);
+    assert!(dropped.load(Ordering::Relaxed));
+}
+
+#[test]
+#[cfg_attr(feature = "fake", ignore = "the fake server does not support process threads")]
+fn client_process_loop_stops_on_quit() {
+    let (c, _) = open_test_client("client_process_loop_stops_on_quit");
+    let (tx, rx) = std::sync::mpsc::channel();
+    let ac = c
+        .activate_process_thread((), move |_, process_loop| {
+            let mut cycles = 0;
+            while let Some(scope) = process_loop.wait_cycle() {
+                cycles += 1;
+                process_loop.signal_cycle(scope, Control::Quit);
+            }
+            tx.send(cycles).unwrap();
+        })
+        .unwrap();
+    let cycles = rx.recv_timeout(time::Duration::from_secs(1)).unwrap();
+    assert_eq!(cycles, 1);
+    ac.deactivate().unwrap();
+}
+
+struct DropFlag(Arc<AtomicBool>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
 #[test]
 fn client_can_set_buffer_size() {
     let (c, _) = open_test_client("client_can_set_buffer_size");
@@ -70,6 +138,25 @@ fn client_can_deactivate() {
     a.deactivate().unwrap();
 }
 
+#[test]
+fn client_can_close() {
+    let (c, _) = open_test_client("client_can_close");
+    assert_eq!(c.close(), Ok(()));
+}
+
+#[test]
+#[cfg_attr(feature = "fake", ignore = "the fake server does not support process threads")]
+fn client_can_reactivate_with_other_process_model() {
+    let (c, _) = open_test_client("client_can_reactivate_with_other_process_model");
+    let a = c
+        .activate_process_thread((), |_: &Client, _: &mut ProcessLoop| ())
+        .unwrap();
+    let (c, _, _) = a.deactivate().unwrap();
+    let a = c.activate_async((), ()).unwrap();
+    let (c, _, _) = a.deactivate().unwrap();
+    c.close().unwrap();
+}
+
 #[test]
 fn client_knows_buffer_size() {
     let (c, _) = open_test_client("client_knows_buffer_size");
@@ -93,6 +180,7 @@ fn client_knows_cpu_load() {
 #[test]
 fn client_can_estimate_frame_times() {
     let (c, _) = open_test_client("client_knows_frame_times");
+    run_cycles_on_test(&c, time::Duration::from_millis(100));
     let current_frame_time = c.frame_time();
     let time = c.frames_to_time(44_100);
     let frames = c.time_to_frames(1_000_000);
@@ -135,6 +223,7 @@ fn client_can_use_ringbuffer() {
         .unwrap();
 
     // spin until realtime closure has been run
+    run_cycles_on_test(_a.as_client(), time::Duration::from_millis(100));
     while reader.space() == 0 {}
 
     let mut outbuf = [0_u8; 8];
@@ -180,6 +269,62 @@ fn client_uuid() {
     assert_eq!(c2.name_by_uuid_str(&uuid3s), None);
 }
 
+#[test]
+fn client_real_time_priorities_are_consistent() {
+    let (c, _) = open_test_client("client_real_time_priorities");
+    match c.real_time_priority() {
+        Some(p) => {
+            assert!(c.is_realtime());
+            assert!(p <= c.max_real_time_priority().unwrap());
+        }
+        None => assert!(!c.is_realtime()),
+    }
+}
+
+#[test]
+fn client_lists_clients_with_ports() {
+    let (c1, _) = open_test_client("client_lists_clients1");
+    let (c2, _) = open_test_client("client_lists_clients2");
+    let (_c3, _) = open_test_client("client_lists_clients3");
+    let p = c2.register_port("p", crate::AudioIn::default()).unwrap();
+
+    let clients = c1.clients();
+    let info = clients
+        .iter()
+        .find(|info| info.name == "client_lists_clients2")
+        .unwrap();
+    assert_eq!(info.uuid, c2.uuid_string());
+    assert_eq!(info.ports, vec![p.name().unwrap()]);
+    if let Some(pid) = info.pid {
+        assert_eq!(pid, std::process::id());
+    }
+    // The listing client is listed without ports, other clients without ports are not.
+    let info = clients
+        .iter()
+        .find(|info| info.name == "client_lists_clients1")
+        .unwrap();
+    assert_eq!(info.uuid, c1.uuid_string());
+    assert!(info.ports.is_empty());
+    assert!(clients
+        .iter()
+        .all(|info| info.name != "client_lists_clients3"));
+}
+
+#[test]
+#[cfg_attr(feature = "fake", ignore = "the fake server does not support internal clients")]
+fn client_reports_missing_internal_clients() {
+    let (c, _) = open_test_client("client_reports_missing_internal_clients");
+    assert!(matches!(
+        c.internal_client_by_name("no_such_internal_client"),
+        Err(Error::ClientError(_))
+    ));
+    let load = crate::InternalClientOptions::new("no_such_internal_client")
+        .with_load_name("no_such_shared_object")
+        .with_load_init("")
+        .load(&c);
+    assert!(matches!(load, Err(Error::ClientError(_))));
+}
+
 #[cfg(feature = "metadata")]
 #[test]
 fn client_numeric_uuid() {
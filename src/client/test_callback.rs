@@ -2,12 +2,16 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{mem, ptr, thread, time};
 
 use super::*;
-use crate::{AudioIn, Client, Control, Frames, NotificationHandler, PortId, ProcessHandler};
+use crate::{
+    AudioIn, AudioOut, Client, Control, Frames, LatencyType, NotificationHandler, PortId,
+    ProcessHandler, TransportBBT, TransportPosition, TransportState,
+};
 
 #[derive(Debug, Default)]
 pub struct Counter {
     pub process_return_val: Control,
     pub induce_xruns: bool,
+    pub panic_in_process: bool,
     pub thread_init_count: AtomicUsize,
     pub frames_processed: usize,
     pub process_thread: Option<thread::ThreadId>,
@@ -17,12 +21,20 @@ pub struct Counter {
     pub unregistered_client_history: Vec<String>,
     pub port_register_history: Vec<PortId>,
     pub port_unregister_history: Vec<PortId>,
+    pub port_rename_history: Vec<(String, String)>,
     pub xruns_count: usize,
+    pub freewheel_history: Vec<bool>,
+    pub freewheeling_cycles: usize,
+    pub latency_history: Vec<LatencyType>,
+    pub timebase_cycles: usize,
+    pub sync_calls: usize,
     pub last_frame_time: Frames,
     pub frames_since_cycle_start: Frames,
 }
 
 impl NotificationHandler for Counter {
+    const LATENCY: bool = true;
+
     fn thread_init(&self, _: &Client) {
         self.thread_init_count.fetch_add(1, Ordering::Relaxed);
     }
@@ -43,18 +55,44 @@ impl NotificationHandler for Counter {
         }
     }
 
+    fn port_rename(
+        &mut self,
+        _: &Client,
+        _: PortId,
+        old_name: &str,
+        new_name: &str,
+    ) -> Control {
+        self.port_rename_history
+            .push((old_name.to_string(), new_name.to_string()));
+        Control::Continue
+    }
+
     fn xrun(&mut self, _: &Client) -> Control {
         self.xruns_count += 1;
         Control::Continue
     }
+
+    fn freewheel(&mut self, _: &Client, is_freewheel_enabled: bool) {
+        self.freewheel_history.push(is_freewheel_enabled);
+    }
+
+    fn latency(&mut self, _: &Client, mode: LatencyType) {
+        self.latency_history.push(mode);
+    }
 }
 
 impl ProcessHandler for Counter {
     fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        if self.panic_in_process {
+            panic!("process panicked");
+        }
         self.frames_processed += ps.n_frames() as usize;
         self.last_frame_time = ps.last_frame_time();
         self.frames_since_cycle_start = ps.frames_since_cycle_start();
         let _cycle_times = ps.cycle_times();
+        if ps.is_freewheeling() {
+            self.freewheeling_cycles += 1;
+        }
         if self.induce_xruns {
             thread::sleep(time::Duration::from_millis(400));
         }
@@ -67,6 +105,25 @@ impl ProcessHandler for Counter {
         self.buffer_size_thread_history.push(thread::current().id());
         Control::Continue
     }
+
+    fn sync(&mut self, _: &Client, _: TransportState, _: &TransportPosition) -> bool {
+        self.sync_calls += 1;
+        true
+    }
+
+    fn timebase(
+        &mut self,
+        _: &Client,
+        _: TransportState,
+        _: Frames,
+        pos: &mut TransportPosition,
+        _: bool,
+    ) {
+        let mut bbt = TransportBBT::default();
+        bbt.with_bbt(1, 1, 0);
+        pos.set_bbt(Some(bbt)).unwrap();
+        self.timebase_cycles += 1;
+    }
 }
 
 fn open_test_client(name: &str) -> Client {
@@ -120,10 +177,126 @@ fn client_cback_calls_thread_init() {
 #[test]
 fn client_cback_calls_process() {
     let ac = active_test_client("client_cback_calls_process");
+    run_cycles_on_test(ac.as_client(), time::Duration::from_millis(100));
     let counter = ac.deactivate().unwrap().2;
     assert!(counter.frames_processed > 0);
     assert!(counter.last_frame_time > 0);
-    assert!(counter.frames_since_cycle_start > 0);
+    // No time passes during the cycles of the fake server.
+    if cfg!(not(feature = "fake")) {
+        assert!(counter.frames_since_cycle_start > 0);
+    }
+}
+
+#[test]
+#[cfg_attr(feature = "fake", ignore = "the handover waits for cycles on another thread")]
+fn client_cback_can_replace_process_handler() {
+    let ac = active_test_client("client_cback_can_replace_process_handler");
+    let old = ac.replace_process_handler(Counter::default()).unwrap();
+    assert!(old.frames_processed > 0);
+    let counter = ac.deactivate().unwrap().2;
+    assert!(counter.frames_processed > 0);
+}
+
+#[test]
+fn client_cback_can_replace_notification_handler() {
+    let ac = active_test_client("client_cback_can_replace_notification_handler");
+    let old = ac.replace_notification_handler(Counter::default());
+    assert_eq!(old.frames_processed, 0);
+    ac.deactivate().unwrap();
+}
+
+#[test]
+fn client_cback_sends_notifications_to_channel() {
+    let (handler, receiver) = ChannelNotificationHandler::new();
+    let ac = open_test_client("client_cback_sends_notifications")
+        .activate_async(handler, ())
+        .unwrap();
+    let other = open_test_client("client_cback_sends_notifications_other");
+    let expected = Notification::ClientRegistration {
+        name: other.name().to_string(),
+        is_registered: true,
+    };
+    let timeout = time::Duration::from_secs(1);
+    let mut received = std::iter::from_fn(|| receiver.recv_timeout(timeout));
+    assert!(received.any(|n| n == expected));
+    ac.deactivate().unwrap();
+}
+
+#[test]
+fn client_cback_catches_panic_in_process() {
+    let ac = open_test_client("client_cback_catches_panic_in_process")
+        .activate_async(
+            (),
+            ClosureProcessHandler::new(|_: &Client, _: &ProcessScope| panic!("process panicked")),
+        )
+        .unwrap();
+    ac.set_panic_policy(PanicPolicy::Silence);
+    run_cycles_on_test(ac.as_client(), time::Duration::from_millis(200));
+    assert!(ac.has_panicked());
+    let payload = ac.take_panic().unwrap();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"process panicked"));
+    ac.deactivate().unwrap();
+}
+
+#[test]
+fn client_cback_reactivation_forgets_panics() {
+    let ac = open_test_client("client_cback_reactivation_forgets_panics")
+        .activate_async(
+            (),
+            ClosureProcessHandler::new(|_: &Client, _: &ProcessScope| panic!("process panicked")),
+        )
+        .unwrap();
+    ac.set_panic_policy(PanicPolicy::Silence);
+    run_cycles_on_test(ac.as_client(), time::Duration::from_millis(200));
+    assert!(ac.has_panicked());
+    let ac = ac.deactivate().unwrap().0.activate_async((), ()).unwrap();
+    assert!(!ac.has_panicked());
+    assert!(ac.take_panic().is_none());
+    assert_eq!(ac.panic_policy(), PanicPolicy::Quit);
+    ac.deactivate().unwrap();
+}
+
+#[test]
+fn client_cback_replacing_process_handler_ends_silence() {
+    let panicking = Counter {
+        panic_in_process: true,
+        ..Counter::default()
+    };
+    let ac = open_test_client("client_cback_replacing_ends_silence")
+        .activate_async((), panicking)
+        .unwrap();
+    ac.set_panic_policy(PanicPolicy::Silence);
+    run_cycles_on_test(ac.as_client(), time::Duration::from_millis(100));
+    assert!(ac.has_panicked());
+    let replaced = thread::scope(|s| {
+        let replacement = s.spawn(|| ac.replace_process_handler(Counter::default()));
+        // The fake server only runs cycles on this thread.
+        while !replacement.is_finished() {
+            run_cycles_on_test(ac.as_client(), time::Duration::from_millis(10));
+        }
+        replacement.join().unwrap()
+    });
+    assert!(replaced.unwrap().panic_in_process);
+    run_cycles_on_test(ac.as_client(), time::Duration::from_millis(100));
+    let buffer_size = ac.as_client().buffer_size();
+    let counter = ac.deactivate().unwrap().2;
+    assert!(counter.frames_processed > 0);
+    assert_eq!(counter.buffer_size_change_history, vec![buffer_size]);
+}
+
+#[test]
+fn client_cback_converts_non_utf8_names_lossily() {
+    let wc = unsafe { Client::from_raw(ptr::null_mut()) };
+    let mut counter = Counter::default();
+    let latin1 = std::ffi::CStr::from_bytes_with_nul(b"caf\xe9\0").unwrap();
+    counter.client_registration_cstr(&wc, latin1, true);
+    counter.port_rename_cstr(&wc, 0, latin1, latin1);
+    assert_eq!(counter.registered_client_history, vec!["caf\u{fffd}"]);
+    assert_eq!(
+        counter.port_rename_history,
+        vec![("caf\u{fffd}".to_string(), "caf\u{fffd}".to_string())]
+    );
+    mem::forget(wc);
 }
 
 #[test]
@@ -151,6 +324,7 @@ fn client_cback_calls_buffer_size_on_process_thread() {
     let initial = ac.as_client().buffer_size();
     let second = initial / 2;
     ac.as_client().set_buffer_size(second).unwrap();
+    run_cycles_on_test(ac.as_client(), time::Duration::from_millis(100));
     let counter = ac.deactivate().unwrap().2;
     let process_thread = counter.process_thread.unwrap();
     assert_eq!(
@@ -188,6 +362,7 @@ fn client_cback_calls_after_client_unregistered() {
 }
 
 #[test]
+#[cfg_attr(feature = "fake", ignore = "the fake server has no deadlines to miss")]
 fn client_cback_reports_xruns() {
     let c = open_test_client("client_cback_reports_xruns");
     let counter = Counter {
@@ -199,6 +374,81 @@ fn client_cback_reports_xruns() {
     assert!(counter.xruns_count > 0, "No xruns encountered.");
 }
 
+#[test]
+fn client_cback_calls_freewheel() {
+    let ac = active_test_client("client_cback_calls_freewheel");
+    ac.as_client().set_freewheel(true).unwrap();
+    run_cycles_on_test(ac.as_client(), time::Duration::from_millis(200));
+    ac.as_client().set_freewheel(false).unwrap();
+    run_cycles_on_test(ac.as_client(), time::Duration::from_millis(200));
+    let (_, notifications, process) = ac.deactivate().unwrap();
+    assert_eq!(notifications.freewheel_history, [true, false]);
+    assert!(process.freewheeling_cycles > 0, "No freewheeling cycles processed.");
+}
+
+#[test]
+fn client_cback_calls_latency() {
+    let ac = active_test_client("client_cback_calls_latency");
+    let pa = ac
+        .as_client()
+        .register_port("pa", AudioOut::default())
+        .unwrap();
+    let pb = ac
+        .as_client()
+        .register_port("pb", AudioIn::default())
+        .unwrap();
+    ac.as_client().connect_ports(&pa, &pb).unwrap();
+    ac.as_client().recompute_total_latencies().unwrap();
+    let counter = ac.deactivate().unwrap().1;
+    assert!(counter.latency_history.contains(&LatencyType::Capture));
+    assert!(counter.latency_history.contains(&LatencyType::Playback));
+}
+
+#[test]
+fn client_cback_calls_timebase() {
+    let ac = active_test_client("client_cback_calls_timebase");
+    let other = active_test_client("client_cback_calls_timebase_other");
+    ac.become_timebase_master(false).unwrap();
+    assert_eq!(
+        other.become_timebase_master(true),
+        Err(crate::Error::TimebaseMasterExists)
+    );
+    let transport = ac.as_client().transport();
+    transport.start().unwrap();
+    run_cycles_on_test(ac.as_client(), time::Duration::from_secs(1));
+    assert!(transport.query().unwrap().pos.valid_bbt());
+    transport.stop().unwrap();
+    transport.release_timebase().unwrap();
+    assert_eq!(transport.release_timebase(), Err(crate::Error::TimebaseError));
+    let counter = ac.deactivate().unwrap().2;
+    assert!(counter.timebase_cycles > 0);
+}
+
+#[test]
+fn client_cback_deactivation_releases_timebase() {
+    let ac = active_test_client("client_cback_releases_timebase");
+    let other = active_test_client("client_cback_releases_timebase_other");
+    ac.become_timebase_master(false).unwrap();
+    let c = ac.deactivate().unwrap().0;
+    other.become_timebase_master(true).unwrap();
+    c.close().unwrap();
+}
+
+#[test]
+fn client_cback_calls_sync_when_slow_sync_is_enabled() {
+    let ac = active_test_client("client_cback_calls_sync");
+    let transport = ac.as_client().transport();
+    transport.set_sync_timeout(1_000_000).unwrap();
+    transport.locate(0).unwrap();
+    run_cycles_on_test(ac.as_client(), time::Duration::from_millis(200));
+    ac.set_slow_sync(true).unwrap();
+    transport.locate(1024).unwrap();
+    run_cycles_on_test(ac.as_client(), time::Duration::from_millis(200));
+    ac.set_slow_sync(false).unwrap();
+    let counter = ac.deactivate().unwrap().2;
+    assert!(counter.sync_calls > 0);
+}
+
 #[test]
 fn client_cback_calls_port_registered() {
     let ac = active_test_client("client_cback_cpr");
@@ -245,3 +495,18 @@ fn client_cback_calls_port_unregistered() {
         "Did not detect port deregistrations."
     );
 }
+
+#[test]
+fn client_cback_calls_port_rename() {
+    let ac = active_test_client("client_cback_cpn");
+    let mut pa = ac
+        .as_client()
+        .register_port("pa", AudioIn::default())
+        .unwrap();
+    pa.set_name("pb").unwrap();
+    let counter = ac.deactivate().unwrap().1;
+    assert_eq!(
+        counter.port_rename_history,
+        [("client_cback_cpn:pa".to_string(), "client_cback_cpn:pb".to_string())],
+    );
+}
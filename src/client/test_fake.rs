@@ -0,0 +1,366 @@
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::test_callback::Counter;
+use super::*;
+use crate::{
+    internal_client_initialize, AudioIn, AudioOut, ChannelNotificationHandler, ClientStatus,
+    Control, Error, InternalClientFinish, MidiIn, MidiOut, Notification, NotificationHandler,
+    PanicPolicy, PortFlags, ProcessLoop, RawMidi, Supervisor, TransportState, XrunRecorder,
+};
+use jack_sys::fake;
+
+fn open_test_client(name: &str) -> Client {
+    Client::new(name, ClientOptions::NO_START_SERVER).unwrap().0
+}
+
+#[test]
+fn fake_server_starts_with_system_ports() {
+    let c = open_test_client("fake_server_starts_with_system_ports");
+    c.register_port("in", AudioIn::default()).unwrap();
+    // Servers are not shared between threads, so the ports of other tests do not show up.
+    assert_eq!(
+        c.ports(None, None, PortFlags::empty()),
+        vec![
+            "system:capture_1",
+            "system:capture_2",
+            "system:playback_1",
+            "system:playback_2",
+            "fake_server_starts_with_system_ports:in",
+        ]
+    );
+}
+
+#[test]
+fn fake_server_runs_cycles_on_demand() {
+    let ac = open_test_client("fake_server_runs_cycles_on_demand")
+        .activate_async((), Counter::default())
+        .unwrap();
+    let buffer_size = ac.as_client().buffer_size() as usize;
+    fake::run_cycles(3);
+    let counter = ac.deactivate().unwrap().2;
+    assert_eq!(counter.frames_processed, 3 * buffer_size);
+    assert_eq!(counter.last_frame_time as usize, 2 * buffer_size);
+}
+
+#[test]
+fn fake_server_processes_sources_first() {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = open_test_client("fake_server_sink");
+    let input = sink.register_port("in", AudioIn::default()).unwrap();
+    let input_name = input.name().unwrap();
+    let sink_received = received.clone();
+    let sink = sink
+        .activate_async(
+            (),
+            ClosureProcessHandler::new(move |_, ps| {
+                sink_received.lock().unwrap().push(input.as_slice(ps)[0]);
+                Control::Continue
+            }),
+        )
+        .unwrap();
+
+    // The source is activated after the sink, but has to run first to feed it.
+    let source = open_test_client("fake_server_source");
+    let mut output = source.register_port("out", AudioOut::default()).unwrap();
+    source
+        .connect_ports_by_name(&output.name().unwrap(), &input_name)
+        .unwrap();
+    let mut value = 0.0;
+    let source = source
+        .activate_async(
+            (),
+            ClosureProcessHandler::new(move |_, ps| {
+                value += 1.0;
+                output.as_mut_slice(ps).iter_mut().for_each(|s| *s = value);
+                Control::Continue
+            }),
+        )
+        .unwrap();
+
+    fake::run_cycles(2);
+    assert_eq!(*received.lock().unwrap(), vec![1.0, 2.0]);
+    source.deactivate().unwrap();
+    sink.deactivate().unwrap();
+}
+
+#[test]
+fn failed_activation_returns_error_and_closes_client() {
+    // The fake server does not support process threads, so registering the process loop fails.
+    let res = open_test_client("failed_activation_returns_error")
+        .activate_process_thread((), |_: &Client, _: &mut ProcessLoop| ());
+    assert!(matches!(res, Err(Error::CallbackRegistrationError)));
+    // The client was closed, so its name is free again.
+    let options = ClientOptions::NO_START_SERVER | ClientOptions::USE_EXACT_NAME;
+    assert!(Client::new("failed_activation_returns_error", options).is_ok());
+}
+
+#[test]
+fn fake_server_passes_midi_between_clients() {
+    let c = open_test_client("fake_server_passes_midi_between_clients");
+    let input = c.register_port("in", MidiIn::default()).unwrap();
+    let mut output = c.register_port("out", MidiOut::default()).unwrap();
+    c.connect_ports(&output, &input).unwrap();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let process_received = received.clone();
+    let ac = c
+        .activate_async(
+            (),
+            ClosureProcessHandler::new(move |_, ps| {
+                let mut received = process_received.lock().unwrap();
+                received.extend(input.iter(ps).map(|m| (m.time, m.bytes.to_vec())));
+                let message = RawMidi {
+                    time: 3,
+                    bytes: &[0x90, 60, 100],
+                };
+                output.writer(ps).write(&message).unwrap();
+                Control::Continue
+            }),
+        )
+        .unwrap();
+
+    // The output of a cycle reaches the input of the same client in the next cycle.
+    fake::run_cycles(2);
+    assert_eq!(*received.lock().unwrap(), vec![(3, vec![0x90, 60, 100])]);
+    ac.deactivate().unwrap();
+}
+
+#[test]
+fn silenced_client_writes_silence_to_its_outputs() {
+    let c = open_test_client("silenced_client");
+    let mut output = c.register_port("out", AudioOut::default()).unwrap();
+    let output_name = output.name().unwrap();
+    let mut cycles = 0;
+    let ac = c
+        .activate_async(
+            (),
+            ClosureProcessHandler::new(move |_, ps| {
+                cycles += 1;
+                output.as_mut_slice(ps).iter_mut().for_each(|s| *s = 1.0);
+                if cycles == 2 {
+                    panic!("process panicked");
+                }
+                Control::Continue
+            }),
+        )
+        .unwrap();
+    ac.set_panic_policy(PanicPolicy::Silence);
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = open_test_client("silenced_client_sink");
+    let input = sink.register_port("in", AudioIn::default()).unwrap();
+    sink.connect_ports_by_name(&output_name, &input.name().unwrap())
+        .unwrap();
+    let sink_received = received.clone();
+    let sink = sink
+        .activate_async(
+            (),
+            ClosureProcessHandler::new(move |_, ps| {
+                sink_received.lock().unwrap().push(input.as_slice(ps)[0]);
+                Control::Continue
+            }),
+        )
+        .unwrap();
+
+    fake::run_cycles(3);
+    assert_eq!(*received.lock().unwrap(), vec![1.0, 0.0, 0.0]);
+    assert!(ac.has_panicked());
+    sink.deactivate().unwrap();
+    ac.deactivate().unwrap();
+}
+
+#[test]
+fn fake_server_notifies_before_returning() {
+    let ac = open_test_client("fake_server_notifies_before_returning")
+        .activate_async(Counter::default(), ())
+        .unwrap();
+    let other = open_test_client("fake_server_notifies_other");
+    let port = other.register_port("in", AudioIn::default()).unwrap();
+    fake::xrun();
+    drop(port);
+    drop(other);
+
+    let counter = ac.deactivate().unwrap().1;
+    assert_eq!(
+        counter.registered_client_history,
+        vec!["fake_server_notifies_other"]
+    );
+    assert_eq!(
+        counter.unregistered_client_history,
+        vec!["fake_server_notifies_other"]
+    );
+    assert_eq!(counter.port_register_history.len(), 1);
+    assert_eq!(
+        counter.port_unregister_history,
+        counter.port_register_history
+    );
+    assert_eq!(counter.xruns_count, 1);
+}
+
+#[test]
+fn fake_server_rolls_transport_with_cycles() {
+    let c = open_test_client("fake_server_rolls_transport_with_cycles");
+    let buffer_size = c.buffer_size();
+    let transport = c.transport();
+    transport.locate(100).unwrap();
+    transport.start().unwrap();
+    fake::run_cycles(2);
+    let state = transport.query().unwrap();
+    assert_eq!(state.state, TransportState::Rolling);
+    assert_eq!(state.pos.frame(), 100 + 2 * buffer_size);
+    transport.stop().unwrap();
+    fake::run_cycles(1);
+    assert_eq!(
+        transport.query().unwrap().pos.frame(),
+        100 + 2 * buffer_size
+    );
+}
+
+#[derive(Clone, Default)]
+struct ShutdownFlag(Arc<AtomicBool>);
+
+impl NotificationHandler for ShutdownFlag {
+    fn shutdown(&mut self, _: ClientStatus, _: &str) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn fake_server_can_shut_down_and_restart() {
+    let flag = ShutdownFlag::default();
+    let _ac = open_test_client("fake_server_can_shut_down")
+        .activate_async(flag.clone(), ())
+        .unwrap();
+    fake::shutdown("stopped by test");
+    assert!(flag.0.load(Ordering::Relaxed));
+    assert!(!fake::is_running());
+    assert!(Client::new("fake_server_after_shutdown", ClientOptions::NO_START_SERVER).is_err());
+
+    fake::reset();
+    assert!(fake::is_running());
+    open_test_client("fake_server_after_shutdown");
+}
+
+#[test]
+fn supervisor_restores_connections_after_shutdown() {
+    let (receivers, received) = crossbeam_channel::unbounded();
+    let supervisor = Supervisor::new(
+        "supervisor_restores",
+        ClientOptions::NO_START_SERVER,
+        move |c: &Client| {
+            let ports = (
+                c.register_port("in", AudioIn::default())?,
+                c.register_port("out", AudioOut::default())?,
+            );
+            let (handler, notifications) = ChannelNotificationHandler::new();
+            receivers.send(notifications).unwrap();
+            let process = ClosureProcessHandler::new(move |_, _| {
+                let _ = &ports;
+                Control::Continue
+            });
+            Ok((handler, process))
+        },
+    )
+    .unwrap();
+    supervisor
+        .with_client(|c| {
+            c.connect_ports_by_name("supervisor_restores:out", "supervisor_restores:in")?;
+            c.connect_ports_by_name("supervisor_restores:out", "system:playback_1")
+        })
+        .unwrap()
+        .unwrap();
+    received.recv().unwrap();
+
+    // The supervisor reopens the client on its own thread, which has a server of its own.
+    fake::shutdown("stopped by test");
+    let notifications = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    let mut restored = Vec::new();
+    while restored.len() < 2 {
+        match notifications.recv_timeout(Duration::from_secs(5)) {
+            Some(Notification::PortsConnected {
+                name_a: Some(name_a),
+                name_b: Some(name_b),
+                are_connected: true,
+                ..
+            }) => restored.push((name_a, name_b)),
+            Some(_) => (),
+            None => break,
+        }
+    }
+    restored.sort();
+    assert_eq!(
+        restored,
+        vec![
+            (
+                "supervisor_restores:out".to_string(),
+                "supervisor_restores:in".to_string()
+            ),
+            (
+                "supervisor_restores:out".to_string(),
+                "system:playback_1".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn xrun_recorder_records_xruns_of_the_server() {
+    let recorder = XrunRecorder::wrap(Counter::default(), 2);
+    let history = recorder.history();
+    let ac = open_test_client("xrun_recorder_records_xruns")
+        .activate_async(recorder, ())
+        .unwrap();
+    let buffer_size = ac.as_client().buffer_size();
+    fake::xrun();
+    fake::run_cycles(2);
+    fake::xrun();
+    fake::xrun();
+    assert_eq!(history.total_count(), 3);
+    let frame_times: Vec<_> = history.events().iter().map(|e| e.frame_time).collect();
+    assert_eq!(frame_times, vec![2 * buffer_size, 2 * buffer_size]);
+
+    let recorder = ac.deactivate().unwrap().1;
+    assert_eq!(recorder.inner().xruns_count, 3);
+}
+
+struct DropFlag(Arc<AtomicBool>);
+
+impl NotificationHandler for DropFlag {}
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn failed_internal_client_keeps_server_owned_client_open() {
+    static FINISH: InternalClientFinish = InternalClientFinish::new();
+    let c = open_test_client("failed_internal_client");
+    let failing = |_: &Client, _: &str| -> Result<((), ()), Error> { Err(Error::UnknownError) };
+    let panicking = |_: &Client, _: &str| -> Result<((), ()), Error> { panic!("init panicked") };
+    let dropped = Arc::new(AtomicBool::new(false));
+    let handler = DropFlag(dropped.clone());
+    let unregistrable = move |_: &Client, _: &str| Ok((handler, ()));
+    unsafe {
+        assert_eq!(
+            internal_client_initialize(c.raw(), ptr::null(), &FINISH, failing),
+            1
+        );
+        assert_eq!(
+            internal_client_initialize(c.raw(), ptr::null(), &FINISH, panicking),
+            1
+        );
+        // Callbacks can not be registered while the client is active.
+        assert_eq!(jack_sys::jack_activate(c.raw()), 0);
+        assert_eq!(
+            internal_client_initialize(c.raw(), ptr::null(), &FINISH, unregistrable),
+            1
+        );
+        assert_eq!(jack_sys::jack_deactivate(c.raw()), 0);
+    }
+    assert!(dropped.load(Ordering::Relaxed));
+    assert_eq!(c.close(), Ok(()));
+}
@@ -0,0 +1,272 @@
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    Client, ClientStatus, Control, Frames, LatencyType, NotificationHandler, NotificationMask,
+    PortId, Time,
+};
+
+/// A single xrun, as recorded by `XrunRecorder`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XrunEvent {
+    /// The delay in microseconds that caused the xrun, see `Client::xrun_delayed_usecs`.
+    pub delayed_usecs: f32,
+    /// The JACK time in microseconds when the xrun was reported, see `jack::get_time`.
+    pub time: Time,
+    /// The estimated frame time when the xrun was reported, see `Client::frame_time`.
+    pub frame_time: Frames,
+    /// The cpu load of JACK when the xrun was reported, see `Client::cpu_load`.
+    pub cpu_load: f32,
+}
+
+#[derive(Debug)]
+struct XrunLog {
+    events: VecDeque<XrunEvent>,
+    capacity: usize,
+    total_count: usize,
+}
+
+/// A bounded history of xruns, shared between an `XrunRecorder` and the rest of the application.
+///
+/// Cloning an `XrunHistory` creates another handle to the same history.
+#[derive(Clone, Debug)]
+pub struct XrunHistory(Arc<Mutex<XrunLog>>);
+
+impl XrunHistory {
+    fn new(capacity: usize) -> XrunHistory {
+        XrunHistory(Arc::new(Mutex::new(XrunLog {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            total_count: 0,
+        })))
+    }
+
+    fn record(&self, event: XrunEvent) {
+        let mut log = self.0.lock().unwrap();
+        if log.capacity == 0 {
+            log.total_count += 1;
+            return;
+        }
+        if log.events.len() == log.capacity {
+            log.events.pop_front();
+        }
+        log.events.push_back(event);
+        log.total_count += 1;
+    }
+
+    /// The recorded xruns, oldest first. Only the most recent ones are kept, up to the capacity
+    /// the recorder was created with.
+    pub fn events(&self) -> Vec<XrunEvent> {
+        self.0.lock().unwrap().events.iter().copied().collect()
+    }
+
+    /// The most recent xrun.
+    pub fn last(&self) -> Option<XrunEvent> {
+        self.0.lock().unwrap().events.back().copied()
+    }
+
+    /// The number of xruns since the history was created or cleared, including the ones that no
+    /// longer fit in the history.
+    pub fn total_count(&self) -> usize {
+        self.0.lock().unwrap().total_count
+    }
+
+    /// Remove all recorded xruns and reset the total count.
+    pub fn clear(&self) {
+        let mut log = self.0.lock().unwrap();
+        log.events.clear();
+        log.total_count = 0;
+    }
+}
+
+/// A `NotificationHandler` that records every xrun into an `XrunHistory`.
+///
+/// The recorder wraps another notification handler, which keeps receiving all notifications,
+/// including xruns.
+///
+/// # Example
+/// ```
+/// let (client, _status) =
+///     jack::Client::new("my_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let recorder = jack::XrunRecorder::new(64);
+/// let history = recorder.history();
+/// let active_client = client.activate_async(recorder, ()).unwrap();
+/// for xrun in history.events() {
+///     println!("xrun of {}us at {}", xrun.delayed_usecs, xrun.time);
+/// }
+/// active_client.deactivate().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct XrunRecorder<N = ()> {
+    history: XrunHistory,
+    inner: N,
+}
+
+impl XrunRecorder<()> {
+    /// Create a recorder that keeps the most recent `capacity` xruns.
+    pub fn new(capacity: usize) -> XrunRecorder<()> {
+        XrunRecorder::wrap((), capacity)
+    }
+}
+
+impl<N> XrunRecorder<N> {
+    /// Create a recorder that keeps the most recent `capacity` xruns and forwards all
+    /// notifications to `inner`.
+    pub fn wrap(inner: N, capacity: usize) -> XrunRecorder<N> {
+        XrunRecorder {
+            history: XrunHistory::new(capacity),
+            inner,
+        }
+    }
+
+    /// A handle to the recorded xruns.
+    pub fn history(&self) -> XrunHistory {
+        self.history.clone()
+    }
+
+    /// The wrapped notification handler.
+    pub fn inner(&self) -> &N {
+        &self.inner
+    }
+
+    /// Consume the recorder and return the wrapped notification handler.
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+}
+
+impl<N: NotificationHandler> NotificationHandler for XrunRecorder<N> {
+    const NOTIFICATIONS: NotificationMask = NotificationMask::from_bits_truncate(
+        N::NOTIFICATIONS.bits() | NotificationMask::XRUN.bits(),
+    );
+
+    const LATENCY: bool = N::LATENCY;
+
+    fn thread_init(&self, c: &Client) {
+        self.inner.thread_init(c)
+    }
+
+    fn shutdown(&mut self, status: ClientStatus, reason: &str) {
+        self.inner.shutdown(status, reason)
+    }
+
+    fn freewheel(&mut self, c: &Client, is_freewheel_enabled: bool) {
+        self.inner.freewheel(c, is_freewheel_enabled)
+    }
+
+    fn sample_rate(&mut self, c: &Client, srate: Frames) -> Control {
+        self.inner.sample_rate(c, srate)
+    }
+
+    fn client_registration(&mut self, c: &Client, name: &str, is_registered: bool) {
+        self.inner.client_registration(c, name, is_registered)
+    }
+
+    fn client_registration_cstr(&mut self, c: &Client, name: &CStr, is_registered: bool) {
+        self.inner.client_registration_cstr(c, name, is_registered)
+    }
+
+    fn port_registration(&mut self, c: &Client, port_id: PortId, is_registered: bool) {
+        self.inner.port_registration(c, port_id, is_registered)
+    }
+
+    fn port_rename(
+        &mut self,
+        c: &Client,
+        port_id: PortId,
+        old_name: &str,
+        new_name: &str,
+    ) -> Control {
+        self.inner.port_rename(c, port_id, old_name, new_name)
+    }
+
+    fn port_rename_cstr(
+        &mut self,
+        c: &Client,
+        port_id: PortId,
+        old_name: &CStr,
+        new_name: &CStr,
+    ) -> Control {
+        self.inner.port_rename_cstr(c, port_id, old_name, new_name)
+    }
+
+    fn ports_connected(
+        &mut self,
+        c: &Client,
+        port_id_a: PortId,
+        port_id_b: PortId,
+        are_connected: bool,
+    ) {
+        self.inner
+            .ports_connected(c, port_id_a, port_id_b, are_connected)
+    }
+
+    fn graph_reorder(&mut self, c: &Client) -> Control {
+        self.inner.graph_reorder(c)
+    }
+
+    fn xrun(&mut self, c: &Client) -> Control {
+        self.history.record(XrunEvent {
+            delayed_usecs: c.xrun_delayed_usecs(),
+            time: crate::get_time(),
+            frame_time: c.frame_time(),
+            cpu_load: c.cpu_load(),
+        });
+        match N::NOTIFICATIONS.contains(NotificationMask::XRUN) {
+            true => self.inner.xrun(c),
+            false => Control::Continue,
+        }
+    }
+
+    fn latency(&mut self, c: &Client, mode: LatencyType) {
+        self.inner.latency(c, mode)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(frame_time: Frames) -> XrunEvent {
+        XrunEvent {
+            delayed_usecs: 1.0,
+            time: 0,
+            frame_time,
+            cpu_load: 0.0,
+        }
+    }
+
+    #[test]
+    fn xrun_history_keeps_most_recent_events() {
+        let history = XrunHistory::new(2);
+        for frame_time in 0..3 {
+            history.record(event(frame_time));
+        }
+        assert_eq!(history.events(), vec![event(1), event(2)]);
+        assert_eq!(history.last(), Some(event(2)));
+        assert_eq!(history.total_count(), 3);
+    }
+
+    #[test]
+    fn xrun_recorder_adds_xrun_to_notifications() {
+        assert_eq!(
+            <XrunRecorder as NotificationHandler>::NOTIFICATIONS,
+            NotificationMask::XRUN
+        );
+        assert_eq!(
+            <XrunRecorder<XrunRecorder> as NotificationHandler>::NOTIFICATIONS,
+            NotificationMask::XRUN
+        );
+    }
+
+    #[test]
+    fn xrun_history_can_be_cleared() {
+        let history = XrunHistory::new(0);
+        history.record(event(0));
+        assert_eq!(history.events(), vec![]);
+        assert_eq!(history.total_count(), 1);
+        history.clear();
+        assert_eq!(history.total_count(), 0);
+    }
+}
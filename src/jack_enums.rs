@@ -6,10 +6,17 @@ pub enum Error {
     CallbackDeregistrationError,
     CallbackRegistrationError,
     ClientActivationError,
+    ClientCloseError,
     ClientDeactivationError,
     ClientError(ClientStatus),
+    ClientNameReservationError(String),
+    ClientNameTooLong(String),
     FreewheelError,
+    InvalidClientName(String),
+    InvalidCommandLine(String),
     InvalidDeactivation,
+    InvalidSessionPath(String),
+    InvalidUuid(String),
     NotEnoughSpace,
     PortAliasError,
     PortAlreadyConnected(String, String),
@@ -18,8 +25,14 @@ pub enum Error {
     PortMonitorError,
     PortNamingError,
     PortRegistrationError(String),
+    ProcessHandlerReplacementError,
+    RealTimeSchedulingError,
+    SessionError,
     SetBufferSizeError,
+    ThreadError,
     TimeError,
+    TimebaseError,
+    TimebaseMasterExists,
     WeakFunctionNotFound(&'static str),
     ClientIsNoLongerAlive,
     RingbufferCreateFailed,
@@ -72,4 +85,11 @@ impl LatencyType {
             LatencyType::Capture => jack_sys::JackCaptureLatency,
         }
     }
+
+    pub(crate) fn from_ffi(mode: libc::c_uint) -> LatencyType {
+        match mode {
+            jack_sys::JackPlaybackLatency => LatencyType::Playback,
+            _ => LatencyType::Capture,
+        }
+    }
 }
@@ -32,10 +32,26 @@
 //! To access the data of registered ports, use their specialized methods within a `ProcessHandler`
 //! callback. For example, `Port<AudioIn>::as_mut_slice` returns a audio buffer that can be written
 //! to.
+//!
+//! # Testing
+//!
+//! With the `fake` feature, the crate talks to an in-process fake server instead of JACK, so tests
+//! do not need a running server. Process cycles only run when `jack_sys::fake::run_cycles` is
+//! called, which makes tests deterministic. See `jack_sys::fake` for what the fake supports.
+//!
+//! `ProcessHarness` builds on it to test a `ProcessHandler` on its own: it feeds synthetic audio
+//! and MIDI to the ports of the handler and records what the handler writes.
 
 pub use crate::client::{
-    AsyncClient, Client, ClientOptions, ClientStatus, ClosureProcessHandler, CycleTimes,
-    InternalClientID, NotificationHandler, ProcessHandler, ProcessScope, CLIENT_NAME_SIZE,
+    AsyncClient, ChannelNotificationHandler, Client, ClientInfo, ClientOptions, ClientStatus,
+    ClosureProcessHandler, CycleTimes, InternalClient, InternalClientID, InternalClientOptions,
+    Notification, NotificationHandler, NotificationMask, NotificationReceiver, PanicPolicy,
+    ProcessHandler, ProcessLoop, ProcessScope, ProcessThread, RecvNotification, Supervisor,
+    XrunEvent, XrunHistory, XrunRecorder, CLIENT_NAME_SIZE,
+};
+#[doc(hidden)]
+pub use crate::client::{
+    internal_client_finish, internal_client_initialize, InternalClientFinish,
 };
 pub use crate::jack_enums::{Control, Error, LatencyType};
 pub use crate::port::{
@@ -44,6 +60,12 @@ pub use crate::port::{
 };
 pub use crate::primitive_types::{Frames, PortId, Time};
 pub use crate::ringbuffer::{RingBuffer, RingBufferReader, RingBufferWriter};
+pub use crate::session::{
+    ClosureSessionHandler, SessionCommand, SessionEvent, SessionEventType, SessionFlags,
+    SessionHandler,
+};
+#[cfg(not(target_os = "windows"))]
+pub use crate::threads::{clear_thread_creator_hook, set_thread_creator_hook, RealtimeThread};
 pub use crate::transport::{
     Transport, TransportBBT, TransportBBTValidationError, TransportPosition, TransportState,
     TransportStatePosition,
@@ -57,6 +79,9 @@ pub use jack_sys;
 #[cfg(feature = "metadata")]
 pub use crate::properties::*;
 
+#[cfg(feature = "harness")]
+pub use crate::testing::ProcessHarness;
+
 /// Create and manage client connections to a JACK server.
 mod client;
 
@@ -74,12 +99,23 @@ mod port;
 /// Platform independent types.
 mod primitive_types;
 
+/// Session management.
+mod session;
+
+/// Threads created by JACK.
+#[cfg(not(target_os = "windows"))]
+mod threads;
+
 /// Transport.
 mod transport;
 
 /// Properties
 mod properties;
 
+/// Running process handlers on the fake server.
+#[cfg(feature = "harness")]
+mod testing;
+
 /// Return JACK's current system time in microseconds, using the JACK clock
 /// source.
 pub fn get_time() -> primitive_types::Time {
@@ -97,6 +133,7 @@ mod test {
     }
 
     #[test]
+    #[cfg_attr(feature = "fake", ignore = "the clock of the fake server only advances with cycles")]
     fn time_is_monotonically_increasing() {
         let initial_t = get_time();
         thread::sleep(time::Duration::from_millis(100));
@@ -98,7 +98,9 @@ mod test {
     use crossbeam_channel::bounded;
 
     use super::*;
+    use crate::client::run_cycles_on_test;
     use crate::{Client, ClientOptions, ClosureProcessHandler, Control};
+    use std::time;
 
     fn open_test_client(name: &str) -> Client {
         Client::new(name, ClientOptions::NO_START_SERVER).unwrap().0
@@ -142,6 +144,7 @@ mod test {
         ac.as_client()
             .connect_ports_by_name("port_audio_crw:ob", "port_audio_crw:ib")
             .unwrap();
+        run_cycles_on_test(ac.as_client(), time::Duration::from_millis(100));
         assert!(
             did_succeed.iter().any(|b| b),
             "input port does not have expected data"
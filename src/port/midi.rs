@@ -213,6 +213,7 @@ impl<'a> MidiWriter<'a> {
 mod test {
     use super::*;
     use crate::client::Client;
+    use crate::client::run_cycles_on_test;
     use crate::client::ClosureProcessHandler;
     use crate::client::ProcessHandler;
     use crate::jack_enums::Control;
@@ -223,7 +224,7 @@ mod test {
     use std::iter::Iterator;
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Mutex;
-    use std::{thread, time};
+    use std::time;
 
     fn open_test_client(name: &str) -> Client {
         Client::new(name, ClientOptions::NO_START_SERVER).unwrap().0
@@ -353,7 +354,7 @@ mod test {
             .unwrap();
 
         // check correctness
-        thread::sleep(time::Duration::from_millis(400));
+        run_cycles_on_test(ac.as_client(), time::Duration::from_millis(400));
         assert!(
             did_succeed.iter().any(|b| b),
             "input port does not have expected data"
@@ -382,6 +383,7 @@ mod test {
             .unwrap();
 
         // check correctness
+        run_cycles_on_test(ac.as_client(), time::Duration::from_millis(100));
         assert!(PMCGMES_MAX_EVENT_SIZE.load(Ordering::Relaxed) > 0);
         ac.deactivate().unwrap();
     }
@@ -419,6 +421,7 @@ mod test {
             .unwrap();
 
         // check correctness
+        run_cycles_on_test(ac.as_client(), time::Duration::from_millis(100));
         assert_eq!(
             *PMCEMES_WRITE_RESULT.lock().unwrap(),
             Err(Error::NotEnoughSpace)
@@ -471,7 +474,7 @@ mod test {
         ac.as_client()
             .connect_ports_by_name("port_midi_iter:op", "port_midi_iter:ip")
             .unwrap();
-        thread::sleep(time::Duration::from_millis(200));
+        run_cycles_on_test(ac.as_client(), time::Duration::from_millis(200));
         ac.deactivate().unwrap();
 
         // check correctness
@@ -516,7 +519,7 @@ mod test {
 
         let ac = c.activate_async((), processor).unwrap();
         connector.connect(ac.as_client());
-        thread::sleep(time::Duration::from_millis(200));
+        run_cycles_on_test(ac.as_client(), time::Duration::from_millis(200));
 
         let (_, _, processor) = ac.deactivate().unwrap();
         let expected: &[OwnedRawMidi] = &stream[0..2];
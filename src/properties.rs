@@ -2,14 +2,40 @@
 //!
 use j::jack_uuid_t as uuid;
 use jack_sys as j;
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use crate::client::PanicState;
 
 /// A description of a Metadata change describint a creation, change or deletion, its owner
 /// `subject` and `key`.
+///
+/// The key is a `str` by default, or a `CStr` for `PropertyChangeHandler::property_changed_cstr`.
 #[derive(Debug, PartialEq)]
-pub enum PropertyChange<'a> {
-    Created { subject: uuid, key: &'a str },
-    Changed { subject: uuid, key: &'a str },
-    Deleted { subject: uuid, key: &'a str },
+pub enum PropertyChange<'a, K: ?Sized = str> {
+    Created { subject: uuid, key: &'a K },
+    Changed { subject: uuid, key: &'a K },
+    Deleted { subject: uuid, key: &'a K },
+}
+
+impl<'a, K: ?Sized> PropertyChange<'a, K> {
+    /// The key of the property that changed.
+    pub fn key(&self) -> &'a K {
+        match *self {
+            PropertyChange::Created { key, .. }
+            | PropertyChange::Changed { key, .. }
+            | PropertyChange::Deleted { key, .. } => key,
+        }
+    }
+
+    // The same change, for the property with `key`.
+    fn with_key<'b, L: ?Sized>(&self, key: &'b L) -> PropertyChange<'b, L> {
+        match *self {
+            PropertyChange::Created { subject, .. } => PropertyChange::Created { subject, key },
+            PropertyChange::Changed { subject, .. } => PropertyChange::Changed { subject, key },
+            PropertyChange::Deleted { subject, .. } => PropertyChange::Deleted { subject, key },
+        }
+    }
 }
 
 /// A trait for reacting to property changes.
@@ -19,6 +45,22 @@ pub enum PropertyChange<'a> {
 /// * Only used if the `metadata` feature is enabled.
 pub trait PropertyChangeHandler: Send {
     fn property_changed(&mut self, change: &PropertyChange);
+
+    /// Called for every property that changes, with the key exactly as JACK reports it. JACK does
+    /// not require keys to be valid UTF-8.
+    ///
+    /// The default implementation calls `property_changed` with the key converted by
+    /// `CStr::to_string_lossy`.
+    fn property_changed_cstr(&mut self, change: &PropertyChange<CStr>) {
+        let key = change.key().to_string_lossy();
+        self.property_changed(&change.with_key(key.as_ref()))
+    }
+}
+
+#[allow(dead_code)] //dead if we haven't enabled metadata
+pub(crate) struct PropertyContext<H> {
+    pub(crate) handler: H,
+    pub(crate) panics: Arc<PanicState>,
 }
 
 #[allow(dead_code)] //dead if we haven't enabled metadata
@@ -30,15 +72,20 @@ pub(crate) unsafe extern "C" fn property_changed<P>(
 ) where
     P: PropertyChangeHandler,
 {
-    let h: &mut P = &mut *(arg as *mut P);
-    let key_c = std::ffi::CStr::from_ptr(key);
-    let key = key_c.to_str().expect("to convert key to valid str");
-    let c = match change {
-        j::PropertyCreated => PropertyChange::Created { subject, key },
-        j::PropertyDeleted => PropertyChange::Deleted { subject, key },
-        _ => PropertyChange::Changed { subject, key },
-    };
-    h.property_changed(&c);
+    let ctx = &mut *(arg as *mut PropertyContext<P>);
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let h = &mut ctx.handler;
+        let key = CStr::from_ptr(key);
+        let c = match change {
+            j::PropertyCreated => PropertyChange::Created { subject, key },
+            j::PropertyDeleted => PropertyChange::Deleted { subject, key },
+            _ => PropertyChange::Changed { subject, key },
+        };
+        h.property_changed_cstr(&c);
+    }));
+    if let Err(payload) = res {
+        ctx.panics.record("property_changed", payload);
+    }
 }
 
 #[cfg(feature = "metadata")]
@@ -71,6 +118,17 @@ mod metadata {
     /// A map of Metadata `key`s, URI Strings, to `Property`s, value and optional type Strings, for a given subject.
     pub type PropertyMap = HashMap<String, Property>;
 
+    /// A `Property` exactly as JACK stores it. JACK does not require values and types to be valid
+    /// UTF-8.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RawProperty {
+        value: ffi::CString,
+        typ: Option<ffi::CString>,
+    }
+
+    /// A map of Metadata `key`s to `RawProperty`s, exactly as JACK stores them.
+    pub type RawPropertyMap = HashMap<ffi::CString, RawProperty>;
+
     /// Wrap a closure that chan handle a `property_changed` callback.
     /// This is called for every property that changes.
     pub struct ClosurePropertyChangeHandler<F>
@@ -108,37 +166,38 @@ mod metadata {
         }
     }
 
-    //helper to convert to an Option<PropertyMap> and free
+    //helper to copy a string from JACK, or null
+    unsafe fn owned_cstring(s: *const ::libc::c_char) -> Option<ffi::CString> {
+        if s.is_null() {
+            None
+        } else {
+            Some(ffi::CStr::from_ptr(s).to_owned())
+        }
+    }
+
+    //helper to convert a RawPropertyMap into a PropertyMap, for strings that are not valid UTF-8
+    fn lossy_map(map: RawPropertyMap) -> PropertyMap {
+        map.iter()
+            .map(|(key, property)| (key.to_string_lossy().into_owned(), property.into()))
+            .collect()
+    }
+
+    //helper to convert to an Option<RawPropertyMap> and free
     unsafe fn description_to_map_free(
         description: *mut j::jack_description_t,
-    ) -> Option<PropertyMap> {
+    ) -> Option<RawPropertyMap> {
         if description.is_null() {
             None
         } else {
             let des = &*description;
             let mut properties = HashMap::new();
             for prop in std::slice::from_raw_parts(des.properties, des.property_cnt as usize) {
-                let typ = if prop._type.is_null() {
-                    None
-                } else {
-                    Some(
-                        ffi::CStr::from_ptr(prop._type)
-                            .to_str()
-                            .expect("to convert type to str")
-                            .to_string(),
-                    )
-                };
                 properties.insert(
-                    ffi::CStr::from_ptr(prop.key)
-                        .to_str()
-                        .expect("to turn key to str")
-                        .to_string(),
-                    Property::new(
-                        ffi::CStr::from_ptr(prop.data)
-                            .to_str()
-                            .expect("to turn data to str"),
-                        typ,
-                    ),
+                    ffi::CStr::from_ptr(prop.key).to_owned(),
+                    RawProperty {
+                        value: ffi::CStr::from_ptr(prop.data).to_owned(),
+                        typ: owned_cstring(prop._type),
+                    },
                 );
             }
             j::jack_free_description(description, 0);
@@ -172,6 +231,32 @@ mod metadata {
         }
     }
 
+    impl RawProperty {
+        /// Get the "value" of a property.
+        pub fn value(&self) -> &ffi::CStr {
+            &self.value
+        }
+
+        /// Get the "type" of a property, if it has been set.
+        /// Either a MIME type or URI.
+        pub fn typ(&self) -> Option<&ffi::CStr> {
+            self.typ.as_deref()
+        }
+    }
+
+    /// Converts the value and type with `CStr::to_string_lossy`.
+    impl From<&RawProperty> for Property {
+        fn from(property: &RawProperty) -> Self {
+            Property::new(
+                property.value.to_string_lossy(),
+                property
+                    .typ
+                    .as_ref()
+                    .map(|t| t.to_string_lossy().into_owned()),
+            )
+        }
+    }
+
     #[cfg(feature = "metadata")]
     impl Client {
         /// Get a property from a subject.
@@ -180,8 +265,24 @@ mod metadata {
         ///
         /// * `subject` - The subject of the property.
         /// * `key` - The key of the property, a URI String.
+        ///
+        /// # Remarks
+        ///
+        /// * Values and types that are not valid UTF-8 are converted lossily, see
+        ///   `Client::property_get_raw`.
         pub fn property_get(&self, subject: uuid, key: &str) -> Option<Property> {
             let key = ffi::CString::new(key).expect("key to be convert to CString");
+            self.property_get_raw(subject, &key)
+                .map(|property| (&property).into())
+        }
+
+        /// Get a property from a subject, exactly as JACK stores it.
+        ///
+        /// # Arguments
+        ///
+        /// * `subject` - The subject of the property.
+        /// * `key` - The key of the property, a URI String.
+        pub fn property_get_raw(&self, subject: uuid, key: &ffi::CStr) -> Option<RawProperty> {
             let mut value: MaybeUninit<*mut ::libc::c_char> = MaybeUninit::uninit();
             let mut typ: MaybeUninit<*mut ::libc::c_char> = MaybeUninit::uninit();
 
@@ -191,14 +292,10 @@ mod metadata {
                 {
                     let value = value.assume_init();
                     let typ = typ.assume_init();
-                    let r = Some(Property::new(
-                        ffi::CStr::from_ptr(value).to_str().unwrap(),
-                        if typ.is_null() {
-                            None
-                        } else {
-                            Some(ffi::CStr::from_ptr(typ).to_str().unwrap().to_string())
-                        },
-                    ));
+                    let r = owned_cstring(value).map(|value| RawProperty {
+                        value,
+                        typ: owned_cstring(typ),
+                    });
                     j::jack_free(value as _);
                     if !typ.is_null() {
                         j::jack_free(typ as _)
@@ -219,7 +316,18 @@ mod metadata {
         /// # Remarks
         ///
         /// * The Jack API calls this data a 'description'.
+        /// * Keys, values and types that are not valid UTF-8 are converted lossily, see
+        ///   `Client::property_get_subject_raw`.
         pub fn property_get_subject(&self, subject: uuid) -> Option<PropertyMap> {
+            self.property_get_subject_raw(subject).map(lossy_map)
+        }
+
+        /// Get all the properties from a subject, exactly as JACK stores them.
+        ///
+        /// # Arguments
+        ///
+        /// * `subject` - The subject of the properties.
+        pub fn property_get_subject_raw(&self, subject: uuid) -> Option<RawPropertyMap> {
             let mut description: MaybeUninit<j::jack_description_t> = MaybeUninit::uninit();
             unsafe {
                 let _ = j::jack_get_properties(subject, description.as_mut_ptr());
@@ -232,7 +340,18 @@ mod metadata {
         /// # Remarks
         ///
         /// * The Jack API calls these maps 'descriptions'.
+        /// * Keys, values and types that are not valid UTF-8 are converted lossily, see
+        ///   `Client::property_get_all_raw`.
         pub fn property_get_all(&self) -> HashMap<uuid, PropertyMap> {
+            self.property_get_all_raw()
+                .into_iter()
+                .map(|(subject, map)| (subject, lossy_map(map)))
+                .collect()
+        }
+
+        /// Get all the properties from all the subjects with Metadata, exactly as JACK stores
+        /// them.
+        pub fn property_get_all_raw(&self) -> HashMap<uuid, RawPropertyMap> {
             let mut map = HashMap::new();
             let mut descriptions: MaybeUninit<*mut j::jack_description_t> = MaybeUninit::uninit();
             unsafe {
@@ -547,6 +666,23 @@ mod metadata {
             );
         }
 
+        #[test]
+        fn property_changed_cstr_converts_key_lossily() {
+            let (sender, receiver): (Sender<PropertyChangeOwned>, _) = channel();
+            let mut handler = ClosurePropertyChangeHandler::new(move |change| {
+                sender.send(change.into()).unwrap();
+            });
+            let key = ffi::CStr::from_bytes_with_nul(b"caf\xe9\0").unwrap();
+            handler.property_changed_cstr(&PropertyChange::Deleted { subject: 1, key });
+            assert_eq!(
+                receiver.try_recv(),
+                Ok(PropertyChangeOwned::Deleted {
+                    subject: 1,
+                    key: "caf\u{FFFD}".into()
+                })
+            );
+        }
+
         #[test]
         #[should_panic]
         fn double_register() {
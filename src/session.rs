@@ -0,0 +1,422 @@
+//! JACK session management, see the [session API docs](https://jackaudio.org/api/group__SessionClientFunctions.html).
+//!
+//! A session manager asks clients to save their state with `Client::session_notify`. Clients
+//! receive the request as a `SessionEvent` through their `SessionHandler`, and reply with the
+//! command line that restores them.
+use bitflags::bitflags;
+use jack_sys as j;
+use std::borrow::Cow;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Weak};
+use std::{ffi, ptr};
+
+use crate::client::PanicState;
+use crate::{Client, Error};
+
+bitflags! {
+    /// Flags set by a client when replying to a `SessionEvent`.
+    pub struct SessionFlags: j::Enum_JackSessionFlags {
+        /// An error occured while saving.
+        const SAVE_ERROR    = j::JackSessionSaveError;
+
+        /// The client needs to be run in a terminal.
+        const NEED_TERMINAL = j::JackSessionNeedTerminal;
+    }
+}
+
+/// The kind of request a session manager makes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SessionEventType {
+    /// Save the session completely.
+    Save,
+    /// Save the session completely, then quit.
+    SaveAndQuit,
+    /// Save a session template. A template does not include the data the client works on, such
+    /// as recordings.
+    SaveTemplate,
+}
+
+impl SessionEventType {
+    fn to_ffi(self) -> j::jack_session_event_type_t {
+        match self {
+            SessionEventType::Save => j::JackSessionSave,
+            SessionEventType::SaveAndQuit => j::JackSessionSaveAndQuit,
+            SessionEventType::SaveTemplate => j::JackSessionSaveTemplate,
+        }
+    }
+
+    fn from_ffi(event_type: j::jack_session_event_type_t) -> SessionEventType {
+        match event_type {
+            j::JackSessionSaveAndQuit => SessionEventType::SaveAndQuit,
+            j::JackSessionSaveTemplate => SessionEventType::SaveTemplate,
+            _ => SessionEventType::Save,
+        }
+    }
+}
+
+/// A request from a session manager to save the state of a client.
+///
+/// Every event must be answered with `SessionEvent::reply`, the session manager waits for the
+/// reply. It may be moved to another thread to reply once saving is done. An event that is dropped
+/// without a reply is answered with `SessionFlags::SAVE_ERROR`.
+pub struct SessionEvent {
+    event: *mut j::jack_session_event_t,
+    client_ptr: *mut j::jack_client_t,
+    client_life: Weak<()>,
+}
+
+unsafe impl Send for SessionEvent {}
+
+impl SessionEvent {
+    /// The kind of request.
+    pub fn event_type(&self) -> SessionEventType {
+        SessionEventType::from_ffi(unsafe { (*self.event)._type })
+    }
+
+    /// The directory the client should save its state to. It ends with a path separator.
+    ///
+    /// Invalid UTF-8 is replaced as by `CStr::to_string_lossy`.
+    pub fn session_dir(&self) -> Cow<'_, str> {
+        unsafe { ffi::CStr::from_ptr((*self.event).session_dir) }.to_string_lossy()
+    }
+
+    /// The UUID the client should pass to `Client::new_with_session_id` when it is restored.
+    ///
+    /// Invalid UTF-8 is replaced as by `CStr::to_string_lossy`.
+    pub fn client_uuid(&self) -> Cow<'_, str> {
+        unsafe { ffi::CStr::from_ptr((*self.event).client_uuid) }.to_string_lossy()
+    }
+
+    /// Reply to the session manager with the command line that restores this client.
+    ///
+    /// `command_line` may contain `${SESSION_DIR}`, which the session manager replaces with the
+    /// directory the session is restored from.
+    ///
+    /// Returns `Err(Error::ClientIsNoLongerAlive)` if the client was closed, or
+    /// `Err(Error::SessionError)` if JACK fails to deliver the reply. If `command_line` contains a
+    /// null byte, `Err(Error::InvalidCommandLine(command_line))` is returned, and the event is
+    /// answered with `SessionFlags::SAVE_ERROR` as if it was dropped.
+    pub fn reply(mut self, command_line: &str, flags: SessionFlags) -> Result<(), Error> {
+        let command_line_c = ffi::CString::new(command_line)
+            .map_err(|_| Error::InvalidCommandLine(command_line.to_string()))?;
+        let res = self.send_reply(&command_line_c, flags);
+        self.free();
+        res
+    }
+
+    fn send_reply(&mut self, command_line: &ffi::CStr, flags: SessionFlags) -> Result<(), Error> {
+        if self.client_life.upgrade().is_none() {
+            return Err(Error::ClientIsNoLongerAlive);
+        }
+        unsafe {
+            // `jack_session_event_free` releases the command line with `free`.
+            libc::free((*self.event).command_line as *mut libc::c_void);
+            (*self.event).command_line = libc::strdup(command_line.as_ptr());
+            (*self.event).flags = flags.bits();
+            match j::jack_session_reply(self.client_ptr, self.event) {
+                0 => Ok(()),
+                _ => Err(Error::SessionError),
+            }
+        }
+    }
+
+    fn free(&mut self) {
+        unsafe { j::jack_session_event_free(self.event) };
+        self.event = ptr::null_mut();
+    }
+}
+
+impl Drop for SessionEvent {
+    fn drop(&mut self) {
+        if !self.event.is_null() {
+            if let Err(e) = self.send_reply(&ffi::CString::default(), SessionFlags::SAVE_ERROR) {
+                log::error!("failed to reply to dropped session event: {:?}", e);
+            }
+            self.free();
+        }
+    }
+}
+
+impl std::fmt::Debug for SessionEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionEvent")
+            .field("event_type", &self.event_type())
+            .field("session_dir", &self.session_dir())
+            .field("client_uuid", &self.client_uuid())
+            .finish()
+    }
+}
+
+/// A reply collected by `Client::session_notify`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SessionCommand {
+    /// The UUID of the client that replied.
+    pub uuid: String,
+    /// The name of the client that replied.
+    pub client_name: String,
+    /// The command line that restores the client.
+    pub command: String,
+    pub flags: SessionFlags,
+}
+
+/// A trait for reacting to requests from a session manager.
+pub trait SessionHandler: Send {
+    /// Called on the notification thread when a session manager makes a request.
+    fn session_event(&mut self, event: SessionEvent);
+}
+
+/// Wrap a closure that can handle a `session_event` callback.
+pub struct ClosureSessionHandler<F>
+where
+    F: 'static + Send + FnMut(SessionEvent),
+{
+    func: F,
+}
+
+impl<F> ClosureSessionHandler<F>
+where
+    F: 'static + Send + FnMut(SessionEvent),
+{
+    /// Create a new `SessionHandler` from a closure.
+    pub fn new(func: F) -> ClosureSessionHandler<F> {
+        ClosureSessionHandler { func }
+    }
+}
+
+impl<F> SessionHandler for ClosureSessionHandler<F>
+where
+    F: 'static + Send + FnMut(SessionEvent),
+{
+    fn session_event(&mut self, event: SessionEvent) {
+        (self.func)(event)
+    }
+}
+
+pub(crate) struct SessionContext<H> {
+    pub(crate) handler: H,
+    pub(crate) client_ptr: *mut j::jack_client_t,
+    pub(crate) client_life: Weak<()>,
+    pub(crate) panics: Arc<PanicState>,
+}
+
+unsafe impl<H: Send> Send for SessionContext<H> {}
+
+pub(crate) unsafe extern "C" fn session_callback<H>(
+    event: *mut j::jack_session_event_t,
+    arg: *mut ::libc::c_void,
+) where
+    H: SessionHandler,
+{
+    let ctx: &mut SessionContext<H> = &mut *(arg as *mut SessionContext<H>);
+    let event = SessionEvent {
+        event,
+        client_ptr: ctx.client_ptr,
+        client_life: ctx.client_life.clone(),
+    };
+    // A panic drops the event, which replies with `SessionFlags::SAVE_ERROR`.
+    let res = panic::catch_unwind(AssertUnwindSafe(|| ctx.handler.session_event(event)));
+    if let Err(payload) = res {
+        ctx.panics.record("session", payload);
+    }
+}
+
+impl Client {
+    /// Reserve `name` for the client that will be opened with the session UUID `uuid`, see
+    /// `Client::new_with_session_id`. Until then, other clients can not take the name.
+    ///
+    /// Returns `Err(Error::InvalidClientName(name))` if `name` is empty or contains a null byte,
+    /// `Err(Error::ClientNameTooLong(name))` if it is longer than `CLIENT_NAME_SIZE`,
+    /// `Err(Error::InvalidUuid(uuid))` if `uuid` contains a null byte, or
+    /// `Err(Error::ClientNameReservationError(name))` if JACK refuses the reservation, for example
+    /// because the name is already in use.
+    pub fn reserve_client_name(&self, name: &str, uuid: &str) -> Result<(), Error> {
+        if name.len() > *crate::CLIENT_NAME_SIZE {
+            return Err(Error::ClientNameTooLong(name.to_string()));
+        }
+        let name_c = match ffi::CString::new(name) {
+            Ok(name_c) if !name.is_empty() => name_c,
+            _ => return Err(Error::InvalidClientName(name.to_string())),
+        };
+        let uuid_c = match ffi::CString::new(uuid) {
+            Ok(uuid_c) => uuid_c,
+            Err(_) => return Err(Error::InvalidUuid(uuid.to_string())),
+        };
+        match unsafe { j::jack_reserve_client_name(self.raw(), name_c.as_ptr(), uuid_c.as_ptr()) } {
+            0 => Ok(()),
+            _ => Err(Error::ClientNameReservationError(name.to_string())),
+        }
+    }
+
+    /// Send a session request to the client named `target`, or to all clients if `target` is
+    /// `None`, and wait for their replies.
+    ///
+    /// `path` is the directory the session is saved to, it should end with a path separator.
+    ///
+    /// Returns `Err(Error::InvalidClientName(target))` or `Err(Error::InvalidSessionPath(path))`
+    /// if `target` or `path` contain a null byte, and `Err(Error::SessionError)` on failure.
+    pub fn session_notify(
+        &self,
+        target: Option<&str>,
+        event_type: SessionEventType,
+        path: &str,
+    ) -> Result<Vec<SessionCommand>, Error> {
+        let target = target
+            .map(|t| ffi::CString::new(t).map_err(|_| Error::InvalidClientName(t.to_string())))
+            .transpose()?;
+        let path =
+            ffi::CString::new(path).map_err(|_| Error::InvalidSessionPath(path.to_string()))?;
+        unsafe {
+            let commands = j::jack_session_notify(
+                self.raw(),
+                target.as_ref().map_or(ptr::null(), |t| t.as_ptr()),
+                event_type.to_ffi(),
+                path.as_ptr(),
+            );
+            if commands.is_null() {
+                return Err(Error::SessionError);
+            }
+            let mut collected = Vec::new();
+            let mut command = commands;
+            while !(*command).uuid.is_null() {
+                let to_string = |s| ffi::CStr::from_ptr(s).to_string_lossy().into_owned();
+                collected.push(SessionCommand {
+                    uuid: to_string((*command).uuid),
+                    client_name: to_string((*command).client_name),
+                    command: to_string((*command).command),
+                    flags: SessionFlags::from_bits_truncate((*command).flags),
+                });
+                command = command.offset(1);
+            }
+            j::jack_session_commands_free(commands);
+            Ok(collected)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ClientOptions;
+    use crossbeam_channel::bounded;
+    use std::time::Duration;
+
+    #[test]
+    fn session_event_type_roundtrips_through_ffi() {
+        for t in [
+            SessionEventType::Save,
+            SessionEventType::SaveAndQuit,
+            SessionEventType::SaveTemplate,
+        ]
+        .iter()
+        {
+            assert_eq!(SessionEventType::from_ffi(t.to_ffi()), *t);
+        }
+    }
+
+    #[test]
+    fn session_event_replaces_invalid_utf8() {
+        let session_dir = ffi::CString::new(b"/tmp/\xffsession/".to_vec()).unwrap();
+        let client_uuid = ffi::CString::new("1234").unwrap();
+        let mut raw = j::jack_session_event_t {
+            _type: j::JackSessionSave,
+            session_dir: session_dir.as_ptr(),
+            client_uuid: client_uuid.as_ptr(),
+            command_line: ptr::null_mut(),
+            flags: 0,
+            future: 0,
+        };
+        let mut event = SessionEvent {
+            event: &mut raw,
+            client_ptr: ptr::null_mut(),
+            client_life: Weak::new(),
+        };
+        assert_eq!(event.session_dir(), "/tmp/\u{fffd}session/");
+        assert_eq!(event.client_uuid(), "1234");
+        assert!(format!("{:?}", event).contains("\u{fffd}session"));
+        // The event is not owned by JACK, so it must not be freed.
+        event.event = ptr::null_mut();
+    }
+
+    #[test]
+    #[cfg_attr(feature = "fake", ignore = "the fake server does not support sessions")]
+    fn reserve_client_name_validates_name() {
+        let (c, _) = Client::new("session_reserve_client", ClientOptions::NO_START_SERVER).unwrap();
+        let uuid = c.uuid_string();
+        let long_name = "a".repeat(*crate::CLIENT_NAME_SIZE + 1);
+        assert_eq!(
+            c.reserve_client_name(&long_name, &uuid),
+            Err(Error::ClientNameTooLong(long_name.clone()))
+        );
+        assert_eq!(
+            c.reserve_client_name("", &uuid),
+            Err(Error::InvalidClientName("".to_string()))
+        );
+        assert_eq!(
+            c.reserve_client_name("a\0b", &uuid),
+            Err(Error::InvalidClientName("a\0b".to_string()))
+        );
+        assert_eq!(
+            c.reserve_client_name("session_reserve_client", &uuid),
+            Err(Error::ClientNameReservationError(
+                "session_reserve_client".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn reserve_client_name_validates_uuid() {
+        let (c, _) = Client::new("session_reserve_uuid", ClientOptions::NO_START_SERVER).unwrap();
+        assert_eq!(
+            c.reserve_client_name("session_reserve_uuid", "1\02"),
+            Err(Error::InvalidUuid("1\02".to_string()))
+        );
+    }
+
+    #[test]
+    fn session_notify_validates_arguments() {
+        let (c, _) =
+            Client::new("session_notify_validates", ClientOptions::NO_START_SERVER).unwrap();
+        assert_eq!(
+            c.session_notify(Some("a\0b"), SessionEventType::Save, "/tmp/"),
+            Err(Error::InvalidClientName("a\0b".to_string()))
+        );
+        assert_eq!(
+            c.session_notify(None, SessionEventType::Save, "/tmp/\0"),
+            Err(Error::InvalidSessionPath("/tmp/\0".to_string()))
+        );
+    }
+
+    #[test]
+    #[cfg_attr(feature = "fake", ignore = "the fake server does not support sessions")]
+    fn session_notify_collects_replies() {
+        let (mut c1, _) = Client::new("session_client1", ClientOptions::NO_START_SERVER).unwrap();
+        let (c2, _) = Client::new("session_client2", ClientOptions::NO_START_SERVER).unwrap();
+        let (sender, receiver) = bounded(1);
+        c1.register_session_handler(ClosureSessionHandler::new(move |event| {
+            sender
+                .send((event.event_type(), event.client_uuid().to_string()))
+                .unwrap();
+            event
+                .reply("session_client1 --restore", SessionFlags::empty())
+                .unwrap();
+        }))
+        .unwrap();
+        let ac = c1.activate_async((), ()).unwrap();
+
+        let commands = c2
+            .session_notify(Some("session_client1"), SessionEventType::Save, "/tmp/")
+            .unwrap();
+        let (event_type, uuid) = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(event_type, SessionEventType::Save);
+        assert_eq!(uuid, ac.as_client().uuid_string());
+        assert_eq!(
+            commands,
+            vec![SessionCommand {
+                uuid,
+                client_name: "session_client1".to_string(),
+                command: "session_client1 --restore".to_string(),
+                flags: SessionFlags::empty(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::slice;
+
+use jack_sys as j;
+
+use crate::{
+    AudioIn, Client, ClientOptions, Control, Error, Frames, MidiIn, Port, PortFlags, PortSpec,
+    ProcessHandler, ProcessScope, Unowned,
+};
+
+/// MIDI events as pairs of the frame they occur at and their data.
+type MidiEvents = Vec<(Frames, Vec<u8>)>;
+
+/// Calls `ProcessHandler::process` with synthetic port buffers, to test handlers without a JACK
+/// server.
+///
+/// The harness opens a client on the fake server of the current thread, see `jack_sys::fake`.
+/// Creating a harness enables the fake server for the rest of the life of the thread, so clients
+/// of a JACK server must be used on other threads.
+/// Before each cycle, the input ports registered on `ProcessHarness::client` are filled from the
+/// signals and MIDI events given to the harness, and after it, whatever the handler wrote to the
+/// output ports is recorded. Ports are referred to by their short names. Frames are counted from
+/// the start of the first cycle, and each cycle processes `Client::buffer_size` frames. Ports
+/// that are neither audio nor MIDI ports are left alone.
+///
+/// Only `process` is called, the other callbacks of the handler are not. `ProcessScope` reports
+/// the clock of the fake server, which the harness does not advance.
+///
+/// # Example
+/// ```
+/// let mut harness = jack::ProcessHarness::new("gain").unwrap();
+/// let input = harness.client().register_port("in", jack::AudioIn::default()).unwrap();
+/// let mut output = harness.client().register_port("out", jack::AudioOut::default()).unwrap();
+/// let mut gain = jack::ClosureProcessHandler::new(move |_, ps| {
+///     for (o, i) in output.as_mut_slice(ps).iter_mut().zip(input.as_slice(ps)) {
+///         *o = 2.0 * i;
+///     }
+///     jack::Control::Continue
+/// });
+///
+/// harness.set_audio_input("in", &[0.25, 0.5]);
+/// harness.process(&mut gain, 1);
+/// assert_eq!(&harness.audio_output("out").unwrap()[..3], &[0.5, 1.0, 0.0]);
+/// ```
+pub struct ProcessHarness {
+    client: Client,
+    frames_processed: Frames,
+    audio_inputs: HashMap<String, Vec<f32>>,
+    midi_inputs: HashMap<String, MidiEvents>,
+    audio_outputs: HashMap<String, Vec<f32>>,
+    midi_outputs: HashMap<String, MidiEvents>,
+}
+
+impl ProcessHarness {
+    /// Open a client named `name` on the fake server of the current thread.
+    ///
+    /// # Remarks
+    /// * This enables the fake server with `jack_sys::fake::enable`, which lasts for the rest of
+    ///   the life of the current thread, also after the harness is dropped.
+    pub fn new(name: &str) -> Result<ProcessHarness, Error> {
+        j::fake::enable();
+        let (client, _status) = Client::new(name, ClientOptions::NO_START_SERVER)?;
+        Ok(ProcessHarness {
+            client,
+            frames_processed: 0,
+            audio_inputs: HashMap::new(),
+            midi_inputs: HashMap::new(),
+            audio_outputs: HashMap::new(),
+            midi_outputs: HashMap::new(),
+        })
+    }
+
+    /// The client to register the ports of the handler with, and to change the buffer size.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The number of frames processed so far.
+    pub fn frames_processed(&self) -> Frames {
+        self.frames_processed
+    }
+
+    /// Feed `signal` to the audio input port `port`, replacing its previous signal. Sample `i` of
+    /// `signal` is read at frame `i`, and frames past its end read as silence.
+    pub fn set_audio_input(&mut self, port: &str, signal: &[f32]) {
+        self.audio_inputs.insert(port.to_string(), signal.to_vec());
+    }
+
+    /// Feed a MIDI event with `bytes` to the MIDI input port `port` at frame `time`.
+    pub fn add_midi_input(&mut self, port: &str, time: Frames, bytes: &[u8]) {
+        let events = self.midi_inputs.entry(port.to_string()).or_default();
+        let index = events.partition_point(|(t, _)| *t <= time);
+        events.insert(index, (time, bytes.to_vec()));
+    }
+
+    /// Everything written to the audio output port `port` so far, or `None` if no cycle ran with
+    /// it.
+    pub fn audio_output(&self, port: &str) -> Option<&[f32]> {
+        self.audio_outputs.get(port).map(Vec::as_slice)
+    }
+
+    /// The MIDI events written to the MIDI output port `port` so far, with the frames they occur
+    /// at, or `None` if no cycle ran with it.
+    pub fn midi_output(&self, port: &str) -> Option<&[(Frames, Vec<u8>)]> {
+        self.midi_outputs.get(port).map(Vec::as_slice)
+    }
+
+    /// Call `handler.process` for `cycles` cycles, or until it returns `Control::Quit`.
+    ///
+    /// Returns what the last call returned, or `Control::Continue` if `cycles` is 0.
+    pub fn process<P: ProcessHandler>(&mut self, handler: &mut P, cycles: usize) -> Control {
+        for _ in 0..cycles {
+            if self.process_cycle(handler) == Control::Quit {
+                return Control::Quit;
+            }
+        }
+        Control::Continue
+    }
+
+    fn process_cycle<P: ProcessHandler>(&mut self, handler: &mut P) -> Control {
+        let n_frames = self.client.buffer_size();
+        let ports = self.ports();
+        for port in &ports {
+            unsafe { self.fill(port, n_frames) };
+        }
+        let ps = unsafe { ProcessScope::from_raw(n_frames, self.client.raw()) };
+        let control = handler.process(&self.client, &ps);
+        for port in &ports {
+            unsafe { self.record(port, n_frames) };
+        }
+        self.frames_processed += n_frames;
+        control
+    }
+
+    /// The ports of the client.
+    fn ports(&self) -> Vec<Port<Unowned>> {
+        self.client
+            .ports(None, None, PortFlags::empty())
+            .iter()
+            .filter_map(|name| self.client.port_by_name(name))
+            .filter(|port| self.client.is_mine(port))
+            .collect()
+    }
+
+    /// Prepare the buffer of `port` for the next cycle. Inputs are filled from the signals and
+    /// events of the harness, and outputs are cleared.
+    unsafe fn fill(&self, port: &Port<Unowned>, n_frames: Frames) {
+        let name = port.short_name().unwrap_or_default();
+        let buffer = port.buffer(n_frames);
+        let start = self.frames_processed;
+        match (port_kind(port), port.flags().contains(PortFlags::IS_INPUT)) {
+            (Some(PortKind::Audio), true) => {
+                let samples = slice::from_raw_parts_mut(buffer as *mut f32, n_frames as usize);
+                let signal = self.audio_inputs.get(&name).map_or(&[][..], Vec::as_slice);
+                for (i, sample) in samples.iter_mut().enumerate() {
+                    *sample = signal.get(start as usize + i).copied().unwrap_or(0.0);
+                }
+            }
+            (Some(PortKind::Audio), false) => {
+                let samples = slice::from_raw_parts_mut(buffer as *mut f32, n_frames as usize);
+                samples.iter_mut().for_each(|s| *s = 0.0);
+            }
+            (Some(PortKind::Midi), is_input) => {
+                j::jack_midi_clear_buffer(buffer);
+                let events = match (is_input, self.midi_inputs.get(&name)) {
+                    (true, Some(events)) => events.as_slice(),
+                    _ => &[],
+                };
+                let in_cycle = events
+                    .iter()
+                    .filter(|(time, _)| start <= *time && *time < start + n_frames);
+                for (time, bytes) in in_cycle {
+                    j::jack_midi_event_write(buffer, time - start, bytes.as_ptr(), bytes.len());
+                }
+            }
+            (None, _) => (),
+        }
+    }
+
+    /// Record what the handler wrote to `port`, if it is an output.
+    unsafe fn record(&mut self, port: &Port<Unowned>, n_frames: Frames) {
+        if port.flags().contains(PortFlags::IS_INPUT) {
+            return;
+        }
+        let name = port.short_name().unwrap_or_default();
+        let buffer = port.buffer(n_frames);
+        match port_kind(port) {
+            Some(PortKind::Audio) => {
+                let samples = slice::from_raw_parts(buffer as *const f32, n_frames as usize);
+                self.audio_outputs
+                    .entry(name)
+                    .or_default()
+                    .extend_from_slice(samples);
+            }
+            Some(PortKind::Midi) => {
+                let events = self.midi_outputs.entry(name).or_default();
+                for i in 0..j::jack_midi_get_event_count(buffer) {
+                    let mut event = j::jack_midi_event_t::default();
+                    if j::jack_midi_event_get(&mut event, buffer, i) == 0 {
+                        let bytes = slice::from_raw_parts(event.buffer, event.size);
+                        events.push((self.frames_processed + event.time, bytes.to_vec()));
+                    }
+                }
+            }
+            None => (),
+        }
+    }
+}
+
+/// The kinds of ports the harness fills and records.
+enum PortKind {
+    Audio,
+    Midi,
+}
+
+fn port_kind(port: &Port<Unowned>) -> Option<PortKind> {
+    let port_type = port.port_type().ok()?;
+    if port_type == AudioIn.jack_port_type() {
+        Some(PortKind::Audio)
+    } else if port_type == MidiIn.jack_port_type() {
+        Some(PortKind::Midi)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AudioOut, ClosureProcessHandler, MidiIn, MidiOut, RawMidi};
+
+    fn open_test_harness(name: &str) -> ProcessHarness {
+        let harness = ProcessHarness::new(name).unwrap();
+        harness.client().set_buffer_size(4).unwrap();
+        harness
+    }
+
+    #[test]
+    fn harness_feeds_and_records_audio_across_cycles() {
+        let mut harness = open_test_harness("harness_feeds_and_records_audio");
+        let input = harness
+            .client()
+            .register_port("in", AudioIn::default())
+            .unwrap();
+        let mut output = harness
+            .client()
+            .register_port("out", AudioOut::default())
+            .unwrap();
+        let mut gain = ClosureProcessHandler::new(move |_, ps| {
+            for (o, i) in output.as_mut_slice(ps).iter_mut().zip(input.as_slice(ps)) {
+                *o = 2.0 * i;
+            }
+            Control::Continue
+        });
+
+        harness.set_audio_input("in", &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(harness.process(&mut gain, 2), Control::Continue);
+        assert_eq!(harness.frames_processed(), 8);
+        assert_eq!(
+            harness.audio_output("out").unwrap(),
+            &[2.0, 4.0, 6.0, 8.0, 10.0, 12.0, 0.0, 0.0]
+        );
+        assert_eq!(harness.audio_output("in"), None);
+    }
+
+    #[test]
+    fn harness_feeds_and_records_midi_at_their_frames() {
+        let mut harness = open_test_harness("harness_feeds_and_records_midi");
+        let input = harness
+            .client()
+            .register_port("in", MidiIn::default())
+            .unwrap();
+        let mut output = harness
+            .client()
+            .register_port("out", MidiOut::default())
+            .unwrap();
+        let mut thru = ClosureProcessHandler::new(move |_, ps| {
+            let mut writer = output.writer(ps);
+            for event in input.iter(ps) {
+                writer.write(&event).unwrap();
+            }
+            Control::Continue
+        });
+
+        harness.add_midi_input("in", 6, &[0x80, 60, 0]);
+        harness.add_midi_input("in", 1, &[0x90, 60, 100]);
+        harness.process(&mut thru, 3);
+        assert_eq!(
+            harness.midi_output("out").unwrap(),
+            &[(1, vec![0x90, 60, 100]), (6, vec![0x80, 60, 0])]
+        );
+    }
+
+    struct OtherOut;
+
+    unsafe impl PortSpec for OtherOut {
+        fn jack_port_type(&self) -> &str {
+            "other"
+        }
+
+        fn jack_flags(&self) -> PortFlags {
+            PortFlags::IS_OUTPUT
+        }
+
+        fn jack_buffer_size(&self) -> libc::c_ulong {
+            16
+        }
+    }
+
+    #[test]
+    fn harness_skips_ports_of_other_types() {
+        let mut harness = open_test_harness("harness_skips_ports_of_other_types");
+        let _other = harness.client().register_port("other", OtherOut).unwrap();
+        harness.process(&mut (), 1);
+        assert_eq!(harness.audio_output("other"), None);
+        assert_eq!(harness.midi_output("other"), None);
+    }
+
+    #[test]
+    fn harness_stops_on_quit() {
+        let mut harness = open_test_harness("harness_stops_on_quit");
+        let mut output = harness
+            .client()
+            .register_port("out", MidiOut::default())
+            .unwrap();
+        let mut cycles = 0;
+        let mut handler = ClosureProcessHandler::new(move |_, ps| {
+            cycles += 1;
+            let note = RawMidi {
+                time: 0,
+                bytes: &[0x90, cycles, 100],
+            };
+            output.writer(ps).write(&note).unwrap();
+            match cycles {
+                2 => Control::Quit,
+                _ => Control::Continue,
+            }
+        });
+
+        assert_eq!(harness.process(&mut handler, 5), Control::Quit);
+        assert_eq!(harness.frames_processed(), 8);
+        assert_eq!(
+            harness.midi_output("out").unwrap(),
+            &[(0, vec![0x90, 1, 100]), (4, vec![0x90, 2, 100])]
+        );
+    }
+}
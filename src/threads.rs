@@ -0,0 +1,288 @@
+//! Threads created and scheduled by JACK.
+use jack_sys as j;
+use lazy_static::lazy_static;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::{fmt, thread};
+
+use crate::{Client, Error};
+
+type ThreadResult<T> = Arc<Mutex<Option<thread::Result<T>>>>;
+
+type ThreadCreatorHook = Arc<dyn Fn() + Send + Sync>;
+
+lazy_static! {
+    static ref THREAD_CREATOR_HOOK: RwLock<Option<ThreadCreatorHook>> = RwLock::new(None);
+}
+
+/// A handle to a thread spawned with `Client::spawn_realtime_thread`.
+///
+/// The thread is detached if the handle is dropped without calling `RealtimeThread::join`.
+pub struct RealtimeThread<T> {
+    native: j::jack_native_thread_t,
+    client_ptr: *mut j::jack_client_t,
+    client_life: Weak<()>,
+    result: ThreadResult<T>,
+    joined: bool,
+}
+
+unsafe impl<T: Send> Send for RealtimeThread<T> {}
+unsafe impl<T: Send> Sync for RealtimeThread<T> {}
+
+impl<T> RealtimeThread<T> {
+    /// Wait for the thread to finish and return the value returned by its closure.
+    ///
+    /// If the closure panicked, the panic is resumed on the calling thread. Returns
+    /// `Err(Error::ThreadError)` if the thread could not be joined.
+    pub fn join(mut self) -> Result<T, Error> {
+        self.joined = true;
+        let res = unsafe {
+            match self.client_life.upgrade() {
+                Some(_) => j::jack_client_stop_thread(self.client_ptr, self.native),
+                None => libc::pthread_join(self.native, std::ptr::null_mut()),
+            }
+        };
+        if res != 0 {
+            return Err(Error::ThreadError);
+        }
+        match self.result.lock().unwrap().take() {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => Err(Error::ThreadError),
+        }
+    }
+
+    /// Returns `true` once the closure of the thread has returned.
+    pub fn is_finished(&self) -> bool {
+        self.result.lock().unwrap().is_some()
+    }
+}
+
+impl<T> Drop for RealtimeThread<T> {
+    fn drop(&mut self) {
+        if !self.joined {
+            unsafe { libc::pthread_detach(self.native) };
+        }
+    }
+}
+
+impl<T> fmt::Debug for RealtimeThread<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RealtimeThread")
+            .field("is_finished", &self.is_finished())
+            .finish()
+    }
+}
+
+unsafe extern "C" fn thread_start(arg: *mut libc::c_void) -> *mut libc::c_void {
+    let start: Box<Box<dyn FnOnce() + Send>> = Box::from_raw(arg as *mut _);
+    start();
+    std::ptr::null_mut()
+}
+
+/// Run `hook` at the start of every thread JACK creates from now on, before JACK runs any code
+/// on it.
+///
+/// This covers the process and notification threads of clients activated afterwards, as well as
+/// threads spawned with `Client::spawn_realtime_thread`. It is useful to name threads, pin them to
+/// CPUs, or set floating point flags such as FTZ/DAZ before the first callback. The hook is
+/// process-global and replaces any previously set hook.
+///
+/// Returns `Err(Error::WeakFunctionNotFound)` if the JACK library does not support custom thread
+/// creators.
+pub fn set_thread_creator_hook<F>(hook: F) -> Result<(), Error>
+where
+    F: 'static + Fn() + Send + Sync,
+{
+    *THREAD_CREATOR_HOOK.write().unwrap() = Some(Arc::new(hook));
+    unsafe { j::jack_set_thread_creator(Some(create_thread)) }
+        .ok_or(Error::WeakFunctionNotFound("jack_set_thread_creator"))
+}
+
+/// Remove the hook set by `set_thread_creator_hook`, and let JACK create threads by itself again.
+pub fn clear_thread_creator_hook() {
+    *THREAD_CREATOR_HOOK.write().unwrap() = None;
+    unsafe { j::jack_set_thread_creator(None) };
+}
+
+struct HookedStart {
+    hook: Option<ThreadCreatorHook>,
+    function: extern "C" fn(*mut libc::c_void) -> *mut libc::c_void,
+    arg: *mut libc::c_void,
+}
+
+extern "C" fn hooked_start(arg: *mut libc::c_void) -> *mut libc::c_void {
+    let HookedStart {
+        hook,
+        function,
+        arg,
+    } = *unsafe { Box::from_raw(arg as *mut HookedStart) };
+    if let Some(hook) = hook {
+        if panic::catch_unwind(AssertUnwindSafe(|| hook())).is_err() {
+            log::error!("thread creator hook panicked");
+        }
+    }
+    // JACK may end the thread with `pthread_cancel` or `pthread_exit` while `function` runs, so
+    // nothing that has to be dropped is alive from here on.
+    function(arg)
+}
+
+unsafe extern "C" fn create_thread(
+    thread: *mut libc::pthread_t,
+    attr: *const libc::pthread_attr_t,
+    function: Option<extern "C" fn(*mut libc::c_void) -> *mut libc::c_void>,
+    arg: *mut libc::c_void,
+) -> libc::c_int {
+    let function = match function {
+        Some(f) => f,
+        None => return libc::EINVAL,
+    };
+    let start = Box::into_raw(Box::new(HookedStart {
+        hook: THREAD_CREATOR_HOOK.read().unwrap().clone(),
+        function,
+        arg,
+    }));
+    let res = libc::pthread_create(thread, attr, hooked_start, start as *mut libc::c_void);
+    if res != 0 {
+        drop(Box::from_raw(start));
+    }
+    res
+}
+
+impl Client {
+    /// Switch the calling thread to real-time scheduling at `priority`.
+    ///
+    /// To run just below the process thread, use `Client::real_time_priority` minus one.
+    ///
+    /// Returns `Err(Error::RealTimeSchedulingError)` on failure, for example when the process does
+    /// not have permission to use real-time scheduling.
+    pub fn acquire_real_time_scheduling(&self, priority: i32) -> Result<(), Error> {
+        match unsafe { j::jack_acquire_real_time_scheduling(libc::pthread_self(), priority) } {
+            0 => Ok(()),
+            _ => Err(Error::RealTimeSchedulingError),
+        }
+    }
+
+    /// Switch the calling thread back to normal scheduling.
+    ///
+    /// Returns `Err(Error::RealTimeSchedulingError)` on failure.
+    pub fn drop_real_time_scheduling(&self) -> Result<(), Error> {
+        match unsafe { j::jack_drop_real_time_scheduling(libc::pthread_self()) } {
+            0 => Ok(()),
+            _ => Err(Error::RealTimeSchedulingError),
+        }
+    }
+
+    // The priority `offset` away from `priority`, kept within what clients may use.
+    fn relative_real_time_priority(&self, priority: i32, offset: i32) -> i32 {
+        let max = self.max_real_time_priority().unwrap_or(priority);
+        (priority + offset).min(max).max(1)
+    }
+
+    /// Spawn `f` on a new thread created by JACK.
+    ///
+    /// If JACK is running with real-time scheduling, the thread runs with real-time scheduling at
+    /// the priority of the process thread plus `priority_offset`, capped at the maximum priority
+    /// allowed for clients. Use a negative offset for workers that should not preempt the process
+    /// thread. Otherwise, the thread runs with normal scheduling.
+    ///
+    /// Panics in `f` are caught before they reach JACK, and resumed by `RealtimeThread::join`.
+    ///
+    /// Returns `Err(Error::ThreadError)` if the thread could not be created.
+    pub fn spawn_realtime_thread<F, T>(
+        &self,
+        priority_offset: i32,
+        f: F,
+    ) -> Result<RealtimeThread<T>, Error>
+    where
+        F: 'static + Send + FnOnce() -> T,
+        T: 'static + Send,
+    {
+        let result: ThreadResult<T> = Arc::default();
+        let thread_result = result.clone();
+        let start: Box<dyn FnOnce() + Send> = Box::new(move || {
+            let res = panic::catch_unwind(AssertUnwindSafe(f));
+            *thread_result.lock().unwrap() = Some(res);
+        });
+        let arg = Box::into_raw(Box::new(start));
+
+        let (priority, realtime) = match self.real_time_priority() {
+            Some(p) => (self.relative_real_time_priority(p, priority_offset), 1),
+            None => (0, 0),
+        };
+        let mut native: j::jack_native_thread_t = unsafe { std::mem::zeroed() };
+        let res = unsafe {
+            j::jack_client_create_thread(
+                self.raw(),
+                &mut native,
+                priority,
+                realtime,
+                Some(thread_start),
+                arg as *mut libc::c_void,
+            )
+        };
+        if res != 0 {
+            drop(unsafe { Box::from_raw(arg) });
+            return Err(Error::ThreadError);
+        }
+        Ok(RealtimeThread {
+            native,
+            client_ptr: self.raw(),
+            client_life: self.life(),
+            result,
+            joined: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ClientOptions;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn open_test_client(name: &str) -> Client {
+        Client::new(name, ClientOptions::NO_START_SERVER).unwrap().0
+    }
+
+    #[test]
+    #[cfg_attr(feature = "fake", ignore = "the fake server does not create threads")]
+    fn realtime_thread_returns_value_on_join() {
+        let c = open_test_client("realtime_thread_returns_value");
+        let t = c.spawn_realtime_thread(-1, || 40 + 2).unwrap();
+        assert_eq!(t.join(), Ok(42));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "fake", ignore = "the fake server does not create threads")]
+    #[should_panic(expected = "worker panicked")]
+    fn realtime_thread_resumes_panic_on_join() {
+        let c = open_test_client("realtime_thread_resumes_panic");
+        let t = c
+            .spawn_realtime_thread(-1, || panic!("worker panicked"))
+            .unwrap();
+        let _: () = t.join().unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(feature = "fake", ignore = "the fake server has no realtime scheduling")]
+    fn realtime_scheduling_can_be_dropped() {
+        let c = open_test_client("realtime_scheduling_can_be_dropped");
+        assert_eq!(c.drop_real_time_scheduling(), Ok(()));
+    }
+
+    #[test]
+    #[cfg_attr(feature = "fake", ignore = "the fake server does not create threads")]
+    fn thread_creator_hook_runs_on_jack_threads() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let hook_count = count.clone();
+        set_thread_creator_hook(move || {
+            hook_count.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        let c = open_test_client("thread_creator_hook_runs");
+        c.spawn_realtime_thread(-1, || ()).unwrap().join().unwrap();
+        clear_thread_creator_hook();
+        assert!(count.load(Ordering::Relaxed) > 0);
+    }
+}
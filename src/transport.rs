@@ -164,6 +164,36 @@ impl Transport {
         )
     }
 
+    /// Set the timeout value for slow-sync clients, in microseconds.
+    ///
+    /// # Remarks
+    ///
+    /// * This timeout prevents unresponsive slow-sync clients from completely halting the
+    ///   transport mechanism. The default is two seconds.
+    /// * When the timeout expires, the transport starts rolling, even if some slow-sync clients
+    ///   are still unready.
+    /// * This affects the whole server, not just this client.
+    pub fn set_sync_timeout(&self, timeout: Time) -> Result<()> {
+        Self::result_from_ffi(
+            self.with_client(|ptr| unsafe { j::jack_set_sync_timeout(ptr, timeout) }),
+            (),
+        )
+    }
+
+    /// Give up being the timebase master.
+    ///
+    /// # Remarks
+    ///
+    /// * Only the current timebase master can release it, otherwise `Err(Error::TimebaseError)`
+    ///   is returned. See `AsyncClient::become_timebase_master`.
+    /// * The `ProcessHandler::timebase` callback is no longer called after this returns.
+    pub fn release_timebase(&self) -> Result<()> {
+        match self.with_client(|ptr| unsafe { j::jack_release_timebase(ptr) })? {
+            0 => Ok(()),
+            _ => Err(crate::Error::TimebaseError),
+        }
+    }
+
     //helper to convert to TransportState
     pub(crate) fn state_from_ffi(state: j::jack_transport_state_t) -> TransportState {
         match state {